@@ -0,0 +1,189 @@
+//! What a device says about itself before anyone connects to it.
+
+use crate::assigned::{self, NamedUuid, APPLE_COMPANY_ID, HUAMI_COMPANY_ID};
+use crate::backend::Peripheral;
+use crate::discovery::Discovered;
+use crate::gatt;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use uuid::Uuid;
+
+/// Advertisement data of a device, with the assigned numbers resolved.
+#[derive(Debug, Clone, Serialize)]
+pub struct Advertisement {
+    pub address: String,
+    /// Platform identifier, the only stable handle on hosts that hide addresses.
+    pub id: String,
+    pub name: Option<String>,
+    /// Signal strength, in dBm.
+    pub rssi: Option<i16>,
+    /// Transmit power the device announces, in dBm.
+    pub tx_power: Option<i16>,
+    pub services: Vec<NamedUuid>,
+    pub service_data: Vec<ServiceData>,
+    pub manufacturer_data: Vec<ManufacturerData>,
+}
+
+impl Advertisement {
+    /// Collects the advertisement of a discovered device, sorted by identifier.
+    pub fn new<P: Peripheral>(device: &Discovered<P>) -> Advertisement {
+        let properties = &device.properties;
+        let mut services: Vec<NamedUuid> = properties
+            .services
+            .iter()
+            .copied()
+            .map(NamedUuid::new)
+            .collect();
+        services.sort_by_key(|s| s.uuid);
+        let mut service_data: Vec<ServiceData> = properties
+            .service_data
+            .iter()
+            .map(|(uuid, data)| ServiceData {
+                service: NamedUuid::new(*uuid),
+                data: data.clone(),
+            })
+            .collect();
+        service_data.sort_by_key(|s| s.service.uuid);
+        let mut manufacturer_data: Vec<ManufacturerData> = properties
+            .manufacturer_data
+            .iter()
+            .map(|(id, data)| ManufacturerData::new(*id, data.clone()))
+            .collect();
+        manufacturer_data.sort_by_key(|m| m.company_id);
+        Advertisement {
+            address: properties.address.to_string(),
+            id: device.peripheral.id().to_string(),
+            name: properties.local_name.clone(),
+            rssi: properties.rssi,
+            tx_power: properties.tx_power_level,
+            services,
+            service_data,
+            manufacturer_data,
+        }
+    }
+}
+
+/// Data a device advertises on behalf of one of its services.
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceData {
+    #[serde(flatten)]
+    pub service: NamedUuid,
+    #[serde(serialize_with = "serialize_hex")]
+    pub data: Vec<u8>,
+}
+
+impl fmt::Display for ServiceData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.service, gatt::hex(&self.data))
+    }
+}
+
+/// Manufacturer specific data, decoded when the company's format is known.
+#[derive(Debug, Clone, Serialize)]
+pub struct ManufacturerData {
+    pub company_id: u16,
+    pub company: Option<&'static str>,
+    #[serde(serialize_with = "serialize_hex")]
+    pub data: Vec<u8>,
+    pub decoded: Option<ManufacturerPayload>,
+}
+
+impl ManufacturerData {
+    pub fn new(company_id: u16, data: Vec<u8>) -> ManufacturerData {
+        ManufacturerData {
+            company_id,
+            company: assigned::registry().company_name(company_id),
+            decoded: ManufacturerPayload::decode(company_id, &data),
+            data,
+        }
+    }
+}
+
+impl fmt::Display for ManufacturerData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.company {
+            Some(company) => write!(f, "{} ({:#06x})", company, self.company_id)?,
+            None => write!(f, "{:#06x}", self.company_id)?,
+        }
+        write!(f, ": {}", gatt::hex(&self.data))?;
+        if let Some(decoded) = &self.decoded {
+            write!(f, " [{}]", decoded)?;
+        }
+        Ok(())
+    }
+}
+
+/// Manufacturer data in a format the tool understands.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "format", rename_all = "snake_case")]
+pub enum ManufacturerPayload {
+    /// Huami watches end their manufacturer data with their public address.
+    Huami { device_address: String },
+    /// An Apple iBeacon frame.
+    IBeacon {
+        proximity_uuid: Uuid,
+        major: u16,
+        minor: u16,
+        /// Signal strength expected at one metre, in dBm.
+        measured_power: i8,
+    },
+}
+
+impl ManufacturerPayload {
+    /// Length of the address trailing Huami manufacturer data.
+    const HUAMI_ADDRESS_LEN: usize = 6;
+    /// Type and length bytes that open an iBeacon frame.
+    const IBEACON_PREFIX: [u8; 2] = [0x02, 0x15];
+    const IBEACON_LEN: usize = 23;
+
+    pub fn decode(company_id: u16, data: &[u8]) -> Option<ManufacturerPayload> {
+        match company_id {
+            HUAMI_COMPANY_ID if data.len() >= Self::HUAMI_ADDRESS_LEN => {
+                let address = &data[data.len() - Self::HUAMI_ADDRESS_LEN..];
+                Some(ManufacturerPayload::Huami {
+                    device_address: address
+                        .iter()
+                        .map(|b| format!("{:02X}", b))
+                        .collect::<Vec<_>>()
+                        .join(":"),
+                })
+            }
+            APPLE_COMPANY_ID
+                if data.len() == Self::IBEACON_LEN && data[..2] == Self::IBEACON_PREFIX =>
+            {
+                Some(ManufacturerPayload::IBeacon {
+                    proximity_uuid: Uuid::from_slice(&data[2..18]).ok()?,
+                    major: u16::from_be_bytes([data[18], data[19]]),
+                    minor: u16::from_be_bytes([data[20], data[21]]),
+                    measured_power: data[22] as i8,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ManufacturerPayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManufacturerPayload::Huami { device_address } => {
+                write!(f, "Huami device {}", device_address)
+            }
+            ManufacturerPayload::IBeacon {
+                proximity_uuid,
+                major,
+                minor,
+                measured_power,
+            } => write!(
+                f,
+                "iBeacon {} major {} minor {} at {} dBm",
+                proximity_uuid, major, minor, measured_power
+            ),
+        }
+    }
+}
+
+/// Serializes bytes the way the tool prints them, see [`gatt::hex`].
+pub(crate) fn serialize_hex<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&gatt::hex(bytes))
+}
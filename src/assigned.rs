@@ -0,0 +1,504 @@
+//! Names of Bluetooth SIG assigned numbers and well known vendor UUIDs.
+//!
+//! A built-in [`Registry`] covers the SIG services, characteristics,
+//! descriptors, company identifiers and appearance values the tool is likely
+//! to meet, plus the vendor UUIDs of the Nordic UART Service, Huami watches
+//! and InfiniTime. More names can be added from a file, one per line:
+//!
+//! ```text
+//! # kind           identifier                             name
+//! service          FEE7                                   Tencent
+//! characteristic   00000020-0000-3512-2118-0009af100700   Huami Chunked Transfer
+//! company          0x0157                                 Zepp Health
+//! appearance       0x00C2                                 Smartwatch
+//! ```
+//!
+//! Output goes through the process-wide registry, see [`install`] and [`registry`].
+
+use crate::error::{Result, SmartwatchError};
+use crate::gatt;
+use crate::selector::parse_company_id;
+use btleplug::api::bleuuid::{uuid_from_u16, uuid_from_u32};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+/// Company identifier of Anhui Huami, the maker of Amazfit and Zepp watches.
+pub const HUAMI_COMPANY_ID: u16 = 0x0157;
+/// Company identifier of Apple, whose manufacturer data carries iBeacon frames.
+pub const APPLE_COMPANY_ID: u16 = 0x004C;
+
+/// Appearance values only name a category in their upper ten bits.
+const APPEARANCE_CATEGORY_MASK: u16 = !0x3F;
+
+const SERVICES: &[(u16, &str)] = &[
+    (0x1800, "Generic Access"),
+    (0x1801, "Generic Attribute"),
+    (0x1802, "Immediate Alert"),
+    (0x1803, "Link Loss"),
+    (0x1804, "Tx Power"),
+    (0x1805, "Current Time"),
+    (0x1806, "Reference Time Update"),
+    (0x1807, "Next DST Change"),
+    (0x1808, "Glucose"),
+    (0x1809, "Health Thermometer"),
+    (0x180A, "Device Information"),
+    (0x180D, "Heart Rate"),
+    (0x180E, "Phone Alert Status"),
+    (0x180F, "Battery"),
+    (0x1810, "Blood Pressure"),
+    (0x1811, "Alert Notification"),
+    (0x1812, "Human Interface Device"),
+    (0x1813, "Scan Parameters"),
+    (0x1814, "Running Speed and Cadence"),
+    (0x1815, "Automation IO"),
+    (0x1816, "Cycling Speed and Cadence"),
+    (0x1818, "Cycling Power"),
+    (0x1819, "Location and Navigation"),
+    (0x181A, "Environmental Sensing"),
+    (0x181B, "Body Composition"),
+    (0x181C, "User Data"),
+    (0x181D, "Weight Scale"),
+    (0x181E, "Bond Management"),
+    (0x181F, "Continuous Glucose Monitoring"),
+    (0x1820, "Internet Protocol Support"),
+    (0x1821, "Indoor Positioning"),
+    (0x1822, "Pulse Oximeter"),
+    (0x1823, "HTTP Proxy"),
+    (0x1824, "Transport Discovery"),
+    (0x1825, "Object Transfer"),
+    (0x1826, "Fitness Machine"),
+    (0x1827, "Mesh Provisioning"),
+    (0x1828, "Mesh Proxy"),
+    (0x1829, "Reconnection Configuration"),
+    (0x183A, "Insulin Delivery"),
+    (0x183B, "Binary Sensor"),
+    (0x183C, "Emergency Configuration"),
+    (0x183E, "Physical Activity Monitor"),
+    (0xFD6F, "Exposure Notification"),
+    (0xFE59, "Nordic Secure DFU"),
+    (0xFE95, "Xiaomi"),
+    (0xFEE0, "Huami"),
+    (0xFEE1, "Huami"),
+];
+
+const CHARACTERISTICS: &[(u16, &str)] = &[
+    (0x2A00, "Device Name"),
+    (0x2A01, "Appearance"),
+    (0x2A02, "Peripheral Privacy Flag"),
+    (0x2A03, "Reconnection Address"),
+    (0x2A04, "Peripheral Preferred Connection Parameters"),
+    (0x2A05, "Service Changed"),
+    (0x2A06, "Alert Level"),
+    (0x2A07, "Tx Power Level"),
+    (0x2A08, "Date Time"),
+    (0x2A09, "Day of Week"),
+    (0x2A0A, "Day Date Time"),
+    (0x2A0C, "Exact Time 256"),
+    (0x2A0D, "DST Offset"),
+    (0x2A0E, "Time Zone"),
+    (0x2A0F, "Local Time Information"),
+    (0x2A11, "Time with DST"),
+    (0x2A12, "Time Accuracy"),
+    (0x2A13, "Time Source"),
+    (0x2A14, "Reference Time Information"),
+    (0x2A16, "Time Update Control Point"),
+    (0x2A17, "Time Update State"),
+    (0x2A18, "Glucose Measurement"),
+    (0x2A19, "Battery Level"),
+    (0x2A1C, "Temperature Measurement"),
+    (0x2A1D, "Temperature Type"),
+    (0x2A1E, "Intermediate Temperature"),
+    (0x2A21, "Measurement Interval"),
+    (0x2A22, "Boot Keyboard Input Report"),
+    (0x2A23, "System ID"),
+    (0x2A24, "Model Number String"),
+    (0x2A25, "Serial Number String"),
+    (0x2A26, "Firmware Revision String"),
+    (0x2A27, "Hardware Revision String"),
+    (0x2A28, "Software Revision String"),
+    (0x2A29, "Manufacturer Name String"),
+    (
+        0x2A2A,
+        "IEEE 11073-20601 Regulatory Certification Data List",
+    ),
+    (0x2A2B, "Current Time"),
+    (0x2A31, "Scan Refresh"),
+    (0x2A32, "Boot Keyboard Output Report"),
+    (0x2A33, "Boot Mouse Input Report"),
+    (0x2A35, "Blood Pressure Measurement"),
+    (0x2A36, "Intermediate Cuff Pressure"),
+    (0x2A37, "Heart Rate Measurement"),
+    (0x2A38, "Body Sensor Location"),
+    (0x2A39, "Heart Rate Control Point"),
+    (0x2A3F, "Alert Status"),
+    (0x2A40, "Ringer Control Point"),
+    (0x2A41, "Ringer Setting"),
+    (0x2A42, "Alert Category ID Bit Mask"),
+    (0x2A43, "Alert Category ID"),
+    (0x2A44, "Alert Notification Control Point"),
+    (0x2A45, "Unread Alert Status"),
+    (0x2A46, "New Alert"),
+    (0x2A47, "Supported New Alert Category"),
+    (0x2A48, "Supported Unread Alert Category"),
+    (0x2A49, "Blood Pressure Feature"),
+    (0x2A4A, "HID Information"),
+    (0x2A4B, "Report Map"),
+    (0x2A4C, "HID Control Point"),
+    (0x2A4D, "Report"),
+    (0x2A4E, "Protocol Mode"),
+    (0x2A4F, "Scan Interval Window"),
+    (0x2A50, "PnP ID"),
+    (0x2A51, "Glucose Feature"),
+    (0x2A52, "Record Access Control Point"),
+    (0x2A53, "RSC Measurement"),
+    (0x2A54, "RSC Feature"),
+    (0x2A55, "SC Control Point"),
+    (0x2A5B, "CSC Measurement"),
+    (0x2A5C, "CSC Feature"),
+    (0x2A5D, "Sensor Location"),
+    (0x2A6D, "Pressure"),
+    (0x2A6E, "Temperature"),
+    (0x2A6F, "Humidity"),
+    (0x2A98, "Weight"),
+    (0x2A9D, "Weight Measurement"),
+    (0x2AA6, "Central Address Resolution"),
+    (0x2AC9, "Resolvable Private Address Only"),
+    (0x2B29, "Client Supported Features"),
+    (0x2B2A, "Database Hash"),
+    (0x2B3A, "Server Supported Features"),
+];
+
+const DESCRIPTORS: &[(u16, &str)] = &[
+    (0x2900, "Characteristic Extended Properties"),
+    (0x2901, "Characteristic User Description"),
+    (0x2902, "Client Characteristic Configuration"),
+    (0x2903, "Server Characteristic Configuration"),
+    (0x2904, "Characteristic Presentation Format"),
+    (0x2905, "Characteristic Aggregate Format"),
+    (0x2906, "Valid Range"),
+    (0x2907, "External Report Reference"),
+    (0x2908, "Report Reference"),
+    (0x2909, "Number of Digitals"),
+    (0x290A, "Value Trigger Setting"),
+    (0x290B, "Environmental Sensing Configuration"),
+    (0x290C, "Environmental Sensing Measurement"),
+    (0x290D, "Environmental Sensing Trigger Setting"),
+    (0x290E, "Time Trigger Setting"),
+];
+
+/// Vendor services and characteristics, outside of the SIG base UUID.
+const VENDOR_UUIDS: &[(UuidKind, u128, &str)] = &[
+    (
+        UuidKind::Service,
+        0x6e400001_b5a3_f393_e0a9_e50e24dcca9e,
+        "Nordic UART",
+    ),
+    (
+        UuidKind::Characteristic,
+        0x6e400002_b5a3_f393_e0a9_e50e24dcca9e,
+        "Nordic UART RX",
+    ),
+    (
+        UuidKind::Characteristic,
+        0x6e400003_b5a3_f393_e0a9_e50e24dcca9e,
+        "Nordic UART TX",
+    ),
+    (
+        UuidKind::Characteristic,
+        0x00000003_0000_3512_2118_0009af100700,
+        "Huami Configuration",
+    ),
+    (
+        UuidKind::Characteristic,
+        0x00000006_0000_3512_2118_0009af100700,
+        "Huami Battery Info",
+    ),
+    (
+        UuidKind::Characteristic,
+        0x00000007_0000_3512_2118_0009af100700,
+        "Huami Realtime Steps",
+    ),
+    (
+        UuidKind::Characteristic,
+        0x00000008_0000_3512_2118_0009af100700,
+        "Huami User Settings",
+    ),
+    (
+        UuidKind::Characteristic,
+        0x00000009_0000_3512_2118_0009af100700,
+        "Huami Auth",
+    ),
+    (
+        UuidKind::Characteristic,
+        0x00000010_0000_3512_2118_0009af100700,
+        "Huami Device Event",
+    ),
+    (
+        UuidKind::Service,
+        0x00001530_0000_3512_2118_0009af100700,
+        "Huami Firmware",
+    ),
+    (
+        UuidKind::Characteristic,
+        0x00001531_0000_3512_2118_0009af100700,
+        "Huami Firmware Control",
+    ),
+    (
+        UuidKind::Characteristic,
+        0x00001532_0000_3512_2118_0009af100700,
+        "Huami Firmware Data",
+    ),
+    (
+        UuidKind::Service,
+        0x00000000_78fc_48fe_8e23_433b3a1942d0,
+        "InfiniTime Music",
+    ),
+    (
+        UuidKind::Service,
+        0x00010000_78fc_48fe_8e23_433b3a1942d0,
+        "InfiniTime Navigation",
+    ),
+    (
+        UuidKind::Service,
+        0x00030000_78fc_48fe_8e23_433b3a1942d0,
+        "InfiniTime Motion",
+    ),
+    (
+        UuidKind::Characteristic,
+        0x00030001_78fc_48fe_8e23_433b3a1942d0,
+        "InfiniTime Step Count",
+    ),
+    (
+        UuidKind::Characteristic,
+        0x00030002_78fc_48fe_8e23_433b3a1942d0,
+        "InfiniTime Raw Motion",
+    ),
+];
+
+const COMPANIES: &[(u16, &str)] = &[
+    (0x0006, "Microsoft"),
+    (0x000F, "Broadcom"),
+    (APPLE_COMPANY_ID, "Apple"),
+    (0x0059, "Nordic Semiconductor"),
+    (0x0075, "Samsung Electronics"),
+    (0x0087, "Garmin International"),
+    (HUAMI_COMPANY_ID, "Anhui Huami (Zepp)"),
+    (0x00E0, "Google"),
+    (0x038F, "Xiaomi"),
+];
+
+const APPEARANCES: &[(u16, &str)] = &[
+    (0x0000, "Unknown"),
+    (0x0040, "Phone"),
+    (0x0080, "Computer"),
+    (0x00C0, "Watch"),
+    (0x00C1, "Sports Watch"),
+    (0x00C2, "Smartwatch"),
+    (0x0100, "Clock"),
+    (0x0140, "Display"),
+    (0x0180, "Remote Control"),
+    (0x01C0, "Eye-glasses"),
+    (0x0200, "Tag"),
+    (0x0240, "Keyring"),
+    (0x0280, "Media Player"),
+    (0x02C0, "Barcode Scanner"),
+    (0x0300, "Thermometer"),
+    (0x0340, "Heart Rate Sensor"),
+    (0x0341, "Heart Rate Belt"),
+    (0x0380, "Blood Pressure"),
+    (0x03C0, "Human Interface Device"),
+    (0x03C1, "Keyboard"),
+    (0x03C2, "Mouse"),
+    (0x03C3, "Joystick"),
+    (0x03C4, "Gamepad"),
+    (0x0400, "Glucose Meter"),
+    (0x0440, "Running Walking Sensor"),
+    (0x0480, "Cycling"),
+    (0x0C40, "Pulse Oximeter"),
+    (0x0C41, "Fingertip Pulse Oximeter"),
+    (0x0C42, "Wrist Worn Pulse Oximeter"),
+    (0x0C80, "Weight Scale"),
+];
+
+/// What a named UUID identifies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UuidKind {
+    Service,
+    Characteristic,
+    Descriptor,
+}
+
+/// Names of UUIDs, company identifiers and appearance values.
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    uuids: HashMap<Uuid, (UuidKind, String)>,
+    companies: HashMap<u16, String>,
+    appearances: HashMap<u16, String>,
+}
+
+impl Registry {
+    /// The names the tool knows without being told.
+    pub fn builtin() -> Registry {
+        let mut registry = Registry::default();
+        for (kind, table) in [
+            (UuidKind::Service, SERVICES),
+            (UuidKind::Characteristic, CHARACTERISTICS),
+            (UuidKind::Descriptor, DESCRIPTORS),
+        ] {
+            for (alias, name) in table {
+                registry.add_uuid(kind, uuid_from_u16(*alias), name);
+            }
+        }
+        for (kind, uuid, name) in VENDOR_UUIDS {
+            registry.add_uuid(*kind, Uuid::from_u128(*uuid), name);
+        }
+        for (id, name) in COMPANIES {
+            registry.companies.insert(*id, name.to_string());
+        }
+        for (value, name) in APPEARANCES {
+            registry.appearances.insert(*value, name.to_string());
+        }
+        registry
+    }
+
+    pub fn add_uuid(&mut self, kind: UuidKind, uuid: Uuid, name: &str) {
+        self.uuids.insert(uuid, (kind, name.to_string()));
+    }
+
+    /// Adds the names listed in a file, replacing the known ones they clash with.
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        let text = fs::read_to_string(path).map_err(SmartwatchError::io(path))?;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            self.add_line(line).map_err(|message| {
+                SmartwatchError::io(path)(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", number + 1, message),
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    fn add_line(&mut self, line: &str) -> std::result::Result<(), String> {
+        let mut fields = line.split_whitespace();
+        let (Some(kind), Some(id)) = (fields.next(), fields.next()) else {
+            return Err(String::from("expected a kind, an identifier and a name"));
+        };
+        let name = fields.collect::<Vec<_>>().join(" ");
+        if name.is_empty() {
+            return Err(format!("no name given for {}", id));
+        }
+        match kind {
+            "service" => self.add_uuid(UuidKind::Service, gatt::parse_uuid(id)?, &name),
+            "characteristic" => {
+                self.add_uuid(UuidKind::Characteristic, gatt::parse_uuid(id)?, &name)
+            }
+            "descriptor" => self.add_uuid(UuidKind::Descriptor, gatt::parse_uuid(id)?, &name),
+            "company" => {
+                self.companies.insert(parse_company_id(id)?, name);
+            }
+            "appearance" => {
+                let value = parse_company_id(id)
+                    .map_err(|_| format!("invalid appearance value {:?}", id))?;
+                self.appearances.insert(value, name);
+            }
+            _ => {
+                return Err(format!(
+                    "unknown kind {:?}: expected service, characteristic, descriptor, company or appearance",
+                    kind
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    pub fn uuid_name(&self, uuid: &Uuid) -> Option<&str> {
+        self.uuids.get(uuid).map(|(_, name)| name.as_str())
+    }
+
+    pub fn uuid_kind(&self, uuid: &Uuid) -> Option<UuidKind> {
+        self.uuids.get(uuid).map(|(kind, _)| *kind)
+    }
+
+    /// Name of the company a manufacturer data identifier belongs to.
+    pub fn company_name(&self, id: u16) -> Option<&str> {
+        self.companies.get(&id).map(String::as_str)
+    }
+
+    /// Name of an appearance value, or of its category when the value itself
+    /// is not known.
+    pub fn appearance_name(&self, value: u16) -> Option<&str> {
+        self.appearances
+            .get(&value)
+            .or_else(|| self.appearances.get(&(value & APPEARANCE_CATEGORY_MASK)))
+            .map(String::as_str)
+    }
+}
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+/// Makes `registry` the one used for output, returning `false` if the
+/// registry was already in use.
+pub fn install(registry: Registry) -> bool {
+    REGISTRY.set(registry).is_ok()
+}
+
+/// The registry used for output, the built-in one unless another was installed.
+pub fn registry() -> &'static Registry {
+    REGISTRY.get_or_init(Registry::builtin)
+}
+
+/// Shortest form of `uuid`: its 16 or 32-bit alias such as `0x2A2B` when it
+/// derives from the Bluetooth base UUID, the full UUID otherwise.
+pub fn short_uuid(uuid: &Uuid) -> String {
+    let alias = (uuid.as_u128() >> 96) as u32;
+    if uuid_from_u32(alias) != *uuid {
+        uuid.to_string()
+    } else if alias <= u32::from(u16::MAX) {
+        format!("0x{:04X}", alias)
+    } else {
+        format!("0x{:08X}", alias)
+    }
+}
+
+/// `uuid` as printed throughout the tool, e.g. `Current Time (0x2A2B)`.
+pub fn describe(uuid: &Uuid) -> String {
+    NamedUuid::new(*uuid).to_string()
+}
+
+/// A UUID with the name it is known by, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct NamedUuid {
+    pub uuid: Uuid,
+    pub name: Option<&'static str>,
+}
+
+impl NamedUuid {
+    /// Looks `uuid` up in the [`registry`].
+    pub fn new(uuid: Uuid) -> NamedUuid {
+        NamedUuid {
+            uuid,
+            name: registry().uuid_name(&uuid),
+        }
+    }
+}
+
+impl fmt::Display for NamedUuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(name) => write!(f, "{} ({})", name, short_uuid(&self.uuid)),
+            None => write!(f, "{}", short_uuid(&self.uuid)),
+        }
+    }
+}
@@ -0,0 +1,735 @@
+//! An in-memory backend, scripted by tests.
+//!
+//! A [`FakePeripheral`] holds a GATT table with values, and can be told to fail
+//! connection attempts, answer reads from a script, expect particular writes,
+//! send notifications once subscribed and take its time over every operation.
+//! A [`FakeAdapter`] advertises its peripherals while scanning. Clones share
+//! their state, so a test keeps a handle on a peripheral to inspect what the
+//! code under test did to it.
+//!
+//! Delays are measured with tokio's clock, so tests can run with paused time.
+//! The module is only built with the `fake` feature, which the integration
+//! tests of this crate turn on.
+
+use super::{
+    Adapter, AdapterEvent, BackendResult, EventStream, Manager, NotificationStream, Peripheral,
+};
+use btleplug::api::{
+    BDAddr, CharPropFlags, Characteristic, Descriptor, PeripheralProperties, Service,
+    ValueNotification, WriteType,
+};
+use futures::channel::mpsc::{self, UnboundedSender};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::time;
+use uuid::Uuid;
+
+/// A manager with a fixed list of adapters.
+#[derive(Debug, Clone, Default)]
+pub struct FakeManager {
+    adapters: Vec<FakeAdapter>,
+}
+
+impl FakeManager {
+    pub fn new(adapters: Vec<FakeAdapter>) -> FakeManager {
+        FakeManager { adapters }
+    }
+}
+
+impl Manager for FakeManager {
+    type Adapter = FakeAdapter;
+
+    async fn adapters(&self) -> BackendResult<Vec<FakeAdapter>> {
+        Ok(self.adapters.clone())
+    }
+}
+
+#[derive(Debug, Default)]
+struct AdapterState {
+    peripherals: Vec<FakePeripheral>,
+    /// Peripherals that advertise but are gone when looked up.
+    vanished: HashSet<BDAddr>,
+    listeners: Vec<UnboundedSender<AdapterEvent<BDAddr>>>,
+    /// Incremented by every start and stop, so that adverts of a previous
+    /// scan are not delivered.
+    scan: u64,
+    scanning: bool,
+    scans: usize,
+}
+
+/// An adapter that advertises the peripherals it was given while scanning.
+#[derive(Debug, Clone, Default)]
+pub struct FakeAdapter {
+    state: Arc<Mutex<AdapterState>>,
+}
+
+impl FakeAdapter {
+    pub fn new() -> FakeAdapter {
+        FakeAdapter::default()
+    }
+
+    pub fn with_peripheral(self, peripheral: FakePeripheral) -> FakeAdapter {
+        lock(&self.state).peripherals.push(peripheral);
+        self
+    }
+
+    /// Adds a peripheral whose adverts arrive but that can no longer be found
+    /// when looked up, as when it leaves range right after advertising.
+    pub fn with_vanishing_peripheral(self, peripheral: FakePeripheral) -> FakeAdapter {
+        lock(&self.state).vanished.insert(peripheral.address());
+        self.with_peripheral(peripheral)
+    }
+
+    /// Whether a scan is in progress.
+    pub fn is_scanning(&self) -> bool {
+        lock(&self.state).scanning
+    }
+
+    /// How many scans were started.
+    pub fn scans(&self) -> usize {
+        lock(&self.state).scans
+    }
+
+    /// Reports that `peripheral` dropped its connection.
+    pub fn drop_connection(&self, peripheral: &FakePeripheral) {
+        lock(&peripheral.state).connected = false;
+        self.emit(AdapterEvent::DeviceDisconnected(peripheral.address()));
+    }
+
+    fn emit(&self, event: AdapterEvent<BDAddr>) {
+        lock(&self.state)
+            .listeners
+            .retain(|listener| listener.unbounded_send(event.clone()).is_ok());
+    }
+
+    /// Sends `event` unless the scan it belongs to has ended.
+    fn emit_during(&self, scan: u64, event: AdapterEvent<BDAddr>) -> bool {
+        let mut state = lock(&self.state);
+        if !state.scanning || state.scan != scan {
+            return false;
+        }
+        state
+            .listeners
+            .retain(|listener| listener.unbounded_send(event.clone()).is_ok());
+        true
+    }
+
+    /// Plays the adverts of `peripheral` for scan number `scan`.
+    async fn advertise(self, scan: u64, peripheral: FakePeripheral) {
+        let (after, updates) = {
+            let state = lock(&peripheral.state);
+            (state.advertise_after, state.advert_updates.clone())
+        };
+        let id = peripheral.address();
+        time::sleep(after).await;
+        if !self.emit_during(scan, AdapterEvent::DeviceDiscovered(id)) {
+            return;
+        }
+        for update in updates {
+            time::sleep(update.after).await;
+            let event = {
+                let mut state = lock(&peripheral.state);
+                if let Some(rssi) = update.rssi {
+                    state.properties.rssi = Some(rssi);
+                }
+                match update.data {
+                    AdvertData::Manufacturer(company_id, data) => {
+                        state
+                            .properties
+                            .manufacturer_data
+                            .insert(company_id, data.clone());
+                        AdapterEvent::ManufacturerData {
+                            id,
+                            manufacturer_data: HashMap::from([(company_id, data)]),
+                        }
+                    }
+                    AdvertData::Service(uuid, data) => {
+                        state.properties.service_data.insert(uuid, data.clone());
+                        AdapterEvent::ServiceData {
+                            id,
+                            service_data: HashMap::from([(uuid, data)]),
+                        }
+                    }
+                    AdvertData::None => AdapterEvent::DeviceUpdated(id),
+                }
+            };
+            if !self.emit_during(scan, event) {
+                return;
+            }
+        }
+    }
+}
+
+impl Adapter for FakeAdapter {
+    type Peripheral = FakePeripheral;
+
+    async fn events(&self) -> BackendResult<EventStream<BDAddr>> {
+        let (sender, receiver) = mpsc::unbounded();
+        lock(&self.state).listeners.push(sender);
+        Ok(Box::pin(receiver))
+    }
+
+    async fn start_scan(&self) -> BackendResult<()> {
+        let (scan, peripherals) = {
+            let mut state = lock(&self.state);
+            state.scan += 1;
+            state.scanning = true;
+            state.scans += 1;
+            (state.scan, state.peripherals.clone())
+        };
+        for peripheral in peripherals {
+            tokio::spawn(self.clone().advertise(scan, peripheral));
+        }
+        Ok(())
+    }
+
+    async fn stop_scan(&self) -> BackendResult<()> {
+        let mut state = lock(&self.state);
+        state.scan += 1;
+        state.scanning = false;
+        Ok(())
+    }
+
+    async fn peripheral(&self, id: &BDAddr) -> BackendResult<FakePeripheral> {
+        let state = lock(&self.state);
+        if state.vanished.contains(id) {
+            return Err(btleplug::Error::DeviceNotFound);
+        }
+        state
+            .peripherals
+            .iter()
+            .find(|peripheral| peripheral.address() == *id)
+            .cloned()
+            .ok_or(btleplug::Error::DeviceNotFound)
+    }
+}
+
+/// What an advert sent while scanning carries.
+#[derive(Debug, Clone)]
+enum AdvertData {
+    None,
+    Manufacturer(u16, Vec<u8>),
+    Service(Uuid, Vec<u8>),
+}
+
+#[derive(Debug, Clone)]
+struct ScriptedAdvert {
+    /// Delay since the previous advert.
+    after: Duration,
+    rssi: Option<i16>,
+    data: AdvertData,
+}
+
+/// A write the peripheral received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedWrite {
+    pub characteristic: Uuid,
+    pub data: Vec<u8>,
+    pub write_type: WriteType,
+}
+
+/// A write a test expects, `data` of `None` accepting any bytes.
+#[derive(Debug, Clone)]
+struct ExpectedWrite {
+    characteristic: Uuid,
+    data: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Default)]
+struct PeripheralState {
+    properties: PeripheralProperties,
+    advertise_after: Duration,
+    advert_updates: Vec<ScriptedAdvert>,
+    /// Services with their characteristics, in the order they were added.
+    table: Vec<(Uuid, Vec<Characteristic>)>,
+    values: HashMap<Uuid, Vec<u8>>,
+    descriptor_values: HashMap<(Uuid, Uuid), Vec<u8>>,
+    /// Replies to the next reads, taking precedence over the values.
+    scripted_reads: HashMap<Uuid, VecDeque<Result<Vec<u8>, String>>>,
+    write_failures: HashMap<Uuid, String>,
+    expected_writes: VecDeque<ExpectedWrite>,
+    unexpected_writes: Vec<RecordedWrite>,
+    writes: Vec<RecordedWrite>,
+    scripted_notifications: HashMap<Uuid, Vec<(Duration, Vec<u8>)>>,
+    subscribed: HashSet<Uuid>,
+    listeners: Vec<UnboundedSender<ValueNotification>>,
+    connected: bool,
+    discovered: bool,
+    connect_attempts: usize,
+    connect_failures: usize,
+    connect_delay: Duration,
+    latency: Duration,
+}
+
+/// A peripheral with a scripted GATT table.
+#[derive(Debug, Clone)]
+pub struct FakePeripheral {
+    state: Arc<Mutex<PeripheralState>>,
+}
+
+impl FakePeripheral {
+    pub fn new(address: BDAddr) -> FakePeripheral {
+        let state = PeripheralState {
+            properties: PeripheralProperties {
+                address,
+                ..PeripheralProperties::default()
+            },
+            ..PeripheralState::default()
+        };
+        FakePeripheral {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    fn configure(self, change: impl FnOnce(&mut PeripheralState)) -> FakePeripheral {
+        change(&mut lock(&self.state));
+        self
+    }
+
+    pub fn with_name(self, name: &str) -> FakePeripheral {
+        self.configure(|state| state.properties.local_name = Some(name.to_string()))
+    }
+
+    pub fn with_rssi(self, rssi: i16) -> FakePeripheral {
+        self.configure(|state| state.properties.rssi = Some(rssi))
+    }
+
+    pub fn with_tx_power(self, tx_power: i16) -> FakePeripheral {
+        self.configure(|state| state.properties.tx_power_level = Some(tx_power))
+    }
+
+    /// Advertises `service` in the list of services.
+    pub fn with_advertised_service(self, service: Uuid) -> FakePeripheral {
+        self.configure(|state| state.properties.services.push(service))
+    }
+
+    pub fn with_manufacturer_data(self, company_id: u16, data: &[u8]) -> FakePeripheral {
+        self.configure(|state| {
+            state
+                .properties
+                .manufacturer_data
+                .insert(company_id, data.to_vec());
+        })
+    }
+
+    pub fn with_service_data(self, service: Uuid, data: &[u8]) -> FakePeripheral {
+        self.configure(|state| {
+            state.properties.service_data.insert(service, data.to_vec());
+        })
+    }
+
+    /// Shows up only `after` the scan started.
+    pub fn advertising_after(self, after: Duration) -> FakePeripheral {
+        self.configure(|state| state.advertise_after = after)
+    }
+
+    /// Advertises again `after` the previous advert, with no new data.
+    pub fn with_advert(self, after: Duration, rssi: Option<i16>) -> FakePeripheral {
+        self.with_advert_update(after, rssi, AdvertData::None)
+    }
+
+    /// Advertises new manufacturer data `after` the previous advert.
+    pub fn with_manufacturer_advert(
+        self,
+        after: Duration,
+        company_id: u16,
+        data: &[u8],
+    ) -> FakePeripheral {
+        self.with_advert_update(
+            after,
+            None,
+            AdvertData::Manufacturer(company_id, data.to_vec()),
+        )
+    }
+
+    /// Advertises new service data `after` the previous advert.
+    pub fn with_service_advert(
+        self,
+        after: Duration,
+        service: Uuid,
+        data: &[u8],
+    ) -> FakePeripheral {
+        self.with_advert_update(after, None, AdvertData::Service(service, data.to_vec()))
+    }
+
+    fn with_advert_update(
+        self,
+        after: Duration,
+        rssi: Option<i16>,
+        data: AdvertData,
+    ) -> FakePeripheral {
+        self.configure(|state| {
+            state
+                .advert_updates
+                .push(ScriptedAdvert { after, rssi, data })
+        })
+    }
+
+    /// Adds a characteristic to `service`, creating the service if need be.
+    pub fn with_characteristic(
+        self,
+        service: Uuid,
+        uuid: Uuid,
+        properties: CharPropFlags,
+        value: &[u8],
+    ) -> FakePeripheral {
+        self.configure(|state| {
+            let characteristic = Characteristic {
+                uuid,
+                service_uuid: service,
+                properties,
+                descriptors: BTreeSet::new(),
+            };
+            match state.table.iter_mut().find(|(s, _)| *s == service) {
+                Some((_, characteristics)) => characteristics.push(characteristic),
+                None => state.table.push((service, vec![characteristic])),
+            }
+            state.values.insert(uuid, value.to_vec());
+        })
+    }
+
+    /// Adds a descriptor to a characteristic added before.
+    ///
+    /// # Panics
+    ///
+    /// If there is no such characteristic.
+    pub fn with_descriptor(self, characteristic: Uuid, uuid: Uuid, value: &[u8]) -> FakePeripheral {
+        self.configure(|state| {
+            let target = state
+                .table
+                .iter_mut()
+                .flat_map(|(_, characteristics)| characteristics.iter_mut())
+                .find(|c| c.uuid == characteristic)
+                .expect("descriptor added to an unknown characteristic");
+            target.descriptors.insert(Descriptor {
+                uuid,
+                service_uuid: target.service_uuid,
+                characteristic_uuid: characteristic,
+            });
+            state
+                .descriptor_values
+                .insert((characteristic, uuid), value.to_vec());
+        })
+    }
+
+    /// Answers the next reads of `characteristic` with `replies` in turn, an
+    /// `Err` failing the read with that message.
+    pub fn with_reads(
+        self,
+        characteristic: Uuid,
+        replies: Vec<Result<Vec<u8>, String>>,
+    ) -> FakePeripheral {
+        self.configure(|state| {
+            state
+                .scripted_reads
+                .entry(characteristic)
+                .or_default()
+                .extend(replies)
+        })
+    }
+
+    /// Expects a write of `data` to `characteristic`, or of anything when
+    /// `data` is `None`; expected writes must come in the order given.
+    pub fn expect_write(self, characteristic: Uuid, data: Option<&[u8]>) -> FakePeripheral {
+        self.configure(|state| {
+            state.expected_writes.push_back(ExpectedWrite {
+                characteristic,
+                data: data.map(<[u8]>::to_vec),
+            })
+        })
+    }
+
+    /// Fails every write to `characteristic` with `message`.
+    pub fn failing_writes(self, characteristic: Uuid, message: &str) -> FakePeripheral {
+        self.configure(|state| {
+            state
+                .write_failures
+                .insert(characteristic, message.to_string());
+        })
+    }
+
+    /// Sends `values` from `characteristic` once subscribed, each after its delay
+    /// since the previous one.
+    pub fn with_notifications(
+        self,
+        characteristic: Uuid,
+        values: Vec<(Duration, Vec<u8>)>,
+    ) -> FakePeripheral {
+        self.configure(|state| {
+            state.scripted_notifications.insert(characteristic, values);
+        })
+    }
+
+    /// Fails the next `count` connection attempts.
+    pub fn failing_connects(self, count: usize) -> FakePeripheral {
+        self.configure(|state| state.connect_failures = count)
+    }
+
+    /// Takes `delay` to establish a connection.
+    pub fn with_connect_delay(self, delay: Duration) -> FakePeripheral {
+        self.configure(|state| state.connect_delay = delay)
+    }
+
+    /// Takes `latency` over every read, write and subscription.
+    pub fn with_latency(self, latency: Duration) -> FakePeripheral {
+        self.configure(|state| state.latency = latency)
+    }
+
+    /// Current value of `characteristic`, including what was written to it.
+    pub fn value(&self, characteristic: Uuid) -> Option<Vec<u8>> {
+        lock(&self.state).values.get(&characteristic).cloned()
+    }
+
+    /// Every write received, in order.
+    pub fn writes(&self) -> Vec<RecordedWrite> {
+        lock(&self.state).writes.clone()
+    }
+
+    pub fn connect_attempts(&self) -> usize {
+        lock(&self.state).connect_attempts
+    }
+
+    pub fn is_subscribed(&self, characteristic: Uuid) -> bool {
+        lock(&self.state).subscribed.contains(&characteristic)
+    }
+
+    /// Sends a notification from `characteristic` now.
+    pub fn notify(&self, characteristic: Uuid, value: &[u8]) {
+        send_notification(&self.state, characteristic, value.to_vec());
+    }
+
+    /// Checks that every expected write came and nothing else was written.
+    pub fn verify(&self) -> Result<(), String> {
+        let state = lock(&self.state);
+        let mut problems: Vec<String> = state
+            .expected_writes
+            .iter()
+            .map(|expected| match &expected.data {
+                Some(data) => format!(
+                    "missing write of {:02X?} to {}",
+                    data, expected.characteristic
+                ),
+                None => format!("missing write to {}", expected.characteristic),
+            })
+            .collect();
+        problems.extend(state.unexpected_writes.iter().map(|write| {
+            format!(
+                "unexpected write of {:02X?} to {}",
+                write.data, write.characteristic
+            )
+        }));
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+
+    /// Waits for the latency, then fails unless connected.
+    async fn operation(&self) -> BackendResult<()> {
+        let latency = lock(&self.state).latency;
+        time::sleep(latency).await;
+        if lock(&self.state).connected {
+            Ok(())
+        } else {
+            Err(btleplug::Error::NotConnected)
+        }
+    }
+
+    fn characteristic_known(state: &PeripheralState, uuid: Uuid) -> BackendResult<()> {
+        let known = state
+            .table
+            .iter()
+            .flat_map(|(_, characteristics)| characteristics)
+            .any(|c| c.uuid == uuid);
+        if known {
+            Ok(())
+        } else {
+            Err(btleplug::Error::NotSupported(format!(
+                "no characteristic {}",
+                uuid
+            )))
+        }
+    }
+}
+
+impl Peripheral for FakePeripheral {
+    type Id = BDAddr;
+
+    fn id(&self) -> BDAddr {
+        self.address()
+    }
+
+    fn address(&self) -> BDAddr {
+        lock(&self.state).properties.address
+    }
+
+    async fn properties(&self) -> BackendResult<Option<PeripheralProperties>> {
+        Ok(Some(lock(&self.state).properties.clone()))
+    }
+
+    fn services(&self) -> BTreeSet<Service> {
+        let state = lock(&self.state);
+        if !state.discovered {
+            return BTreeSet::new();
+        }
+        state
+            .table
+            .iter()
+            .map(|(uuid, characteristics)| Service {
+                uuid: *uuid,
+                primary: true,
+                characteristics: characteristics.iter().cloned().collect(),
+            })
+            .collect()
+    }
+
+    async fn is_connected(&self) -> BackendResult<bool> {
+        Ok(lock(&self.state).connected)
+    }
+
+    async fn connect(&self) -> BackendResult<()> {
+        let delay = {
+            let mut state = lock(&self.state);
+            state.connect_attempts += 1;
+            state.connect_delay
+        };
+        time::sleep(delay).await;
+        let mut state = lock(&self.state);
+        if state.connect_failures > 0 {
+            state.connect_failures -= 1;
+            return Err(btleplug::Error::RuntimeError(String::from(
+                "connection refused",
+            )));
+        }
+        state.connected = true;
+        Ok(())
+    }
+
+    async fn disconnect(&self) -> BackendResult<()> {
+        let mut state = lock(&self.state);
+        state.connected = false;
+        state.subscribed.clear();
+        Ok(())
+    }
+
+    async fn discover_services(&self) -> BackendResult<()> {
+        self.operation().await?;
+        lock(&self.state).discovered = true;
+        Ok(())
+    }
+
+    async fn read(&self, characteristic: &Characteristic) -> BackendResult<Vec<u8>> {
+        self.operation().await?;
+        let mut state = lock(&self.state);
+        Self::characteristic_known(&state, characteristic.uuid)?;
+        if let Some(reply) = state
+            .scripted_reads
+            .get_mut(&characteristic.uuid)
+            .and_then(VecDeque::pop_front)
+        {
+            return reply.map_err(btleplug::Error::RuntimeError);
+        }
+        Ok(state
+            .values
+            .get(&characteristic.uuid)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn write(
+        &self,
+        characteristic: &Characteristic,
+        data: &[u8],
+        write_type: WriteType,
+    ) -> BackendResult<()> {
+        self.operation().await?;
+        let mut state = lock(&self.state);
+        Self::characteristic_known(&state, characteristic.uuid)?;
+        let write = RecordedWrite {
+            characteristic: characteristic.uuid,
+            data: data.to_vec(),
+            write_type,
+        };
+        state.writes.push(write.clone());
+        let expected = state.expected_writes.front().is_some_and(|expected| {
+            expected.characteristic == write.characteristic
+                && expected
+                    .data
+                    .as_ref()
+                    .is_none_or(|data| *data == write.data)
+        });
+        if expected {
+            state.expected_writes.pop_front();
+        } else if !state.expected_writes.is_empty() {
+            state.unexpected_writes.push(write.clone());
+        }
+        if let Some(message) = state.write_failures.get(&characteristic.uuid) {
+            return Err(btleplug::Error::RuntimeError(message.clone()));
+        }
+        state.values.insert(write.characteristic, write.data);
+        Ok(())
+    }
+
+    async fn read_descriptor(&self, descriptor: &Descriptor) -> BackendResult<Vec<u8>> {
+        self.operation().await?;
+        lock(&self.state)
+            .descriptor_values
+            .get(&(descriptor.characteristic_uuid, descriptor.uuid))
+            .cloned()
+            .ok_or_else(|| {
+                btleplug::Error::NotSupported(format!("no descriptor {}", descriptor.uuid))
+            })
+    }
+
+    async fn subscribe(&self, characteristic: &Characteristic) -> BackendResult<()> {
+        self.operation().await?;
+        let scripted = {
+            let mut state = lock(&self.state);
+            Self::characteristic_known(&state, characteristic.uuid)?;
+            state.subscribed.insert(characteristic.uuid);
+            state.scripted_notifications.remove(&characteristic.uuid)
+        };
+        if let Some(values) = scripted {
+            let state = Arc::clone(&self.state);
+            let uuid = characteristic.uuid;
+            tokio::spawn(async move {
+                for (after, value) in values {
+                    time::sleep(after).await;
+                    if !lock(&state).subscribed.contains(&uuid) {
+                        return;
+                    }
+                    send_notification(&state, uuid, value);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    async fn unsubscribe(&self, characteristic: &Characteristic) -> BackendResult<()> {
+        self.operation().await?;
+        lock(&self.state).subscribed.remove(&characteristic.uuid);
+        Ok(())
+    }
+
+    async fn notifications(&self) -> BackendResult<NotificationStream> {
+        let (sender, receiver) = mpsc::unbounded();
+        lock(&self.state).listeners.push(sender);
+        Ok(Box::pin(receiver))
+    }
+}
+
+fn send_notification(state: &Mutex<PeripheralState>, uuid: Uuid, value: Vec<u8>) {
+    let notification = ValueNotification { uuid, value };
+    lock(state)
+        .listeners
+        .retain(|listener| listener.unbounded_send(notification.clone()).is_ok());
+}
+
+/// Locks `state`; a test that panicked while holding the lock has failed anyway.
+fn lock<T>(state: &Mutex<T>) -> MutexGuard<'_, T> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,214 @@
+//! The Bluetooth stack the tool talks to, behind traits.
+//!
+//! Everything above this module is written against [`Manager`], [`Adapter`]
+//! and [`Peripheral`] rather than against btleplug directly, so that the same
+//! flows run on real hardware ([`platform`]), on an in-memory stand-in
+//! (`fake`, built with the `fake` feature), on a simulated watch ([`sim`])
+//! and on a recorded trace ([`replay`]). The traits mirror the subset of
+//! btleplug's API the tool uses and keep its value types and error.
+
+use btleplug::api::{
+    BDAddr, Characteristic, Descriptor, PeripheralProperties, Service, ValueNotification, WriteType,
+};
+use futures::stream::Stream;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::hash::Hash;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::str::FromStr;
+use uuid::Uuid;
+
+#[cfg(feature = "fake")]
+pub mod fake;
+pub mod platform;
+pub mod record;
+pub mod replay;
+pub mod sim;
+pub mod trace;
+
+/// Result of a backend operation.
+pub type BackendResult<T> = std::result::Result<T, btleplug::Error>;
+
+/// Stream of the events of an adapter.
+pub type EventStream<I> = Pin<Box<dyn Stream<Item = AdapterEvent<I>> + Send>>;
+
+/// Stream of the notifications of a peripheral.
+pub type NotificationStream = Pin<Box<dyn Stream<Item = ValueNotification> + Send>>;
+
+/// Identifier of the peripherals of an adapter.
+pub type PeripheralId<A> = <<A as Adapter>::Peripheral as Peripheral>::Id;
+
+/// Bluetooth stack picked on the command line: `platform`, `sim:<profile.toml>`
+/// or `replay:<trace.jsonl>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackendSpec {
+    /// The host's Bluetooth stack.
+    Platform,
+    /// The watch simulated from a [`sim::SimProfile`] file.
+    Sim(PathBuf),
+    /// The session recorded in a [`trace`] file.
+    Replay(PathBuf),
+}
+
+impl FromStr for BackendSpec {
+    type Err = String;
+
+    fn from_str(text: &str) -> std::result::Result<BackendSpec, String> {
+        match text.split_once(':') {
+            _ if text == "platform" => Ok(BackendSpec::Platform),
+            Some(("sim", path)) if !path.is_empty() => Ok(BackendSpec::Sim(PathBuf::from(path))),
+            Some(("replay", path)) if !path.is_empty() => {
+                Ok(BackendSpec::Replay(PathBuf::from(path)))
+            }
+            _ => Err(format!(
+                "unknown backend {:?}, expected platform, sim:<profile.toml> or replay:<trace.jsonl>",
+                text
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BackendSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendSpec::Platform => write!(f, "platform"),
+            BackendSpec::Sim(path) => write!(f, "sim:{}", path.display()),
+            BackendSpec::Replay(path) => write!(f, "replay:{}", path.display()),
+        }
+    }
+}
+
+/// What an adapter reports while scanning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdapterEvent<I> {
+    DeviceDiscovered(I),
+    DeviceUpdated(I),
+    DeviceConnected(I),
+    DeviceDisconnected(I),
+    ManufacturerData {
+        id: I,
+        manufacturer_data: HashMap<u16, Vec<u8>>,
+    },
+    ServiceData {
+        id: I,
+        service_data: HashMap<Uuid, Vec<u8>>,
+    },
+    Services {
+        id: I,
+        services: Vec<Uuid>,
+    },
+}
+
+impl<I> AdapterEvent<I> {
+    /// The peripheral the event is about.
+    pub fn id(&self) -> &I {
+        match self {
+            AdapterEvent::DeviceDiscovered(id)
+            | AdapterEvent::DeviceUpdated(id)
+            | AdapterEvent::DeviceConnected(id)
+            | AdapterEvent::DeviceDisconnected(id)
+            | AdapterEvent::ManufacturerData { id, .. }
+            | AdapterEvent::ServiceData { id, .. }
+            | AdapterEvent::Services { id, .. } => id,
+        }
+    }
+
+    /// Whether the event stems from an advertisement.
+    pub fn is_advertisement(&self) -> bool {
+        !matches!(
+            self,
+            AdapterEvent::DeviceConnected(_) | AdapterEvent::DeviceDisconnected(_)
+        )
+    }
+}
+
+/// Entry point of a Bluetooth stack.
+pub trait Manager: Send + Sync {
+    type Adapter: Adapter;
+
+    fn adapters(&self) -> impl Future<Output = BackendResult<Vec<Self::Adapter>>> + Send;
+}
+
+/// A Bluetooth adapter of the host.
+pub trait Adapter: Send + Sync {
+    type Peripheral: Peripheral;
+
+    /// Subscribes to the events of the adapter, from now on.
+    fn events(&self)
+        -> impl Future<Output = BackendResult<EventStream<PeripheralId<Self>>>> + Send;
+
+    fn start_scan(&self) -> impl Future<Output = BackendResult<()>> + Send;
+
+    fn stop_scan(&self) -> impl Future<Output = BackendResult<()>> + Send;
+
+    /// Looks up a peripheral the adapter has seen.
+    fn peripheral(
+        &self,
+        id: &PeripheralId<Self>,
+    ) -> impl Future<Output = BackendResult<Self::Peripheral>> + Send;
+}
+
+/// A remote device.
+pub trait Peripheral: Clone + fmt::Debug + Send + Sync {
+    type Id: Clone + fmt::Debug + fmt::Display + Eq + Hash + Send + Sync + 'static;
+
+    fn id(&self) -> Self::Id;
+
+    fn address(&self) -> BDAddr;
+
+    /// What the device advertised, `None` if it is gone.
+    fn properties(
+        &self,
+    ) -> impl Future<Output = BackendResult<Option<PeripheralProperties>>> + Send;
+
+    /// Services found by the last service discovery.
+    fn services(&self) -> BTreeSet<Service>;
+
+    /// Characteristics of all the discovered services.
+    fn characteristics(&self) -> BTreeSet<Characteristic> {
+        self.services()
+            .into_iter()
+            .flat_map(|service| service.characteristics)
+            .collect()
+    }
+
+    fn is_connected(&self) -> impl Future<Output = BackendResult<bool>> + Send;
+
+    fn connect(&self) -> impl Future<Output = BackendResult<()>> + Send;
+
+    fn disconnect(&self) -> impl Future<Output = BackendResult<()>> + Send;
+
+    fn discover_services(&self) -> impl Future<Output = BackendResult<()>> + Send;
+
+    fn read(
+        &self,
+        characteristic: &Characteristic,
+    ) -> impl Future<Output = BackendResult<Vec<u8>>> + Send;
+
+    fn write(
+        &self,
+        characteristic: &Characteristic,
+        data: &[u8],
+        write_type: WriteType,
+    ) -> impl Future<Output = BackendResult<()>> + Send;
+
+    fn read_descriptor(
+        &self,
+        descriptor: &Descriptor,
+    ) -> impl Future<Output = BackendResult<Vec<u8>>> + Send;
+
+    fn subscribe(
+        &self,
+        characteristic: &Characteristic,
+    ) -> impl Future<Output = BackendResult<()>> + Send;
+
+    fn unsubscribe(
+        &self,
+        characteristic: &Characteristic,
+    ) -> impl Future<Output = BackendResult<()>> + Send;
+
+    /// Notifications of every subscribed characteristic, from now on.
+    fn notifications(&self) -> impl Future<Output = BackendResult<NotificationStream>> + Send;
+}
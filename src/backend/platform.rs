@@ -0,0 +1,139 @@
+//! The backend traits on top of btleplug, i.e. the host's Bluetooth stack.
+
+use super::{
+    Adapter, AdapterEvent, BackendResult, EventStream, Manager, NotificationStream, Peripheral,
+};
+use btleplug::api::{
+    BDAddr, CentralEvent, Characteristic, Descriptor, PeripheralProperties, ScanFilter, Service,
+    WriteType,
+};
+use btleplug::platform;
+use futures::stream::StreamExt;
+use std::collections::BTreeSet;
+
+pub use btleplug::platform::PeripheralId;
+
+impl Manager for platform::Manager {
+    type Adapter = platform::Adapter;
+
+    async fn adapters(&self) -> BackendResult<Vec<platform::Adapter>> {
+        btleplug::api::Manager::adapters(self).await
+    }
+}
+
+impl Adapter for platform::Adapter {
+    type Peripheral = platform::Peripheral;
+
+    async fn events(&self) -> BackendResult<EventStream<PeripheralId>> {
+        let events = btleplug::api::Central::events(self).await?;
+        Ok(Box::pin(events.filter_map(|event| {
+            futures::future::ready(adapter_event(event))
+        })))
+    }
+
+    async fn start_scan(&self) -> BackendResult<()> {
+        btleplug::api::Central::start_scan(self, ScanFilter::default()).await
+    }
+
+    async fn stop_scan(&self) -> BackendResult<()> {
+        btleplug::api::Central::stop_scan(self).await
+    }
+
+    async fn peripheral(&self, id: &PeripheralId) -> BackendResult<platform::Peripheral> {
+        btleplug::api::Central::peripheral(self, id).await
+    }
+}
+
+/// The backend's view of `event`, `None` for events about the adapter itself.
+fn adapter_event(event: CentralEvent) -> Option<AdapterEvent<PeripheralId>> {
+    let event = match event {
+        CentralEvent::DeviceDiscovered(id) => AdapterEvent::DeviceDiscovered(id),
+        CentralEvent::DeviceUpdated(id) => AdapterEvent::DeviceUpdated(id),
+        CentralEvent::DeviceConnected(id) => AdapterEvent::DeviceConnected(id),
+        CentralEvent::DeviceDisconnected(id) => AdapterEvent::DeviceDisconnected(id),
+        CentralEvent::ManufacturerDataAdvertisement {
+            id,
+            manufacturer_data,
+        } => AdapterEvent::ManufacturerData {
+            id,
+            manufacturer_data,
+        },
+        CentralEvent::ServiceDataAdvertisement { id, service_data } => {
+            AdapterEvent::ServiceData { id, service_data }
+        }
+        CentralEvent::ServicesAdvertisement { id, services } => {
+            AdapterEvent::Services { id, services }
+        }
+        _ => return None,
+    };
+    Some(event)
+}
+
+impl Peripheral for platform::Peripheral {
+    type Id = PeripheralId;
+
+    fn id(&self) -> PeripheralId {
+        btleplug::api::Peripheral::id(self)
+    }
+
+    fn address(&self) -> BDAddr {
+        btleplug::api::Peripheral::address(self)
+    }
+
+    async fn properties(&self) -> BackendResult<Option<PeripheralProperties>> {
+        btleplug::api::Peripheral::properties(self).await
+    }
+
+    fn services(&self) -> BTreeSet<Service> {
+        btleplug::api::Peripheral::services(self)
+    }
+
+    fn characteristics(&self) -> BTreeSet<Characteristic> {
+        btleplug::api::Peripheral::characteristics(self)
+    }
+
+    async fn is_connected(&self) -> BackendResult<bool> {
+        btleplug::api::Peripheral::is_connected(self).await
+    }
+
+    async fn connect(&self) -> BackendResult<()> {
+        btleplug::api::Peripheral::connect(self).await
+    }
+
+    async fn disconnect(&self) -> BackendResult<()> {
+        btleplug::api::Peripheral::disconnect(self).await
+    }
+
+    async fn discover_services(&self) -> BackendResult<()> {
+        btleplug::api::Peripheral::discover_services(self).await
+    }
+
+    async fn read(&self, characteristic: &Characteristic) -> BackendResult<Vec<u8>> {
+        btleplug::api::Peripheral::read(self, characteristic).await
+    }
+
+    async fn write(
+        &self,
+        characteristic: &Characteristic,
+        data: &[u8],
+        write_type: WriteType,
+    ) -> BackendResult<()> {
+        btleplug::api::Peripheral::write(self, characteristic, data, write_type).await
+    }
+
+    async fn read_descriptor(&self, descriptor: &Descriptor) -> BackendResult<Vec<u8>> {
+        btleplug::api::Peripheral::read_descriptor(self, descriptor).await
+    }
+
+    async fn subscribe(&self, characteristic: &Characteristic) -> BackendResult<()> {
+        btleplug::api::Peripheral::subscribe(self, characteristic).await
+    }
+
+    async fn unsubscribe(&self, characteristic: &Characteristic) -> BackendResult<()> {
+        btleplug::api::Peripheral::unsubscribe(self, characteristic).await
+    }
+
+    async fn notifications(&self) -> BackendResult<NotificationStream> {
+        btleplug::api::Peripheral::notifications(self).await
+    }
+}
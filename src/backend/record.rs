@@ -0,0 +1,412 @@
+//! Recording of every operation on a backend to a [trace](super::trace).
+//!
+//! [`RecordingManager`] wraps another manager, and the adapters and
+//! peripherals it hands out, so that each call is written to the trace with
+//! its arguments, result and duration. Adapter events are recorded from the
+//! moment the adapters are listed and notifications from the first
+//! subscription to the device, whether or not anybody consumes them.
+
+use super::trace::{
+    Address, Bytes, Entry, EventRecord, Header, Outcome, PropertiesRecord, Record, ServiceRecord,
+};
+use super::{
+    Adapter, BackendResult, EventStream, Manager, NotificationStream, Peripheral, PeripheralId,
+};
+use crate::clock::Clock;
+use crate::error::{Result, SmartwatchError};
+use btleplug::api::{BDAddr, Characteristic, Descriptor, PeripheralProperties, Service, WriteType};
+use futures::stream::StreamExt;
+use std::collections::{BTreeSet, HashSet};
+use std::fs::File;
+use std::future::Future;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Trace file being written, shared by everything recording to it.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    path: PathBuf,
+    state: Arc<Mutex<RecorderState>>,
+}
+
+#[derive(Debug)]
+struct RecorderState {
+    file: File,
+    started: Instant,
+    /// First write that failed; recording stops there.
+    error: Option<io::Error>,
+    /// Adapters and devices whose unsolicited traffic is being recorded.
+    listening: HashSet<String>,
+}
+
+impl Recorder {
+    /// Creates the trace at `path` and writes its header.
+    pub fn create(path: &Path, clock: &dyn Clock) -> Result<Recorder> {
+        let mut file = File::create(path).map_err(SmartwatchError::io(path))?;
+        let header = serde_json::to_string(&Header::new(clock.now()))
+            .map_err(|err| SmartwatchError::io(path)(err.into()))?;
+        writeln!(file, "{}", header).map_err(SmartwatchError::io(path))?;
+        Ok(Recorder {
+            path: path.to_path_buf(),
+            state: Arc::new(Mutex::new(RecorderState {
+                file,
+                started: Instant::now(),
+                error: None,
+                listening: HashSet::new(),
+            })),
+        })
+    }
+
+    /// Flushes the trace, reporting the first write that failed.
+    pub fn finish(&self) -> Result<()> {
+        let mut state = lock(&self.state);
+        let flushed = state.file.flush();
+        match state.error.take() {
+            Some(err) => Err(SmartwatchError::io(&self.path)(err)),
+            None => flushed.map_err(SmartwatchError::io(&self.path)),
+        }
+    }
+
+    /// Writes `record`, which happened at `at` and took `took`.
+    fn write(&self, at: Instant, took: Option<Duration>, record: Record) {
+        let mut state = lock(&self.state);
+        if state.error.is_some() {
+            return;
+        }
+        let entry = Entry {
+            at_us: micros(at.saturating_duration_since(state.started)),
+            took_us: took.map(micros),
+            record,
+        };
+        let written = serde_json::to_string(&entry)
+            .map_err(io::Error::from)
+            .and_then(|line| writeln!(state.file, "{}", line));
+        if let Err(err) = written {
+            log::warn!("Recording to {} stopped: {}", self.path.display(), err);
+            state.error = Some(err);
+        }
+    }
+
+    /// Runs `operation`, then records what `record` makes of its result.
+    async fn time<T>(
+        &self,
+        operation: impl Future<Output = BackendResult<T>>,
+        record: impl FnOnce(&BackendResult<T>) -> Record,
+    ) -> BackendResult<T> {
+        let started = Instant::now();
+        let result = operation.await;
+        self.write(started, Some(started.elapsed()), record(&result));
+        result
+    }
+
+    /// Whether `source` is not recorded yet; it is from now on.
+    fn start_listening(&self, source: String) -> bool {
+        lock(&self.state).listening.insert(source)
+    }
+
+    async fn record_events<I: std::fmt::Display>(self, adapter: usize, mut events: EventStream<I>) {
+        while let Some(event) = events.next().await {
+            let event = EventRecord::new(&event);
+            self.write(Instant::now(), None, Record::Event { adapter, event });
+        }
+    }
+
+    async fn record_notifications(self, device: String, mut notifications: NotificationStream) {
+        while let Some(notification) = notifications.next().await {
+            let record = Record::Notification {
+                device: device.clone(),
+                characteristic: notification.uuid,
+                value: Bytes(notification.value),
+            };
+            self.write(Instant::now(), None, record);
+        }
+    }
+}
+
+/// A manager whose traffic is recorded.
+#[derive(Debug)]
+pub struct RecordingManager<M> {
+    inner: M,
+    recorder: Recorder,
+}
+
+impl<M: Manager> RecordingManager<M> {
+    pub fn new(inner: M, recorder: Recorder) -> RecordingManager<M> {
+        RecordingManager { inner, recorder }
+    }
+}
+
+impl<M: Manager> Manager for RecordingManager<M> {
+    type Adapter = RecordingAdapter<M::Adapter>;
+
+    async fn adapters(&self) -> BackendResult<Vec<RecordingAdapter<M::Adapter>>> {
+        let adapters = self
+            .recorder
+            .time(self.inner.adapters(), |result| Record::Adapters {
+                result: Outcome::of(result, Vec::len),
+            })
+            .await?;
+        let mut recording = Vec::with_capacity(adapters.len());
+        for (index, inner) in adapters.into_iter().enumerate() {
+            if self.recorder.start_listening(format!("adapter {}", index)) {
+                match inner.events().await {
+                    Ok(events) => {
+                        tokio::spawn(self.recorder.clone().record_events(index, events));
+                    }
+                    Err(err) => {
+                        log::warn!("Not recording the events of adapter {}: {}", index, err)
+                    }
+                }
+            }
+            recording.push(RecordingAdapter {
+                inner,
+                index,
+                recorder: self.recorder.clone(),
+            });
+        }
+        Ok(recording)
+    }
+}
+
+/// An adapter whose traffic is recorded.
+#[derive(Debug)]
+pub struct RecordingAdapter<A> {
+    inner: A,
+    /// Position of the adapter in the list of the manager.
+    index: usize,
+    recorder: Recorder,
+}
+
+impl<A: Adapter> Adapter for RecordingAdapter<A> {
+    type Peripheral = RecordingPeripheral<A::Peripheral>;
+
+    async fn events(&self) -> BackendResult<EventStream<PeripheralId<A>>> {
+        self.inner.events().await
+    }
+
+    async fn start_scan(&self) -> BackendResult<()> {
+        let adapter = self.index;
+        self.recorder
+            .time(self.inner.start_scan(), |result| Record::StartScan {
+                adapter,
+                result: Outcome::of(result, |_| ()),
+            })
+            .await
+    }
+
+    async fn stop_scan(&self) -> BackendResult<()> {
+        let adapter = self.index;
+        self.recorder
+            .time(self.inner.stop_scan(), |result| Record::StopScan {
+                adapter,
+                result: Outcome::of(result, |_| ()),
+            })
+            .await
+    }
+
+    async fn peripheral(
+        &self,
+        id: &PeripheralId<A>,
+    ) -> BackendResult<RecordingPeripheral<A::Peripheral>> {
+        let adapter = self.index;
+        let inner = self
+            .recorder
+            .time(self.inner.peripheral(id), |result| Record::Peripheral {
+                adapter,
+                device: id.to_string(),
+                result: Outcome::of(result, |peripheral| Address(peripheral.address())),
+            })
+            .await?;
+        Ok(RecordingPeripheral {
+            inner,
+            recorder: self.recorder.clone(),
+        })
+    }
+}
+
+/// A peripheral whose traffic is recorded.
+#[derive(Debug, Clone)]
+pub struct RecordingPeripheral<P> {
+    inner: P,
+    recorder: Recorder,
+}
+
+impl<P: Peripheral> RecordingPeripheral<P> {
+    fn device(&self) -> String {
+        self.inner.id().to_string()
+    }
+}
+
+impl<P: Peripheral> Peripheral for RecordingPeripheral<P> {
+    type Id = P::Id;
+
+    fn id(&self) -> P::Id {
+        self.inner.id()
+    }
+
+    fn address(&self) -> BDAddr {
+        self.inner.address()
+    }
+
+    async fn properties(&self) -> BackendResult<Option<PeripheralProperties>> {
+        let device = self.device();
+        self.recorder
+            .time(self.inner.properties(), |result| Record::Properties {
+                device,
+                result: Outcome::of(result, |properties| {
+                    properties.as_ref().map(PropertiesRecord::from)
+                }),
+            })
+            .await
+    }
+
+    fn services(&self) -> BTreeSet<Service> {
+        self.inner.services()
+    }
+
+    async fn is_connected(&self) -> BackendResult<bool> {
+        let device = self.device();
+        self.recorder
+            .time(self.inner.is_connected(), |result| Record::IsConnected {
+                device,
+                result: Outcome::of(result, |connected| *connected),
+            })
+            .await
+    }
+
+    async fn connect(&self) -> BackendResult<()> {
+        let device = self.device();
+        self.recorder
+            .time(self.inner.connect(), |result| Record::Connect {
+                device,
+                result: Outcome::of(result, |_| ()),
+            })
+            .await
+    }
+
+    async fn disconnect(&self) -> BackendResult<()> {
+        let device = self.device();
+        self.recorder
+            .time(self.inner.disconnect(), |result| Record::Disconnect {
+                device,
+                result: Outcome::of(result, |_| ()),
+            })
+            .await
+    }
+
+    async fn discover_services(&self) -> BackendResult<()> {
+        let device = self.device();
+        self.recorder
+            .time(self.inner.discover_services(), |result| {
+                Record::DiscoverServices {
+                    device,
+                    result: Outcome::of(result, |_| {
+                        self.inner
+                            .services()
+                            .iter()
+                            .map(ServiceRecord::from)
+                            .collect()
+                    }),
+                }
+            })
+            .await
+    }
+
+    async fn read(&self, characteristic: &Characteristic) -> BackendResult<Vec<u8>> {
+        let device = self.device();
+        self.recorder
+            .time(self.inner.read(characteristic), |result| Record::Read {
+                device,
+                characteristic: characteristic.uuid,
+                result: Outcome::of(result, |value| Bytes(value.clone())),
+            })
+            .await
+    }
+
+    async fn write(
+        &self,
+        characteristic: &Characteristic,
+        data: &[u8],
+        write_type: WriteType,
+    ) -> BackendResult<()> {
+        let device = self.device();
+        self.recorder
+            .time(
+                self.inner.write(characteristic, data, write_type),
+                |result| Record::Write {
+                    device,
+                    characteristic: characteristic.uuid,
+                    data: Bytes(data.to_vec()),
+                    with_response: write_type == WriteType::WithResponse,
+                    result: Outcome::of(result, |_| ()),
+                },
+            )
+            .await
+    }
+
+    async fn read_descriptor(&self, descriptor: &Descriptor) -> BackendResult<Vec<u8>> {
+        let device = self.device();
+        self.recorder
+            .time(self.inner.read_descriptor(descriptor), |result| {
+                Record::ReadDescriptor {
+                    device,
+                    characteristic: descriptor.characteristic_uuid,
+                    descriptor: descriptor.uuid,
+                    result: Outcome::of(result, |value| Bytes(value.clone())),
+                }
+            })
+            .await
+    }
+
+    async fn subscribe(&self, characteristic: &Characteristic) -> BackendResult<()> {
+        let device = self.device();
+        if self.recorder.start_listening(device.clone()) {
+            match self.inner.notifications().await {
+                Ok(notifications) => {
+                    let recorder = self.recorder.clone();
+                    tokio::spawn(recorder.record_notifications(device.clone(), notifications));
+                }
+                Err(err) => log::warn!("Not recording the notifications of {}: {}", device, err),
+            }
+        }
+        self.recorder
+            .time(self.inner.subscribe(characteristic), |result| {
+                Record::Subscribe {
+                    device,
+                    characteristic: characteristic.uuid,
+                    result: Outcome::of(result, |_| ()),
+                }
+            })
+            .await
+    }
+
+    async fn unsubscribe(&self, characteristic: &Characteristic) -> BackendResult<()> {
+        let device = self.device();
+        self.recorder
+            .time(self.inner.unsubscribe(characteristic), |result| {
+                Record::Unsubscribe {
+                    device,
+                    characteristic: characteristic.uuid,
+                    result: Outcome::of(result, |_| ()),
+                }
+            })
+            .await
+    }
+
+    async fn notifications(&self) -> BackendResult<NotificationStream> {
+        self.inner.notifications().await
+    }
+}
+
+fn micros(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros()).unwrap_or(u64::MAX)
+}
+
+fn lock<T>(state: &Mutex<T>) -> MutexGuard<'_, T> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,476 @@
+//! A backend serving a recorded [trace](super::trace) back.
+//!
+//! Each operation is answered with the next recorded answer to the same
+//! operation on the same device and attribute, after the time it took when
+//! recorded; devices and characteristics may thus be called in another order
+//! than recorded. Adapter listings, peripheral lookups, properties and
+//! connection checks keep their last answer once the recorded ones run out.
+//! Adverts and notifications arrive at the time they were recorded, counted
+//! from the listing of the adapters, notifications only while subscribed.
+//! Written data is not compared with the trace, as time writes differ from one
+//! run to the next.
+
+use super::trace::{Entry, Header, Record, TRACE_FORMAT, TRACE_VERSION};
+use super::{
+    Adapter, AdapterEvent, BackendResult, EventStream, Manager, NotificationStream, Peripheral,
+};
+use crate::error::{Result, SmartwatchError};
+use btleplug::api::{
+    BDAddr, Characteristic, Descriptor, PeripheralProperties, Service, ValueNotification, WriteType,
+};
+use futures::channel::mpsc::{self, UnboundedSender};
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::time::{self, Instant};
+use uuid::Uuid;
+
+/// Operation answered by an entry of the trace.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Adapters,
+    StartScan(usize),
+    StopScan(usize),
+    Peripheral(usize, String),
+    Properties(String),
+    IsConnected(String),
+    Connect(String),
+    Disconnect(String),
+    DiscoverServices(String),
+    Read(String, Uuid),
+    Write(String, Uuid),
+    ReadDescriptor(String, Uuid, Uuid),
+    Subscribe(String, Uuid),
+    Unsubscribe(String, Uuid),
+}
+
+impl Key {
+    /// The operation `record` answers, `None` for adverts and notifications.
+    fn of(record: &Record) -> Option<Key> {
+        let key = match record {
+            Record::Adapters { .. } => Key::Adapters,
+            Record::StartScan { adapter, .. } => Key::StartScan(*adapter),
+            Record::StopScan { adapter, .. } => Key::StopScan(*adapter),
+            Record::Peripheral {
+                adapter, device, ..
+            } => Key::Peripheral(*adapter, device.clone()),
+            Record::Properties { device, .. } => Key::Properties(device.clone()),
+            Record::IsConnected { device, .. } => Key::IsConnected(device.clone()),
+            Record::Connect { device, .. } => Key::Connect(device.clone()),
+            Record::Disconnect { device, .. } => Key::Disconnect(device.clone()),
+            Record::DiscoverServices { device, .. } => Key::DiscoverServices(device.clone()),
+            Record::Read {
+                device,
+                characteristic,
+                ..
+            } => Key::Read(device.clone(), *characteristic),
+            Record::Write {
+                device,
+                characteristic,
+                ..
+            } => Key::Write(device.clone(), *characteristic),
+            Record::ReadDescriptor {
+                device,
+                characteristic,
+                descriptor,
+                ..
+            } => Key::ReadDescriptor(device.clone(), *characteristic, *descriptor),
+            Record::Subscribe {
+                device,
+                characteristic,
+                ..
+            } => Key::Subscribe(device.clone(), *characteristic),
+            Record::Unsubscribe {
+                device,
+                characteristic,
+                ..
+            } => Key::Unsubscribe(device.clone(), *characteristic),
+            Record::Event { .. } | Record::Notification { .. } => return None,
+        };
+        Some(key)
+    }
+
+    /// Whether the last answer is given again once the recorded ones run out.
+    fn repeats(&self) -> bool {
+        matches!(
+            self,
+            Key::Adapters | Key::Peripheral(..) | Key::Properties(_) | Key::IsConnected(_)
+        )
+    }
+}
+
+#[derive(Default)]
+struct ReplayState {
+    answers: HashMap<Key, VecDeque<Entry>>,
+    last: HashMap<Key, Entry>,
+    /// Adverts and notifications, until the replay starts.
+    timeline: Option<Vec<Entry>>,
+    events: HashMap<usize, Vec<UnboundedSender<AdapterEvent<String>>>>,
+    notifications: HashMap<String, Vec<UnboundedSender<ValueNotification>>>,
+    subscribed: HashSet<(String, Uuid)>,
+    services: HashMap<String, BTreeSet<Service>>,
+}
+
+impl fmt::Debug for ReplayState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReplayState")
+            .field("subscribed", &self.subscribed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReplayState {
+    fn next_answer(&mut self, key: &Key) -> BackendResult<Entry> {
+        if let Some(entry) = self.answers.get_mut(key).and_then(VecDeque::pop_front) {
+            if key.repeats() {
+                self.last.insert(key.clone(), entry.clone());
+            }
+            return Ok(entry);
+        }
+        self.last.get(key).cloned().ok_or_else(|| {
+            btleplug::Error::RuntimeError(format!("the trace has no answer left to {:?}", key))
+        })
+    }
+
+    fn deliver(&mut self, record: Record) {
+        match record {
+            Record::Event { adapter, event } => {
+                let event = event.to_event();
+                if let AdapterEvent::DeviceDisconnected(device) = &event {
+                    self.subscribed
+                        .retain(|(subscribed, _)| subscribed != device);
+                }
+                if let Some(listeners) = self.events.get_mut(&adapter) {
+                    listeners.retain(|listener| listener.unbounded_send(event.clone()).is_ok());
+                }
+            }
+            Record::Notification {
+                device,
+                characteristic,
+                value,
+            } => {
+                if !self.subscribed.contains(&(device.clone(), characteristic)) {
+                    return;
+                }
+                let notification = ValueNotification {
+                    uuid: characteristic,
+                    value: value.0,
+                };
+                if let Some(listeners) = self.notifications.get_mut(&device) {
+                    listeners
+                        .retain(|listener| listener.unbounded_send(notification.clone()).is_ok());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Answers the operation `key` from the trace, after the time it took.
+async fn answer(state: &Mutex<ReplayState>, key: Key) -> BackendResult<Record> {
+    let entry = lock(state).next_answer(&key)?;
+    time::sleep(Duration::from_micros(entry.took_us.unwrap_or_default())).await;
+    Ok(entry.record)
+}
+
+/// Delivers the adverts and notifications of `timeline`, `origin` being the
+/// trace time of `start`.
+async fn play(state: Arc<Mutex<ReplayState>>, timeline: Vec<Entry>, start: Instant, origin: u64) {
+    for entry in timeline {
+        let after = Duration::from_micros(entry.at_us.saturating_sub(origin));
+        time::sleep_until(start + after).await;
+        lock(&state).deliver(entry.record);
+    }
+}
+
+/// A manager replaying a trace.
+#[derive(Debug, Clone)]
+pub struct ReplayManager {
+    state: Arc<Mutex<ReplayState>>,
+}
+
+impl ReplayManager {
+    /// Loads the trace at `path`.
+    pub fn load(path: &Path) -> Result<ReplayManager> {
+        let file = File::open(path).map_err(SmartwatchError::io(path))?;
+        ReplayManager::read(BufReader::new(file)).map_err(SmartwatchError::io(path))
+    }
+
+    /// Reads a trace, failing with [`io::ErrorKind::InvalidData`] when it is
+    /// malformed or of another version.
+    pub fn read(reader: impl BufRead) -> io::Result<ReplayManager> {
+        let mut lines = reader.lines();
+        let header: Header = match lines.next() {
+            Some(line) => parse(1, &line?)?,
+            None => return Err(invalid(String::from("empty trace"))),
+        };
+        if header.format != TRACE_FORMAT {
+            return Err(invalid(format!("not a trace but {:?}", header.format)));
+        }
+        if header.version != TRACE_VERSION {
+            return Err(invalid(format!(
+                "trace version {} is not supported, expected {}",
+                header.version, TRACE_VERSION
+            )));
+        }
+        let mut state = ReplayState::default();
+        let mut timeline = Vec::new();
+        for (index, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: Entry = parse(index + 2, &line)?;
+            match Key::of(&entry.record) {
+                Some(key) => state.answers.entry(key).or_default().push_back(entry),
+                None => timeline.push(entry),
+            }
+        }
+        state.timeline = Some(timeline);
+        Ok(ReplayManager {
+            state: Arc::new(Mutex::new(state)),
+        })
+    }
+}
+
+impl Manager for ReplayManager {
+    type Adapter = ReplayAdapter;
+
+    async fn adapters(&self) -> BackendResult<Vec<ReplayAdapter>> {
+        let (entry, timeline) = {
+            let mut state = lock(&self.state);
+            (state.next_answer(&Key::Adapters)?, state.timeline.take())
+        };
+        if let Some(timeline) = timeline {
+            let state = Arc::clone(&self.state);
+            tokio::spawn(play(state, timeline, Instant::now(), entry.at_us));
+        }
+        time::sleep(Duration::from_micros(entry.took_us.unwrap_or_default())).await;
+        match entry.record {
+            Record::Adapters { result } => Ok((0..result.into_result()?)
+                .map(|index| ReplayAdapter {
+                    index,
+                    state: Arc::clone(&self.state),
+                })
+                .collect()),
+            record => Err(unexpected(&record)),
+        }
+    }
+}
+
+/// An adapter of a replayed trace.
+#[derive(Debug, Clone)]
+pub struct ReplayAdapter {
+    index: usize,
+    state: Arc<Mutex<ReplayState>>,
+}
+
+impl Adapter for ReplayAdapter {
+    type Peripheral = ReplayPeripheral;
+
+    async fn events(&self) -> BackendResult<EventStream<String>> {
+        let (sender, receiver) = mpsc::unbounded();
+        lock(&self.state)
+            .events
+            .entry(self.index)
+            .or_default()
+            .push(sender);
+        Ok(Box::pin(receiver))
+    }
+
+    async fn start_scan(&self) -> BackendResult<()> {
+        match answer(&self.state, Key::StartScan(self.index)).await? {
+            Record::StartScan { result, .. } => result.into_result(),
+            record => Err(unexpected(&record)),
+        }
+    }
+
+    async fn stop_scan(&self) -> BackendResult<()> {
+        match answer(&self.state, Key::StopScan(self.index)).await? {
+            Record::StopScan { result, .. } => result.into_result(),
+            record => Err(unexpected(&record)),
+        }
+    }
+
+    async fn peripheral(&self, id: &String) -> BackendResult<ReplayPeripheral> {
+        match answer(&self.state, Key::Peripheral(self.index, id.clone())).await? {
+            Record::Peripheral { result, .. } => Ok(ReplayPeripheral {
+                device: id.clone(),
+                address: result.into_result()?.0,
+                state: Arc::clone(&self.state),
+            }),
+            record => Err(unexpected(&record)),
+        }
+    }
+}
+
+/// A peripheral of a replayed trace, identified as in the trace.
+#[derive(Debug, Clone)]
+pub struct ReplayPeripheral {
+    device: String,
+    address: BDAddr,
+    state: Arc<Mutex<ReplayState>>,
+}
+
+impl Peripheral for ReplayPeripheral {
+    type Id = String;
+
+    fn id(&self) -> String {
+        self.device.clone()
+    }
+
+    fn address(&self) -> BDAddr {
+        self.address
+    }
+
+    async fn properties(&self) -> BackendResult<Option<PeripheralProperties>> {
+        match answer(&self.state, Key::Properties(self.device.clone())).await? {
+            Record::Properties { result, .. } => {
+                Ok(result.into_result()?.map(PeripheralProperties::from))
+            }
+            record => Err(unexpected(&record)),
+        }
+    }
+
+    fn services(&self) -> BTreeSet<Service> {
+        lock(&self.state)
+            .services
+            .get(&self.device)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    async fn is_connected(&self) -> BackendResult<bool> {
+        match answer(&self.state, Key::IsConnected(self.device.clone())).await? {
+            Record::IsConnected { result, .. } => result.into_result(),
+            record => Err(unexpected(&record)),
+        }
+    }
+
+    async fn connect(&self) -> BackendResult<()> {
+        match answer(&self.state, Key::Connect(self.device.clone())).await? {
+            Record::Connect { result, .. } => result.into_result(),
+            record => Err(unexpected(&record)),
+        }
+    }
+
+    async fn disconnect(&self) -> BackendResult<()> {
+        match answer(&self.state, Key::Disconnect(self.device.clone())).await? {
+            Record::Disconnect { result, .. } => {
+                result.into_result()?;
+                lock(&self.state)
+                    .subscribed
+                    .retain(|(device, _)| *device != self.device);
+                Ok(())
+            }
+            record => Err(unexpected(&record)),
+        }
+    }
+
+    async fn discover_services(&self) -> BackendResult<()> {
+        match answer(&self.state, Key::DiscoverServices(self.device.clone())).await? {
+            Record::DiscoverServices { result, .. } => {
+                let services = result.into_result()?.iter().map(Service::from).collect();
+                lock(&self.state)
+                    .services
+                    .insert(self.device.clone(), services);
+                Ok(())
+            }
+            record => Err(unexpected(&record)),
+        }
+    }
+
+    async fn read(&self, characteristic: &Characteristic) -> BackendResult<Vec<u8>> {
+        let key = Key::Read(self.device.clone(), characteristic.uuid);
+        match answer(&self.state, key).await? {
+            Record::Read { result, .. } => Ok(result.into_result()?.0),
+            record => Err(unexpected(&record)),
+        }
+    }
+
+    async fn write(
+        &self,
+        characteristic: &Characteristic,
+        _data: &[u8],
+        _write_type: WriteType,
+    ) -> BackendResult<()> {
+        let key = Key::Write(self.device.clone(), characteristic.uuid);
+        match answer(&self.state, key).await? {
+            Record::Write { result, .. } => result.into_result(),
+            record => Err(unexpected(&record)),
+        }
+    }
+
+    async fn read_descriptor(&self, descriptor: &Descriptor) -> BackendResult<Vec<u8>> {
+        let key = Key::ReadDescriptor(
+            self.device.clone(),
+            descriptor.characteristic_uuid,
+            descriptor.uuid,
+        );
+        match answer(&self.state, key).await? {
+            Record::ReadDescriptor { result, .. } => Ok(result.into_result()?.0),
+            record => Err(unexpected(&record)),
+        }
+    }
+
+    async fn subscribe(&self, characteristic: &Characteristic) -> BackendResult<()> {
+        let key = Key::Subscribe(self.device.clone(), characteristic.uuid);
+        match answer(&self.state, key).await? {
+            Record::Subscribe { result, .. } => {
+                result.into_result()?;
+                lock(&self.state)
+                    .subscribed
+                    .insert((self.device.clone(), characteristic.uuid));
+                Ok(())
+            }
+            record => Err(unexpected(&record)),
+        }
+    }
+
+    async fn unsubscribe(&self, characteristic: &Characteristic) -> BackendResult<()> {
+        let key = Key::Unsubscribe(self.device.clone(), characteristic.uuid);
+        match answer(&self.state, key).await? {
+            Record::Unsubscribe { result, .. } => {
+                result.into_result()?;
+                lock(&self.state)
+                    .subscribed
+                    .remove(&(self.device.clone(), characteristic.uuid));
+                Ok(())
+            }
+            record => Err(unexpected(&record)),
+        }
+    }
+
+    async fn notifications(&self) -> BackendResult<NotificationStream> {
+        let (sender, receiver) = mpsc::unbounded();
+        lock(&self.state)
+            .notifications
+            .entry(self.device.clone())
+            .or_default()
+            .push(sender);
+        Ok(Box::pin(receiver))
+    }
+}
+
+fn parse<T: DeserializeOwned>(number: usize, line: &str) -> io::Result<T> {
+    serde_json::from_str(line).map_err(|err| invalid(format!("line {}: {}", number, err)))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn unexpected(record: &Record) -> btleplug::Error {
+    btleplug::Error::RuntimeError(format!("unexpected answer in the trace: {:?}", record))
+}
+
+fn lock<T>(state: &Mutex<T>) -> MutexGuard<'_, T> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,798 @@
+//! A simulated watch, to run the whole tool without Bluetooth hardware.
+//!
+//! The watch described by a [`SimProfile`] advertises while the adapter scans
+//! and, once connected, serves the Current Time, Battery, Heart Rate, Device
+//! Information and Nordic UART services. It keeps its own clock, which runs off
+//! the host clock at the profile's drift rate and is reset by writes to Current
+//! Time. Subscribed characteristics are notified periodically, and connections
+//! can be refused, slowed down or dropped as the profile asks.
+
+use super::{
+    Adapter, AdapterEvent, BackendResult, EventStream, Manager, NotificationStream, Peripheral,
+};
+use crate::clock::Clock;
+use crate::error::Result;
+use crate::gatt::cts::{
+    AdjustReason, CurrentTime, DstOffset, LocalTimeInformation, ReferenceTimeInformation,
+    TimeAccuracy, TimeSource,
+};
+use crate::gatt::descriptor::{CLIENT_CONFIGURATION_UUID, PRESENTATION_FORMAT_UUID};
+use crate::gatt::{
+    BATTERY_LEVEL_UUID, CURRENT_TIME_UUID, FIRMWARE_REVISION_UUID, HARDWARE_REVISION_UUID,
+    LOCAL_TIME_INFORMATION_UUID, MANUFACTURER_NAME_UUID, MODEL_NUMBER_UUID,
+    REFERENCE_TIME_INFORMATION_UUID, SERIAL_NUMBER_UUID, SOFTWARE_REVISION_UUID,
+};
+use crate::zone;
+use btleplug::api::bleuuid::uuid_from_u16;
+use btleplug::api::{
+    BDAddr, CharPropFlags, Characteristic, Descriptor, PeripheralProperties, Service,
+    ValueNotification, WriteType,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use futures::channel::mpsc::{self, UnboundedSender};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::time;
+use uuid::Uuid;
+
+pub mod profile;
+
+pub use profile::SimProfile;
+
+const CURRENT_TIME_SERVICE_UUID: Uuid = uuid_from_u16(0x1805);
+const BATTERY_SERVICE_UUID: Uuid = uuid_from_u16(0x180F);
+const HEART_RATE_SERVICE_UUID: Uuid = uuid_from_u16(0x180D);
+const HEART_RATE_MEASUREMENT_UUID: Uuid = uuid_from_u16(0x2A37);
+const BODY_SENSOR_LOCATION_UUID: Uuid = uuid_from_u16(0x2A38);
+const HEART_RATE_CONTROL_POINT_UUID: Uuid = uuid_from_u16(0x2A39);
+const DEVICE_INFORMATION_SERVICE_UUID: Uuid = uuid_from_u16(0x180A);
+const UART_SERVICE_UUID: Uuid = Uuid::from_u128(0x6e400001_b5a3_f393_e0a9_e50e24dcca9e);
+const UART_RX_UUID: Uuid = Uuid::from_u128(0x6e400002_b5a3_f393_e0a9_e50e24dcca9e);
+const UART_TX_UUID: Uuid = Uuid::from_u128(0x6e400003_b5a3_f393_e0a9_e50e24dcca9e);
+
+/// Presentation Format of Battery Level: uint8 percentage.
+const BATTERY_LEVEL_FORMAT: [u8; 7] = [0x04, 0x00, 0xAD, 0x27, 0x01, 0x00, 0x00];
+/// Heart Rate Control Point command resetting the energy expended.
+const RESET_ENERGY_EXPENDED: u8 = 0x01;
+
+/// A manager with one adapter, in range of the simulated watch.
+#[derive(Debug, Clone)]
+pub struct SimManager {
+    adapter: SimAdapter,
+}
+
+impl SimManager {
+    /// Builds the watch of `profile`, its clock running off `host`.
+    pub fn new(profile: SimProfile, host: Arc<dyn Clock>) -> Result<SimManager> {
+        let scan = Arc::new(Mutex::new(ScanState::default()));
+        let watch = SimWatch::new(profile, host, Arc::clone(&scan))?;
+        Ok(SimManager {
+            adapter: SimAdapter { watch, scan },
+        })
+    }
+
+    /// The simulated watch, e.g. to drop its connection on demand.
+    pub fn watch(&self) -> &SimWatch {
+        &self.adapter.watch
+    }
+}
+
+impl Manager for SimManager {
+    type Adapter = SimAdapter;
+
+    async fn adapters(&self) -> BackendResult<Vec<SimAdapter>> {
+        Ok(vec![self.adapter.clone()])
+    }
+}
+
+#[derive(Debug, Default)]
+struct ScanState {
+    listeners: Vec<UnboundedSender<AdapterEvent<BDAddr>>>,
+    /// Incremented by every start and stop, so that a stopped scan's adverts end.
+    scan: u64,
+    scanning: bool,
+}
+
+impl ScanState {
+    fn emit(&mut self, event: AdapterEvent<BDAddr>) {
+        self.listeners
+            .retain(|listener| listener.unbounded_send(event.clone()).is_ok());
+    }
+}
+
+/// An adapter that sees the simulated watch advertise while scanning.
+#[derive(Debug, Clone)]
+pub struct SimAdapter {
+    watch: SimWatch,
+    scan: Arc<Mutex<ScanState>>,
+}
+
+impl SimAdapter {
+    /// Advertises every advertising interval until scan number `scan` stops.
+    async fn advertise(self, scan: u64) {
+        let (interval, manufacturer) = {
+            let state = lock(&self.watch.state);
+            (
+                state.profile.advertising_interval,
+                state.profile.manufacturer.clone(),
+            )
+        };
+        let id = self.watch.address();
+        let mut event = AdapterEvent::DeviceDiscovered(id);
+        loop {
+            {
+                let mut state = lock(&self.scan);
+                if !state.scanning || state.scan != scan {
+                    return;
+                }
+                state.emit(event);
+            }
+            time::sleep(interval).await;
+            event = match &manufacturer {
+                Some(manufacturer) => AdapterEvent::ManufacturerData {
+                    id,
+                    manufacturer_data: HashMap::from([(
+                        manufacturer.company_id,
+                        manufacturer.data.clone(),
+                    )]),
+                },
+                None => AdapterEvent::DeviceUpdated(id),
+            };
+        }
+    }
+}
+
+impl Adapter for SimAdapter {
+    type Peripheral = SimWatch;
+
+    async fn events(&self) -> BackendResult<EventStream<BDAddr>> {
+        let (sender, receiver) = mpsc::unbounded();
+        lock(&self.scan).listeners.push(sender);
+        Ok(Box::pin(receiver))
+    }
+
+    async fn start_scan(&self) -> BackendResult<()> {
+        let scan = {
+            let mut state = lock(&self.scan);
+            state.scan += 1;
+            state.scanning = true;
+            state.scan
+        };
+        tokio::spawn(self.clone().advertise(scan));
+        Ok(())
+    }
+
+    async fn stop_scan(&self) -> BackendResult<()> {
+        let mut state = lock(&self.scan);
+        state.scan += 1;
+        state.scanning = false;
+        Ok(())
+    }
+
+    async fn peripheral(&self, id: &BDAddr) -> BackendResult<SimWatch> {
+        if *id == self.watch.address() {
+            Ok(self.watch.clone())
+        } else {
+            Err(btleplug::Error::DeviceNotFound)
+        }
+    }
+}
+
+/// Local time running at its own rate since it was last set.
+#[derive(Debug, Clone, Copy)]
+struct WatchClock {
+    /// Host time at which the clock was set.
+    set_at: DateTime<Utc>,
+    /// Local time the clock was set to.
+    local: NaiveDateTime,
+    /// How much faster than the host the clock runs, in parts per million.
+    drift_ppm: f64,
+}
+
+impl WatchClock {
+    fn now(&self, host_now: DateTime<Utc>) -> NaiveDateTime {
+        let elapsed = host_now - self.set_at;
+        let nanos = elapsed.num_nanoseconds().unwrap_or(i64::MAX) as f64;
+        let drift = chrono::Duration::nanoseconds((nanos * self.drift_ppm / 1e6).round() as i64);
+        self.local + elapsed + drift
+    }
+
+    fn set(&mut self, host_now: DateTime<Utc>, local: NaiveDateTime) {
+        self.set_at = host_now;
+        self.local = local;
+    }
+}
+
+/// What the watch does after a write.
+enum Reply {
+    Nothing,
+    Notify(Uuid, Vec<u8>),
+    Disconnect,
+}
+
+struct WatchState {
+    profile: SimProfile,
+    host: Arc<dyn Clock>,
+    started_at: DateTime<Utc>,
+    clock: WatchClock,
+    adjust_reason: AdjustReason,
+    local_time_information: LocalTimeInformation,
+    reference_time: ReferenceTimeInformation,
+    reference_updated_at: DateTime<Utc>,
+    table: BTreeSet<Service>,
+    connected: bool,
+    discovered: bool,
+    /// Incremented by every connection and disconnection, so that timers of a
+    /// previous connection end.
+    connection: u64,
+    connect_failures: usize,
+    subscribed: HashSet<Uuid>,
+    listeners: Vec<UnboundedSender<ValueNotification>>,
+    measurements: u64,
+}
+
+impl WatchState {
+    fn read(&self, uuid: Uuid) -> BackendResult<Vec<u8>> {
+        let now = self.host.now();
+        let info = self.profile.device_information.clone().unwrap_or_default();
+        let text = |value: Option<String>| value.map(String::into_bytes);
+        let value = match uuid {
+            CURRENT_TIME_UUID => Some(self.current_time()?),
+            LOCAL_TIME_INFORMATION_UUID => Some(
+                self.local_time_information
+                    .encode()
+                    .map_err(runtime)?
+                    .to_vec(),
+            ),
+            REFERENCE_TIME_INFORMATION_UUID => {
+                let hours = (now - self.reference_updated_at).num_hours().max(0);
+                let info = ReferenceTimeInformation {
+                    days_since_update: (hours / 24).min(255) as u8,
+                    hours_since_update: (hours % 24) as u8,
+                    ..self.reference_time
+                };
+                Some(info.encode().map_err(runtime)?.to_vec())
+            }
+            BATTERY_LEVEL_UUID => Some(vec![self.battery_level()]),
+            BODY_SENSOR_LOCATION_UUID => self
+                .profile
+                .heart_rate
+                .as_ref()
+                .map(|heart_rate| vec![heart_rate.sensor_location]),
+            MANUFACTURER_NAME_UUID => text(info.manufacturer),
+            MODEL_NUMBER_UUID => text(info.model),
+            SERIAL_NUMBER_UUID => text(info.serial),
+            HARDWARE_REVISION_UUID => text(info.hardware),
+            FIRMWARE_REVISION_UUID => text(info.firmware),
+            SOFTWARE_REVISION_UUID => text(info.software),
+            _ => None,
+        };
+        value.ok_or_else(|| not_permitted("read", uuid))
+    }
+
+    fn write(&mut self, uuid: Uuid, data: &[u8]) -> BackendResult<Reply> {
+        let now = self.host.now();
+        match uuid {
+            CURRENT_TIME_UUID => {
+                let time = CurrentTime::decode(data).map_err(runtime)?;
+                let local = time.to_naive_datetime().map_err(runtime)?;
+                self.clock.set(now, local);
+                self.adjust_reason = time.adjust_reason;
+                self.reference_updated_at = now;
+            }
+            LOCAL_TIME_INFORMATION_UUID => {
+                self.local_time_information =
+                    LocalTimeInformation::decode(data).map_err(runtime)?;
+            }
+            REFERENCE_TIME_INFORMATION_UUID => {
+                self.reference_time = ReferenceTimeInformation::decode(data).map_err(runtime)?;
+                self.reference_updated_at = now;
+            }
+            HEART_RATE_CONTROL_POINT_UUID if self.profile.heart_rate.is_some() => {
+                if data != [RESET_ENERGY_EXPENDED] {
+                    return Err(btleplug::Error::RuntimeError(String::from(
+                        "control point command not supported",
+                    )));
+                }
+            }
+            UART_RX_UUID if self.profile.uart.is_some() => return self.uart_command(data),
+            _ => return Err(not_permitted("write", uuid)),
+        }
+        Ok(Reply::Nothing)
+    }
+
+    fn uart_command(&self, data: &[u8]) -> BackendResult<Reply> {
+        let echo = self.profile.uart.as_ref().is_some_and(|uart| uart.echo);
+        let line = String::from_utf8_lossy(data);
+        let answer = match line.trim() {
+            "disconnect" => return Ok(Reply::Disconnect),
+            "time" => self
+                .clock
+                .now(self.host.now())
+                .format("%Y-%m-%dT%H:%M:%S%.3f\n")
+                .to_string(),
+            "battery" => format!("{}%\n", self.battery_level()),
+            _ if echo => line.to_string(),
+            _ => return Ok(Reply::Nothing),
+        };
+        Ok(Reply::Notify(UART_TX_UUID, answer.into_bytes()))
+    }
+
+    fn read_descriptor(&self, descriptor: &Descriptor) -> BackendResult<Vec<u8>> {
+        match descriptor.uuid {
+            CLIENT_CONFIGURATION_UUID => {
+                let notifying = self.subscribed.contains(&descriptor.characteristic_uuid);
+                Ok(vec![u8::from(notifying), 0])
+            }
+            PRESENTATION_FORMAT_UUID if descriptor.characteristic_uuid == BATTERY_LEVEL_UUID => {
+                Ok(BATTERY_LEVEL_FORMAT.to_vec())
+            }
+            _ => Err(not_permitted("read", descriptor.uuid)),
+        }
+    }
+
+    fn current_time(&self) -> BackendResult<Vec<u8>> {
+        let now = self.clock.now(self.host.now());
+        let time = CurrentTime::from_datetime(&now, self.adjust_reason).map_err(runtime)?;
+        Ok(time.encode().map_err(runtime)?.to_vec())
+    }
+
+    fn battery_level(&self) -> u8 {
+        let Some(battery) = &self.profile.battery else {
+            return 0;
+        };
+        let hours = (self.host.now() - self.started_at).num_seconds() as f64 / 3600.0;
+        (f64::from(battery.level) - battery.drain_per_hour * hours).clamp(0.0, 100.0) as u8
+    }
+
+    /// Next Heart Rate Measurement: uint8 pulse sweeping around the average.
+    fn heart_rate_measurement(&mut self) -> Vec<u8> {
+        let Some(heart_rate) = &self.profile.heart_rate else {
+            return vec![0x00, 0];
+        };
+        let (bpm, variation) = (i64::from(heart_rate.bpm), i64::from(heart_rate.variation));
+        let delta = if variation == 0 {
+            0
+        } else {
+            let phase = (self.measurements % (4 * variation as u64)) as i64;
+            if phase <= 2 * variation {
+                phase - variation
+            } else {
+                3 * variation - phase
+            }
+        };
+        self.measurements += 1;
+        vec![0x00, (bpm + delta).clamp(0, 255) as u8]
+    }
+
+    /// Interval at which `uuid` is notified once subscribed.
+    fn notify_interval(&self, uuid: Uuid) -> Option<Duration> {
+        match uuid {
+            CURRENT_TIME_UUID => self.profile.clock.notify_interval,
+            BATTERY_LEVEL_UUID => self.profile.battery.as_ref()?.notify_interval,
+            HEART_RATE_MEASUREMENT_UUID => Some(self.profile.heart_rate.as_ref()?.notify_interval),
+            _ => None,
+        }
+    }
+
+    /// Sends a notification, if the client subscribed to `uuid`.
+    fn notify(&mut self, uuid: Uuid, value: Vec<u8>) {
+        if !self.subscribed.contains(&uuid) {
+            return;
+        }
+        let notification = ValueNotification { uuid, value };
+        self.listeners
+            .retain(|listener| listener.unbounded_send(notification.clone()).is_ok());
+    }
+
+    fn characteristic(&self, uuid: Uuid) -> Option<&Characteristic> {
+        self.table
+            .iter()
+            .flat_map(|service| &service.characteristics)
+            .find(|c| c.uuid == uuid)
+    }
+}
+
+/// The simulated watch.
+#[derive(Clone)]
+pub struct SimWatch {
+    state: Arc<Mutex<WatchState>>,
+    scan: Arc<Mutex<ScanState>>,
+}
+
+impl fmt::Debug for SimWatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SimWatch")
+            .field("address", &self.address())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SimWatch {
+    fn new(
+        profile: SimProfile,
+        host: Arc<dyn Clock>,
+        scan: Arc<Mutex<ScanState>>,
+    ) -> Result<SimWatch> {
+        let zone: Tz = zone::resolve(profile.clock.time_zone.as_deref())?;
+        let now = host.now();
+        let clock = WatchClock {
+            set_at: now,
+            local: (now + profile.clock.offset)
+                .with_timezone(&zone)
+                .naive_local(),
+            drift_ppm: profile.clock.drift_ppm,
+        };
+        let state = WatchState {
+            // A zone the characteristic cannot describe leaves it unknown.
+            local_time_information: zone::local_time_information(&zone, &now).unwrap_or(
+                LocalTimeInformation {
+                    time_zone: None,
+                    dst_offset: DstOffset::Unknown,
+                },
+            ),
+            reference_time: ReferenceTimeInformation::just_updated(
+                TimeSource::Unknown,
+                TimeAccuracy::Unknown,
+            ),
+            reference_updated_at: now,
+            table: gatt_table(&profile),
+            connect_failures: profile.faults.connect_failures,
+            profile,
+            host,
+            started_at: now,
+            clock,
+            adjust_reason: AdjustReason::NONE,
+            connected: false,
+            discovered: false,
+            connection: 0,
+            subscribed: HashSet::new(),
+            listeners: Vec::new(),
+            measurements: 0,
+        };
+        Ok(SimWatch {
+            state: Arc::new(Mutex::new(state)),
+            scan,
+        })
+    }
+
+    /// Drops the connection, as when the watch goes out of range.
+    pub fn drop_connection(&self) {
+        let id = self.address();
+        {
+            let mut state = lock(&self.state);
+            if !state.connected {
+                return;
+            }
+            state.connected = false;
+            state.connection += 1;
+            state.subscribed.clear();
+        }
+        lock(&self.scan).emit(AdapterEvent::DeviceDisconnected(id));
+    }
+
+    /// Current local time of the watch clock.
+    pub fn local_time(&self) -> NaiveDateTime {
+        let state = lock(&self.state);
+        state.clock.now(state.host.now())
+    }
+
+    /// Waits for the latency of a GATT operation, then fails unless connected.
+    async fn operation(&self) -> BackendResult<()> {
+        let latency = lock(&self.state).profile.faults.latency;
+        time::sleep(latency).await;
+        if lock(&self.state).connected {
+            Ok(())
+        } else {
+            Err(btleplug::Error::NotConnected)
+        }
+    }
+
+    /// Notifies `uuid` every `interval` while subscribed during `connection`.
+    async fn tick(self, uuid: Uuid, interval: Duration, connection: u64) {
+        loop {
+            time::sleep(interval).await;
+            let mut state = lock(&self.state);
+            if state.connection != connection || !state.subscribed.contains(&uuid) {
+                return;
+            }
+            let value = match uuid {
+                HEART_RATE_MEASUREMENT_UUID => state.heart_rate_measurement(),
+                _ => match state.read(uuid) {
+                    Ok(value) => value,
+                    Err(_) => return,
+                },
+            };
+            state.notify(uuid, value);
+        }
+    }
+
+    /// Drops the connection numbered `connection` after `after`.
+    async fn disconnect_after(self, after: Duration, connection: u64) {
+        time::sleep(after).await;
+        if lock(&self.state).connection == connection {
+            self.drop_connection();
+        }
+    }
+}
+
+impl Peripheral for SimWatch {
+    type Id = BDAddr;
+
+    fn id(&self) -> BDAddr {
+        self.address()
+    }
+
+    fn address(&self) -> BDAddr {
+        lock(&self.state).profile.address
+    }
+
+    async fn properties(&self) -> BackendResult<Option<PeripheralProperties>> {
+        let state = lock(&self.state);
+        let profile = &state.profile;
+        let mut services = vec![CURRENT_TIME_SERVICE_UUID];
+        if profile.heart_rate.is_some() {
+            services.push(HEART_RATE_SERVICE_UUID);
+        }
+        Ok(Some(PeripheralProperties {
+            address: profile.address,
+            local_name: Some(profile.name.clone()),
+            tx_power_level: profile.tx_power,
+            rssi: Some(profile.rssi),
+            manufacturer_data: profile
+                .manufacturer
+                .iter()
+                .map(|m| (m.company_id, m.data.clone()))
+                .collect(),
+            services,
+            ..PeripheralProperties::default()
+        }))
+    }
+
+    fn services(&self) -> BTreeSet<Service> {
+        let state = lock(&self.state);
+        if state.discovered {
+            state.table.clone()
+        } else {
+            BTreeSet::new()
+        }
+    }
+
+    async fn is_connected(&self) -> BackendResult<bool> {
+        Ok(lock(&self.state).connected)
+    }
+
+    async fn connect(&self) -> BackendResult<()> {
+        let delay = lock(&self.state).profile.faults.connect_delay;
+        time::sleep(delay).await;
+        let (connection, disconnect_after) = {
+            let mut state = lock(&self.state);
+            if state.connected {
+                return Ok(());
+            }
+            if state.connect_failures > 0 {
+                state.connect_failures -= 1;
+                return Err(btleplug::Error::RuntimeError(String::from(
+                    "connection refused",
+                )));
+            }
+            state.connected = true;
+            state.connection += 1;
+            (state.connection, state.profile.faults.disconnect_after)
+        };
+        lock(&self.scan).emit(AdapterEvent::DeviceConnected(self.address()));
+        if let Some(after) = disconnect_after {
+            tokio::spawn(self.clone().disconnect_after(after, connection));
+        }
+        Ok(())
+    }
+
+    async fn disconnect(&self) -> BackendResult<()> {
+        self.drop_connection();
+        Ok(())
+    }
+
+    async fn discover_services(&self) -> BackendResult<()> {
+        self.operation().await?;
+        lock(&self.state).discovered = true;
+        Ok(())
+    }
+
+    async fn read(&self, characteristic: &Characteristic) -> BackendResult<Vec<u8>> {
+        self.operation().await?;
+        lock(&self.state).read(characteristic.uuid)
+    }
+
+    async fn write(
+        &self,
+        characteristic: &Characteristic,
+        data: &[u8],
+        _write_type: WriteType,
+    ) -> BackendResult<()> {
+        self.operation().await?;
+        let reply = lock(&self.state).write(characteristic.uuid, data)?;
+        match reply {
+            Reply::Nothing => {}
+            Reply::Notify(uuid, value) => lock(&self.state).notify(uuid, value),
+            Reply::Disconnect => self.drop_connection(),
+        }
+        Ok(())
+    }
+
+    async fn read_descriptor(&self, descriptor: &Descriptor) -> BackendResult<Vec<u8>> {
+        self.operation().await?;
+        lock(&self.state).read_descriptor(descriptor)
+    }
+
+    async fn subscribe(&self, characteristic: &Characteristic) -> BackendResult<()> {
+        self.operation().await?;
+        let uuid = characteristic.uuid;
+        let ticker = {
+            let mut state = lock(&self.state);
+            let notifies = state.characteristic(uuid).is_some_and(|c| {
+                c.properties
+                    .intersects(CharPropFlags::NOTIFY | CharPropFlags::INDICATE)
+            });
+            if !notifies {
+                return Err(not_permitted("subscription", uuid));
+            }
+            let interval = state.notify_interval(uuid);
+            let connection = state.connection;
+            let newly = state.subscribed.insert(uuid);
+            interval
+                .filter(|_| newly)
+                .map(|interval| (interval, connection))
+        };
+        if let Some((interval, connection)) = ticker {
+            tokio::spawn(self.clone().tick(uuid, interval, connection));
+        }
+        Ok(())
+    }
+
+    async fn unsubscribe(&self, characteristic: &Characteristic) -> BackendResult<()> {
+        self.operation().await?;
+        lock(&self.state).subscribed.remove(&characteristic.uuid);
+        Ok(())
+    }
+
+    async fn notifications(&self) -> BackendResult<NotificationStream> {
+        let (sender, receiver) = mpsc::unbounded();
+        lock(&self.state).listeners.push(sender);
+        Ok(Box::pin(receiver))
+    }
+}
+
+/// Services of the watch described by `profile`.
+fn gatt_table(profile: &SimProfile) -> BTreeSet<Service> {
+    let read = CharPropFlags::READ;
+    let read_write = CharPropFlags::READ | CharPropFlags::WRITE;
+    let notify = CharPropFlags::NOTIFY;
+    let mut table = BTreeSet::new();
+    table.insert(service(
+        CURRENT_TIME_SERVICE_UUID,
+        vec![
+            characteristic(CURRENT_TIME_UUID, read_write | notify, &[]),
+            characteristic(LOCAL_TIME_INFORMATION_UUID, read_write, &[]),
+            characteristic(REFERENCE_TIME_INFORMATION_UUID, read_write, &[]),
+        ],
+    ));
+    if profile.battery.is_some() {
+        table.insert(service(
+            BATTERY_SERVICE_UUID,
+            vec![characteristic(
+                BATTERY_LEVEL_UUID,
+                read | notify,
+                &[PRESENTATION_FORMAT_UUID],
+            )],
+        ));
+    }
+    if profile.heart_rate.is_some() {
+        table.insert(service(
+            HEART_RATE_SERVICE_UUID,
+            vec![
+                characteristic(HEART_RATE_MEASUREMENT_UUID, notify, &[]),
+                characteristic(BODY_SENSOR_LOCATION_UUID, read, &[]),
+                characteristic(HEART_RATE_CONTROL_POINT_UUID, CharPropFlags::WRITE, &[]),
+            ],
+        ));
+    }
+    if let Some(info) = &profile.device_information {
+        let strings = [
+            (MANUFACTURER_NAME_UUID, &info.manufacturer),
+            (MODEL_NUMBER_UUID, &info.model),
+            (SERIAL_NUMBER_UUID, &info.serial),
+            (HARDWARE_REVISION_UUID, &info.hardware),
+            (FIRMWARE_REVISION_UUID, &info.firmware),
+            (SOFTWARE_REVISION_UUID, &info.software),
+        ];
+        table.insert(service(
+            DEVICE_INFORMATION_SERVICE_UUID,
+            strings
+                .iter()
+                .filter(|(_, value)| value.is_some())
+                .map(|(uuid, _)| characteristic(*uuid, read, &[]))
+                .collect(),
+        ));
+    }
+    if profile.uart.is_some() {
+        table.insert(service(
+            UART_SERVICE_UUID,
+            vec![
+                characteristic(
+                    UART_RX_UUID,
+                    CharPropFlags::WRITE | CharPropFlags::WRITE_WITHOUT_RESPONSE,
+                    &[],
+                ),
+                characteristic(UART_TX_UUID, notify, &[]),
+            ],
+        ));
+    }
+    table
+}
+
+/// A primary service, setting the service of its characteristics and descriptors.
+fn service(uuid: Uuid, characteristics: Vec<Characteristic>) -> Service {
+    let characteristics = characteristics
+        .into_iter()
+        .map(|mut characteristic| {
+            characteristic.service_uuid = uuid;
+            characteristic.descriptors = characteristic
+                .descriptors
+                .into_iter()
+                .map(|descriptor| Descriptor {
+                    service_uuid: uuid,
+                    ..descriptor
+                })
+                .collect();
+            characteristic
+        })
+        .collect();
+    Service {
+        uuid,
+        primary: true,
+        characteristics,
+    }
+}
+
+/// A characteristic with `descriptors`, plus a Client Characteristic
+/// Configuration when it notifies.
+fn characteristic(uuid: Uuid, properties: CharPropFlags, descriptors: &[Uuid]) -> Characteristic {
+    let mut descriptors: BTreeSet<Descriptor> = descriptors
+        .iter()
+        .map(|descriptor| Descriptor {
+            uuid: *descriptor,
+            service_uuid: Uuid::nil(),
+            characteristic_uuid: uuid,
+        })
+        .collect();
+    if properties.contains(CharPropFlags::NOTIFY) {
+        descriptors.insert(Descriptor {
+            uuid: CLIENT_CONFIGURATION_UUID,
+            service_uuid: Uuid::nil(),
+            characteristic_uuid: uuid,
+        });
+    }
+    Characteristic {
+        uuid,
+        service_uuid: Uuid::nil(),
+        properties,
+        descriptors,
+    }
+}
+
+fn runtime(err: impl fmt::Display) -> btleplug::Error {
+    btleplug::Error::RuntimeError(err.to_string())
+}
+
+fn not_permitted(operation: &str, uuid: Uuid) -> btleplug::Error {
+    btleplug::Error::NotSupported(format!("{} of {} not permitted", operation, uuid))
+}
+
+fn lock<T>(state: &Mutex<T>) -> MutexGuard<'_, T> {
+    state
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,234 @@
+//! Description of a simulated watch, loaded from TOML.
+//!
+//! ```toml
+//! name = "Amazfit GTS 4 Mini"
+//! address = "C0:FF:EE:00:00:01"
+//!
+//! [clock]
+//! time_zone = "Europe/Rome"
+//! offset = "-3s"
+//! drift_ppm = 25.0
+//!
+//! [battery]
+//! level = 80
+//! notify_interval = "30s"
+//! ```
+//!
+//! Durations are written as for `--offset`, e.g. `250ms` or `1m30s`. The
+//! Current Time Service is always there; the other services only when their
+//! table is present.
+
+use crate::clock;
+use crate::error::{Result, SmartwatchError};
+use crate::gatt;
+use btleplug::api::BDAddr;
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimProfile {
+    /// Advertised local name.
+    pub name: String,
+    #[serde(deserialize_with = "address")]
+    pub address: BDAddr,
+    /// Signal strength the adverts are received with, in dBm.
+    #[serde(default = "default_rssi")]
+    pub rssi: i16,
+    pub tx_power: Option<i16>,
+    /// Time between two adverts while scanning.
+    #[serde(
+        default = "default_advertising_interval",
+        deserialize_with = "clock::deserialize_interval"
+    )]
+    pub advertising_interval: Duration,
+    pub manufacturer: Option<ManufacturerProfile>,
+    #[serde(default)]
+    pub clock: ClockProfile,
+    pub battery: Option<BatteryProfile>,
+    pub heart_rate: Option<HeartRateProfile>,
+    pub device_information: Option<DeviceInformationProfile>,
+    pub uart: Option<UartProfile>,
+    #[serde(default)]
+    pub faults: FaultProfile,
+}
+
+/// Manufacturer data put in every advert.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManufacturerProfile {
+    pub company_id: u16,
+    /// Payload in hex, e.g. `"02 00 c0 ff ee"`.
+    #[serde(deserialize_with = "hex")]
+    pub data: Vec<u8>,
+}
+
+/// The watch's own clock.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClockProfile {
+    /// IANA zone the watch shows the time in; the host zone if omitted.
+    pub time_zone: Option<String>,
+    /// How far ahead of the host the watch starts, negative when behind.
+    #[serde(default, deserialize_with = "clock::deserialize_offset")]
+    pub offset: chrono::Duration,
+    /// How much faster than the host the watch runs, in parts per million.
+    #[serde(default)]
+    pub drift_ppm: f64,
+    /// Notify the Current Time at this interval once subscribed.
+    #[serde(default, deserialize_with = "clock::deserialize_optional_interval")]
+    pub notify_interval: Option<Duration>,
+}
+
+/// Battery Service (0x180F).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatteryProfile {
+    /// Charge when the simulation starts, in percent.
+    #[serde(default = "full_battery")]
+    pub level: u8,
+    /// Percentage points lost per hour.
+    #[serde(default)]
+    pub drain_per_hour: f64,
+    /// Notify the level at this interval once subscribed.
+    #[serde(default, deserialize_with = "clock::deserialize_optional_interval")]
+    pub notify_interval: Option<Duration>,
+}
+
+/// Heart Rate Service (0x180D).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HeartRateProfile {
+    /// Average pulse, in beats per minute.
+    #[serde(default = "resting_pulse")]
+    pub bpm: u8,
+    /// The pulse sweeps this far above and below `bpm`.
+    #[serde(default)]
+    pub variation: u8,
+    /// Body Sensor Location, 2 for the wrist.
+    #[serde(default = "wrist")]
+    pub sensor_location: u8,
+    /// Time between two measurements once subscribed.
+    #[serde(
+        default = "one_second",
+        deserialize_with = "clock::deserialize_interval"
+    )]
+    pub notify_interval: Duration,
+}
+
+/// Device Information Service (0x180A); only the strings given are exposed.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceInformationProfile {
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub hardware: Option<String>,
+    pub firmware: Option<String>,
+    pub software: Option<String>,
+}
+
+/// Nordic UART Service.
+///
+/// Lines written to RX are answered on TX: `time` with the watch time,
+/// `battery` with the battery level, `disconnect` by dropping the connection,
+/// and anything else by echoing it when `echo` is set.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UartProfile {
+    #[serde(default = "enabled")]
+    pub echo: bool,
+}
+
+/// Misbehaviour to inject.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FaultProfile {
+    /// Refuse this many connection attempts first.
+    #[serde(default)]
+    pub connect_failures: usize,
+    /// Time taken to establish a connection.
+    #[serde(default, deserialize_with = "clock::deserialize_interval")]
+    pub connect_delay: Duration,
+    /// Time taken by every read, write and subscription.
+    #[serde(default, deserialize_with = "clock::deserialize_interval")]
+    pub latency: Duration,
+    /// Drop every connection after this long.
+    #[serde(default, deserialize_with = "clock::deserialize_optional_interval")]
+    pub disconnect_after: Option<Duration>,
+}
+
+impl SimProfile {
+    /// Loads and checks a profile.
+    pub fn load(path: &Path) -> Result<SimProfile> {
+        let text = fs::read_to_string(path).map_err(SmartwatchError::io(path))?;
+        let invalid = |message: String| {
+            SmartwatchError::io(path)(io::Error::new(io::ErrorKind::InvalidData, message))
+        };
+        let profile: SimProfile = toml::from_str(&text).map_err(|err| invalid(err.to_string()))?;
+        profile.check().map_err(invalid)?;
+        Ok(profile)
+    }
+
+    fn check(&self) -> std::result::Result<(), String> {
+        if let Some(battery) = &self.battery {
+            if battery.level > 100 {
+                return Err(format!("battery level {} is above 100", battery.level));
+            }
+        }
+        if let Some(heart_rate) = &self.heart_rate {
+            if heart_rate.bpm < heart_rate.variation {
+                return Err(String::from("heart rate variation exceeds the pulse"));
+            }
+        }
+        if !self.clock.drift_ppm.is_finite() || self.clock.drift_ppm.abs() >= 1e6 {
+            return Err(format!("clock drift of {} ppm", self.clock.drift_ppm));
+        }
+        if self.advertising_interval.is_zero() {
+            return Err(String::from("advertising interval must not be zero"));
+        }
+        Ok(())
+    }
+}
+
+fn default_rssi() -> i16 {
+    -60
+}
+
+fn default_advertising_interval() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn full_battery() -> u8 {
+    100
+}
+
+fn resting_pulse() -> u8 {
+    70
+}
+
+fn wrist() -> u8 {
+    2
+}
+
+fn one_second() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn address<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<BDAddr, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    text.parse()
+        .map_err(|_| de::Error::custom(format!("invalid address {:?}", text)))
+}
+
+fn hex<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<u8>, D::Error> {
+    gatt::parse_hex(&String::deserialize(deserializer)?).map_err(de::Error::custom)
+}
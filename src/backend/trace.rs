@@ -0,0 +1,471 @@
+//! Traces of the traffic between the tool and a Bluetooth stack.
+//!
+//! A trace is a JSON-lines file: a [`Header`] naming the format version, then
+//! one [`Entry`] per operation, advert or notification in the order they
+//! happened. [`record`](super::record) writes traces and
+//! [`replay`](super::replay) serves them back.
+//!
+//! ```text
+//! {"format":"smartwatch-trace","version":1,"started_at":"2026-03-01T12:00:00Z"}
+//! {"at_us":0,"took_us":15,"op":"adapters","result":{"ok":1}}
+//! {"at_us":1520,"took_us":40000,"op":"read","device":"C0:FF:EE:00:00:01","characteristic":"00002a2b-0000-1000-8000-00805f9b34fb","result":{"ok":"EA 07 03 01 0D 00 00 07 00 01"}}
+//! ```
+
+use super::{AdapterEvent, BackendResult};
+use crate::dump::{self, PROPERTY_NAMES};
+use crate::gatt;
+use btleplug::api::{
+    BDAddr, CharPropFlags, Characteristic, Descriptor, PeripheralProperties, Service,
+};
+use chrono::{DateTime, Utc};
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use uuid::Uuid;
+
+/// Value of [`Header::format`].
+pub const TRACE_FORMAT: &str = "smartwatch-trace";
+/// Version of the trace format written, the only one read back.
+pub const TRACE_VERSION: u32 = 1;
+
+/// First line of a trace.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    pub format: String,
+    pub version: u32,
+    pub started_at: DateTime<Utc>,
+}
+
+impl Header {
+    pub fn new(started_at: DateTime<Utc>) -> Header {
+        Header {
+            format: TRACE_FORMAT.to_string(),
+            version: TRACE_VERSION,
+            started_at,
+        }
+    }
+}
+
+/// One line of a trace after the header.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Time since the start of the session, in microseconds.
+    pub at_us: u64,
+    /// Time the operation took, in microseconds; absent for adverts and notifications.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub took_us: Option<u64>,
+    #[serde(flatten)]
+    pub record: Record,
+}
+
+/// What happened. Devices are named by their platform identifier.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Record {
+    /// Adapters listed, answered with their number.
+    Adapters {
+        result: Outcome<usize>,
+    },
+    StartScan {
+        adapter: usize,
+        result: Outcome<()>,
+    },
+    StopScan {
+        adapter: usize,
+        result: Outcome<()>,
+    },
+    /// Event reported by an adapter.
+    Event {
+        adapter: usize,
+        event: EventRecord,
+    },
+    /// Peripheral looked up, answered with its address.
+    Peripheral {
+        adapter: usize,
+        device: String,
+        result: Outcome<Address>,
+    },
+    Properties {
+        device: String,
+        result: Outcome<Option<PropertiesRecord>>,
+    },
+    IsConnected {
+        device: String,
+        result: Outcome<bool>,
+    },
+    Connect {
+        device: String,
+        result: Outcome<()>,
+    },
+    Disconnect {
+        device: String,
+        result: Outcome<()>,
+    },
+    /// Service discovery, answered with the services found.
+    DiscoverServices {
+        device: String,
+        result: Outcome<Vec<ServiceRecord>>,
+    },
+    Read {
+        device: String,
+        characteristic: Uuid,
+        result: Outcome<Bytes>,
+    },
+    Write {
+        device: String,
+        characteristic: Uuid,
+        data: Bytes,
+        with_response: bool,
+        result: Outcome<()>,
+    },
+    ReadDescriptor {
+        device: String,
+        characteristic: Uuid,
+        descriptor: Uuid,
+        result: Outcome<Bytes>,
+    },
+    Subscribe {
+        device: String,
+        characteristic: Uuid,
+        result: Outcome<()>,
+    },
+    Unsubscribe {
+        device: String,
+        characteristic: Uuid,
+        result: Outcome<()>,
+    },
+    Notification {
+        device: String,
+        characteristic: Uuid,
+        value: Bytes,
+    },
+}
+
+/// Result of an operation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome<T> {
+    Ok(T),
+    Err(ErrorRecord),
+}
+
+impl<T> Outcome<T> {
+    /// Records `result`, keeping what `value` takes from a success.
+    pub fn of<U>(result: &BackendResult<U>, value: impl FnOnce(&U) -> T) -> Outcome<T> {
+        match result {
+            Ok(ok) => Outcome::Ok(value(ok)),
+            Err(err) => Outcome::Err(ErrorRecord::from(err)),
+        }
+    }
+
+    /// The recorded result, with the error rebuilt as close to the original as possible.
+    pub fn into_result(self) -> BackendResult<T> {
+        match self {
+            Outcome::Ok(value) => Ok(value),
+            Outcome::Err(err) => Err(err.into_error()),
+        }
+    }
+}
+
+/// A failed operation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorRecord {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+/// The btleplug errors the tool tells apart; the others are replayed as
+/// runtime errors carrying the original message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    PermissionDenied,
+    DeviceNotFound,
+    NotConnected,
+    NotSupported,
+    Other,
+}
+
+impl From<&btleplug::Error> for ErrorRecord {
+    fn from(err: &btleplug::Error) -> ErrorRecord {
+        let (kind, message) = match err {
+            btleplug::Error::PermissionDenied => (ErrorKind::PermissionDenied, err.to_string()),
+            btleplug::Error::DeviceNotFound => (ErrorKind::DeviceNotFound, err.to_string()),
+            btleplug::Error::NotConnected => (ErrorKind::NotConnected, err.to_string()),
+            btleplug::Error::NotSupported(message) => (ErrorKind::NotSupported, message.clone()),
+            btleplug::Error::RuntimeError(message) => (ErrorKind::Other, message.clone()),
+            _ => (ErrorKind::Other, err.to_string()),
+        };
+        ErrorRecord { kind, message }
+    }
+}
+
+impl ErrorRecord {
+    pub fn into_error(self) -> btleplug::Error {
+        match self.kind {
+            ErrorKind::PermissionDenied => btleplug::Error::PermissionDenied,
+            ErrorKind::DeviceNotFound => btleplug::Error::DeviceNotFound,
+            ErrorKind::NotConnected => btleplug::Error::NotConnected,
+            ErrorKind::NotSupported => btleplug::Error::NotSupported(self.message),
+            ErrorKind::Other => btleplug::Error::RuntimeError(self.message),
+        }
+    }
+}
+
+/// An adapter event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EventRecord {
+    DeviceDiscovered {
+        device: String,
+    },
+    DeviceUpdated {
+        device: String,
+    },
+    DeviceConnected {
+        device: String,
+    },
+    DeviceDisconnected {
+        device: String,
+    },
+    ManufacturerData {
+        device: String,
+        /// Company identifier and payload of each entry.
+        data: Vec<(u16, Bytes)>,
+    },
+    ServiceData {
+        device: String,
+        data: Vec<(Uuid, Bytes)>,
+    },
+    Services {
+        device: String,
+        services: Vec<Uuid>,
+    },
+}
+
+impl EventRecord {
+    pub fn new<I: Display>(event: &AdapterEvent<I>) -> EventRecord {
+        let device = event.id().to_string();
+        match event {
+            AdapterEvent::DeviceDiscovered(_) => EventRecord::DeviceDiscovered { device },
+            AdapterEvent::DeviceUpdated(_) => EventRecord::DeviceUpdated { device },
+            AdapterEvent::DeviceConnected(_) => EventRecord::DeviceConnected { device },
+            AdapterEvent::DeviceDisconnected(_) => EventRecord::DeviceDisconnected { device },
+            AdapterEvent::ManufacturerData {
+                manufacturer_data, ..
+            } => EventRecord::ManufacturerData {
+                device,
+                data: sorted(
+                    manufacturer_data
+                        .iter()
+                        .map(|(id, data)| (*id, Bytes(data.clone()))),
+                ),
+            },
+            AdapterEvent::ServiceData { service_data, .. } => EventRecord::ServiceData {
+                device,
+                data: sorted(
+                    service_data
+                        .iter()
+                        .map(|(uuid, data)| (*uuid, Bytes(data.clone()))),
+                ),
+            },
+            AdapterEvent::Services { services, .. } => EventRecord::Services {
+                device,
+                services: services.clone(),
+            },
+        }
+    }
+
+    /// The event, about the device named as in the trace.
+    pub fn to_event(&self) -> AdapterEvent<String> {
+        match self.clone() {
+            EventRecord::DeviceDiscovered { device } => AdapterEvent::DeviceDiscovered(device),
+            EventRecord::DeviceUpdated { device } => AdapterEvent::DeviceUpdated(device),
+            EventRecord::DeviceConnected { device } => AdapterEvent::DeviceConnected(device),
+            EventRecord::DeviceDisconnected { device } => AdapterEvent::DeviceDisconnected(device),
+            EventRecord::ManufacturerData { device, data } => AdapterEvent::ManufacturerData {
+                id: device,
+                manufacturer_data: data.into_iter().map(|(id, data)| (id, data.0)).collect(),
+            },
+            EventRecord::ServiceData { device, data } => AdapterEvent::ServiceData {
+                id: device,
+                service_data: data
+                    .into_iter()
+                    .map(|(uuid, data)| (uuid, data.0))
+                    .collect(),
+            },
+            EventRecord::Services { device, services } => AdapterEvent::Services {
+                id: device,
+                services,
+            },
+        }
+    }
+}
+
+/// What a device advertised.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PropertiesRecord {
+    pub address: Address,
+    pub local_name: Option<String>,
+    pub tx_power_level: Option<i16>,
+    pub rssi: Option<i16>,
+    pub manufacturer_data: Vec<(u16, Bytes)>,
+    pub service_data: Vec<(Uuid, Bytes)>,
+    pub services: Vec<Uuid>,
+}
+
+impl From<&PeripheralProperties> for PropertiesRecord {
+    fn from(properties: &PeripheralProperties) -> PropertiesRecord {
+        PropertiesRecord {
+            address: Address(properties.address),
+            local_name: properties.local_name.clone(),
+            tx_power_level: properties.tx_power_level,
+            rssi: properties.rssi,
+            manufacturer_data: sorted(
+                properties
+                    .manufacturer_data
+                    .iter()
+                    .map(|(id, data)| (*id, Bytes(data.clone()))),
+            ),
+            service_data: sorted(
+                properties
+                    .service_data
+                    .iter()
+                    .map(|(uuid, data)| (*uuid, Bytes(data.clone()))),
+            ),
+            services: properties.services.clone(),
+        }
+    }
+}
+
+impl From<PropertiesRecord> for PeripheralProperties {
+    fn from(record: PropertiesRecord) -> PeripheralProperties {
+        PeripheralProperties {
+            address: record.address.0,
+            local_name: record.local_name,
+            tx_power_level: record.tx_power_level,
+            rssi: record.rssi,
+            manufacturer_data: record
+                .manufacturer_data
+                .into_iter()
+                .map(|(id, data)| (id, data.0))
+                .collect(),
+            service_data: record
+                .service_data
+                .into_iter()
+                .map(|(uuid, data)| (uuid, data.0))
+                .collect(),
+            services: record.services,
+            ..PeripheralProperties::default()
+        }
+    }
+}
+
+/// A discovered service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceRecord {
+    pub uuid: Uuid,
+    pub primary: bool,
+    pub characteristics: Vec<CharacteristicRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CharacteristicRecord {
+    pub uuid: Uuid,
+    /// Names of the properties, see [`PROPERTY_NAMES`].
+    pub properties: Vec<String>,
+    pub descriptors: Vec<Uuid>,
+}
+
+impl From<&Service> for ServiceRecord {
+    fn from(service: &Service) -> ServiceRecord {
+        ServiceRecord {
+            uuid: service.uuid,
+            primary: service.primary,
+            characteristics: service
+                .characteristics
+                .iter()
+                .map(|characteristic| CharacteristicRecord {
+                    uuid: characteristic.uuid,
+                    properties: dump::property_names(characteristic.properties),
+                    descriptors: characteristic.descriptors.iter().map(|d| d.uuid).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&ServiceRecord> for Service {
+    fn from(record: &ServiceRecord) -> Service {
+        let characteristics = record
+            .characteristics
+            .iter()
+            .map(|characteristic| Characteristic {
+                uuid: characteristic.uuid,
+                service_uuid: record.uuid,
+                properties: PROPERTY_NAMES
+                    .iter()
+                    .filter(|(_, name)| characteristic.properties.iter().any(|p| p == name))
+                    .fold(CharPropFlags::empty(), |flags, (flag, _)| flags | *flag),
+                descriptors: characteristic
+                    .descriptors
+                    .iter()
+                    .map(|uuid| Descriptor {
+                        uuid: *uuid,
+                        service_uuid: record.uuid,
+                        characteristic_uuid: characteristic.uuid,
+                    })
+                    .collect(),
+            })
+            .collect();
+        Service {
+            uuid: record.uuid,
+            primary: record.primary,
+            characteristics,
+        }
+    }
+}
+
+/// Bytes, written in hex as printed by [`gatt::hex`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bytes(pub Vec<u8>);
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&gatt::hex(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        gatt::parse_hex(&text).map(Bytes).map_err(de::Error::custom)
+    }
+}
+
+/// A device address, written as `C0:FF:EE:00:00:01`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Address(pub BDAddr);
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Address, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse()
+            .map(Address)
+            .map_err(|_| de::Error::custom(format!("invalid address {:?}", text)))
+    }
+}
+
+/// Map entries in a stable order, so that equal maps record the same way.
+fn sorted<K: Ord, V>(entries: impl Iterator<Item = (K, V)>) -> Vec<(K, V)> {
+    let mut entries: Vec<(K, V)> = entries.collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
@@ -0,0 +1,1268 @@
+//! Import of Android btsnoop HCI logs as [traces](crate::backend::trace).
+//!
+//! Android writes the HCI traffic of its Bluetooth stack to `btsnoop_hci.log`
+//! once "Enable Bluetooth HCI snoop log" is on in the developer options, in
+//! the btsnoop format of RFC 1761. The import follows the LE connections of
+//! the log, reassembles the L2CAP frames of their ACL data and names the
+//! attribute handles of the ATT PDUs after the service discovery the phone
+//! ran; operations on handles the log does not name are counted and left out.
+//! Adverts received while scanning become discovery events, so that the trace
+//! replays like a session recorded by the tool.
+//!
+//! Huami watches authenticate the app on the Huami Auth characteristic. When
+//! pairing, the app writes its 16-byte key in clear (`01 00 <key>`); on every
+//! connection the watch then sends a random number (`10 02 01 <number>`) that
+//! the app answers encrypted with the key (`03 00 <encrypted>`). The import
+//! reports these exchanges, so the key of a pairing by the official app can
+//! be reused.
+
+use crate::backend::trace::{
+    Address, Bytes, CharacteristicRecord, Entry, ErrorKind, ErrorRecord, EventRecord, Header,
+    Outcome, PropertiesRecord, Record, ServiceRecord,
+};
+use crate::dump;
+use crate::error::{Result, SmartwatchError};
+use btleplug::api::bleuuid::{uuid_from_u16, uuid_from_u32};
+use btleplug::api::{BDAddr, CharPropFlags};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use uuid::Uuid;
+
+/// Huami Auth characteristic, in the FEE1 service of Amazfit and Zepp watches.
+pub const HUAMI_AUTH_UUID: Uuid = Uuid::from_u128(0x00000009_0000_3512_2118_0009af100700);
+
+const MAGIC: &[u8; 8] = b"btsnoop\0";
+const VERSION: u32 = 1;
+/// Datalink of logs whose record flags tell commands from events.
+const DATALINK_HCI: u32 = 1001;
+/// Datalink of logs whose packets start with their H4 type, as Android writes them.
+const DATALINK_H4: u32 = 1002;
+const RECORD_HEADER_LEN: usize = 24;
+/// Timestamps count microseconds from midnight, January 1st of year 0.
+const UNIX_EPOCH_MICROS: i64 = 0x00E0_3AB4_4A67_6000;
+
+const DISCONNECT: u16 = 0x0406;
+const LE_SET_SCAN_ENABLE: u16 = 0x200C;
+const LE_CREATE_CONNECTION: u16 = 0x200D;
+const LE_SET_EXTENDED_SCAN_ENABLE: u16 = 0x2042;
+const LE_EXTENDED_CREATE_CONNECTION: u16 = 0x2043;
+
+const DISCONNECTION_COMPLETE: u8 = 0x05;
+const LE_META: u8 = 0x3E;
+const LE_CONNECTION_COMPLETE: u8 = 0x01;
+const LE_ADVERTISING_REPORT: u8 = 0x02;
+const LE_ENHANCED_CONNECTION_COMPLETE: u8 = 0x0A;
+const LE_EXTENDED_ADVERTISING_REPORT: u8 = 0x0D;
+const LE_ENHANCED_CONNECTION_COMPLETE_V2: u8 = 0x29;
+/// RSSI and Tx power value of adverts that do not report them.
+const NOT_AVAILABLE: i8 = 127;
+
+const ATT_CID: u16 = 0x0004;
+const ERROR_RESPONSE: u8 = 0x01;
+const FIND_INFORMATION_REQUEST: u8 = 0x04;
+const FIND_INFORMATION_RESPONSE: u8 = 0x05;
+const FIND_BY_TYPE_VALUE_REQUEST: u8 = 0x06;
+const FIND_BY_TYPE_VALUE_RESPONSE: u8 = 0x07;
+const READ_BY_TYPE_REQUEST: u8 = 0x08;
+const READ_BY_TYPE_RESPONSE: u8 = 0x09;
+const READ_REQUEST: u8 = 0x0A;
+const READ_RESPONSE: u8 = 0x0B;
+const READ_BLOB_REQUEST: u8 = 0x0C;
+const READ_BLOB_RESPONSE: u8 = 0x0D;
+const READ_BY_GROUP_TYPE_REQUEST: u8 = 0x10;
+const READ_BY_GROUP_TYPE_RESPONSE: u8 = 0x11;
+const WRITE_REQUEST: u8 = 0x12;
+const NOTIFICATION: u8 = 0x1B;
+const INDICATION: u8 = 0x1D;
+const WRITE_COMMAND: u8 = 0x52;
+
+const PRIMARY_SERVICE_UUID: Uuid = uuid_from_u16(0x2800);
+const SECONDARY_SERVICE_UUID: Uuid = uuid_from_u16(0x2801);
+const INCLUDE_UUID: Uuid = uuid_from_u16(0x2802);
+const CHARACTERISTIC_UUID: Uuid = uuid_from_u16(0x2803);
+const CCCD_UUID: Uuid = uuid_from_u16(0x2902);
+
+/// What an HCI packet carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketKind {
+    Command,
+    Acl,
+    Sco,
+    Event,
+    Iso,
+    Other,
+}
+
+impl PacketKind {
+    fn from_h4(indicator: u8) -> PacketKind {
+        match indicator {
+            0x01 => PacketKind::Command,
+            0x02 => PacketKind::Acl,
+            0x03 => PacketKind::Sco,
+            0x04 => PacketKind::Event,
+            0x05 => PacketKind::Iso,
+            _ => PacketKind::Other,
+        }
+    }
+}
+
+/// A packet of the log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    pub at: DateTime<Utc>,
+    /// Whether the controller passed it to the host, rather than the other way round.
+    pub received: bool,
+    pub kind: PacketKind,
+    /// The HCI packet, without its H4 type.
+    pub data: Vec<u8>,
+}
+
+/// Reads the packets of the log at `path`.
+pub fn read(path: &Path) -> Result<Vec<Packet>> {
+    let bytes = fs::read(path).map_err(SmartwatchError::io(path))?;
+    parse(&bytes).map_err(SmartwatchError::io(path))
+}
+
+/// Parses a btsnoop log. A record cut short, as when the log was copied while
+/// being written, ends the log.
+pub fn parse(bytes: &[u8]) -> io::Result<Vec<Packet>> {
+    if bytes.len() < 16 || &bytes[..8] != MAGIC {
+        return Err(invalid("not a btsnoop log".to_string()));
+    }
+    let version = be_u32(&bytes[8..12]);
+    if version != VERSION {
+        return Err(invalid(format!(
+            "btsnoop version {} is not supported, expected {}",
+            version, VERSION
+        )));
+    }
+    let datalink = be_u32(&bytes[12..16]);
+    if datalink != DATALINK_HCI && datalink != DATALINK_H4 {
+        return Err(invalid(format!("btsnoop datalink {} is not HCI", datalink)));
+    }
+
+    let mut packets = Vec::new();
+    let mut rest = &bytes[16..];
+    while !rest.is_empty() {
+        let Some(header) = rest.get(..RECORD_HEADER_LEN) else {
+            log::warn!("The btsnoop log ends within a record header");
+            break;
+        };
+        let included = be_u32(&header[4..8]) as usize;
+        let flags = be_u32(&header[8..12]);
+        let timestamp = i64::from_be_bytes(header[16..24].try_into().expect("8 bytes"));
+        let Some(data) = rest.get(RECORD_HEADER_LEN..RECORD_HEADER_LEN + included) else {
+            log::warn!("The btsnoop log ends within a packet");
+            break;
+        };
+        rest = &rest[RECORD_HEADER_LEN + included..];
+
+        let received = flags & 0x01 != 0;
+        let (kind, data) = if datalink == DATALINK_H4 {
+            match data.split_first() {
+                Some((&indicator, data)) => (PacketKind::from_h4(indicator), data),
+                None => continue,
+            }
+        } else if flags & 0x02 != 0 {
+            let kind = if received {
+                PacketKind::Event
+            } else {
+                PacketKind::Command
+            };
+            (kind, data)
+        } else {
+            (PacketKind::Acl, data)
+        };
+        let unix = timestamp.saturating_sub(UNIX_EPOCH_MICROS);
+        packets.push(Packet {
+            at: DateTime::from_timestamp(
+                unix.div_euclid(1_000_000),
+                (unix.rem_euclid(1_000_000) * 1000) as u32,
+            )
+            .unwrap_or_default(),
+            received,
+            kind,
+            data: data.to_vec(),
+        });
+    }
+    Ok(packets)
+}
+
+/// A log turned into a trace.
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub header: Header,
+    pub entries: Vec<Entry>,
+    pub summary: Summary,
+}
+
+impl Import {
+    /// Writes the trace to `path`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let write = || -> io::Result<()> {
+            let mut writer = BufWriter::new(File::create(path)?);
+            serde_json::to_writer(&mut writer, &self.header)?;
+            writeln!(writer)?;
+            for entry in &self.entries {
+                serde_json::to_writer(&mut writer, entry)?;
+                writeln!(writer)?;
+            }
+            writer.flush()
+        };
+        write().map_err(SmartwatchError::io(path))
+    }
+}
+
+/// What the import found in a log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Summary {
+    pub started_at: DateTime<Utc>,
+    pub packets: usize,
+    /// ATT PDUs reassembled from the ACL data.
+    pub att_pdus: usize,
+    /// Devices whose adverts were received.
+    pub advertisers: usize,
+    pub connections: Vec<ConnectionSummary>,
+    pub auth: Vec<AuthExchange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConnectionSummary {
+    /// Address of the device, or the HCI handle when the log starts within the connection.
+    pub device: String,
+    pub handle: u16,
+    pub connected_at: Option<DateTime<Utc>>,
+    pub att_pdus: usize,
+    /// Characteristics and descriptors named by the discovery in the log.
+    pub attributes: usize,
+    /// Operations written to the trace.
+    pub operations: usize,
+    /// Operations left out of the trace, on handles the log does not name or
+    /// with nothing to answer in a replay.
+    pub skipped: usize,
+}
+
+/// Huami authentication seen on a connection.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AuthExchange {
+    pub device: String,
+    /// Key the app gave the watch when pairing, as `0x` and 32 hex digits the
+    /// way Gadgetbridge takes it.
+    pub key: Option<String>,
+    /// Random number the watch asked the app to encrypt.
+    pub challenge: Option<Bytes>,
+    /// The number encrypted by the app.
+    pub response: Option<Bytes>,
+    /// Whether the watch accepted the app; `None` if the log ends before it answers.
+    pub accepted: Option<bool>,
+}
+
+impl AuthExchange {
+    fn written(&mut self, characteristic: Uuid, value: &[u8]) {
+        if characteristic != HUAMI_AUTH_UUID || value.len() < 18 {
+            return;
+        }
+        match value[0] & 0x0F {
+            0x01 => {
+                let digits: String = value[2..18].iter().map(|b| format!("{:02x}", b)).collect();
+                self.key = Some(format!("0x{}", digits));
+            }
+            0x03 => self.response = Some(Bytes(value[2..18].to_vec())),
+            _ => {}
+        }
+    }
+
+    fn notified(&mut self, characteristic: Uuid, value: &[u8]) {
+        if characteristic != HUAMI_AUTH_UUID || value.len() < 3 || value[0] != 0x10 {
+            return;
+        }
+        match value[1] & 0x0F {
+            0x02 if value[2] == 0x01 && value.len() >= 19 => {
+                self.challenge = Some(Bytes(value[3..19].to_vec()))
+            }
+            0x03 => self.accepted = Some(value[2] == 0x01),
+            _ => {}
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.key.is_none()
+            && self.challenge.is_none()
+            && self.response.is_none()
+            && self.accepted.is_none()
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Log started {}: {} packets, {} ATT PDUs, adverts of {} devices",
+            self.started_at, self.packets, self.att_pdus, self.advertisers
+        )?;
+        for connection in &self.connections {
+            writeln!(
+                f,
+                "{} (handle 0x{:04X}): {} ATT PDUs, {} attributes named, {} operations, {} skipped",
+                connection.device,
+                connection.handle,
+                connection.att_pdus,
+                connection.attributes,
+                connection.operations,
+                connection.skipped
+            )?;
+        }
+        for auth in &self.auth {
+            let outcome = match auth.accepted {
+                Some(true) => "accepted",
+                Some(false) => "refused",
+                None => "unanswered",
+            };
+            match &auth.key {
+                Some(key) => {
+                    writeln!(f, "Huami auth of {}: key {}, {}", auth.device, key, outcome)?
+                }
+                None => writeln!(
+                    f,
+                    "Huami auth of {}: key not in the log, {}",
+                    auth.device, outcome
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Turns the packets of a log into a trace.
+pub fn import(packets: &[Packet]) -> Import {
+    let origin = packets
+        .first()
+        .map_or_else(DateTime::default, |packet| packet.at);
+    let mut importer = Importer {
+        trace: Trace {
+            origin,
+            entries: Vec::new(),
+        },
+        connections: Vec::new(),
+        open: HashMap::new(),
+        fragments: HashMap::new(),
+        adverts: HashMap::new(),
+        connecting: None,
+        disconnecting: HashMap::new(),
+        att_pdus: 0,
+    };
+    importer.trace.push(
+        origin,
+        Some(origin),
+        Record::Adapters {
+            result: Outcome::Ok(1),
+        },
+    );
+    for packet in packets {
+        match packet.kind {
+            PacketKind::Command => importer.command(packet),
+            PacketKind::Event => importer.event(packet),
+            PacketKind::Acl => importer.acl(packet),
+            _ => {}
+        }
+    }
+    importer.finish(packets.len())
+}
+
+/// Entries of the trace being built, unsorted until the end.
+struct Trace {
+    origin: DateTime<Utc>,
+    entries: Vec<Entry>,
+}
+
+impl Trace {
+    /// Adds `record`, which happened at `at` and was answered at `until`.
+    fn push(&mut self, at: DateTime<Utc>, until: Option<DateTime<Utc>>, record: Record) {
+        self.entries.push(Entry {
+            at_us: micros(at - self.origin),
+            took_us: until.map(|until| micros(until - at)),
+            record,
+        });
+    }
+
+    /// Appends `part` to the value of the read at `index`, answered at `until`.
+    fn extend_read(&mut self, index: usize, part: &[u8], until: DateTime<Utc>) {
+        let until = micros(until - self.origin);
+        let entry = &mut self.entries[index];
+        if let Record::Read {
+            result: Outcome::Ok(value),
+            ..
+        }
+        | Record::ReadDescriptor {
+            result: Outcome::Ok(value),
+            ..
+        } = &mut entry.record
+        {
+            value.0.extend_from_slice(part);
+            entry.took_us = Some(until.saturating_sub(entry.at_us));
+        }
+    }
+}
+
+struct Importer {
+    trace: Trace,
+    connections: Vec<Connection>,
+    /// Connection open on each HCI handle, as an index in `connections`.
+    open: HashMap<u16, usize>,
+    /// L2CAP frames being reassembled, by handle and direction.
+    fragments: HashMap<(u16, bool), Vec<u8>>,
+    /// What each device advertised so far.
+    adverts: HashMap<BDAddr, PropertiesRecord>,
+    /// When the phone last asked for a connection.
+    connecting: Option<DateTime<Utc>>,
+    /// When the phone asked to drop each connection.
+    disconnecting: HashMap<u16, DateTime<Utc>>,
+    att_pdus: usize,
+}
+
+impl Importer {
+    fn command(&mut self, packet: &Packet) {
+        let (Some(opcode), Some(params)) = (le_u16(&packet.data, 0), packet.data.get(3..)) else {
+            return;
+        };
+        match opcode {
+            LE_SET_SCAN_ENABLE | LE_SET_EXTENDED_SCAN_ENABLE => {
+                let result = Outcome::Ok(());
+                let record = match params.first() {
+                    Some(0) => Record::StopScan { adapter: 0, result },
+                    Some(1) => Record::StartScan { adapter: 0, result },
+                    _ => return,
+                };
+                self.trace.push(packet.at, Some(packet.at), record);
+            }
+            LE_CREATE_CONNECTION | LE_EXTENDED_CREATE_CONNECTION => {
+                self.connecting = Some(packet.at)
+            }
+            DISCONNECT => {
+                if let Some(handle) = le_u16(params, 0) {
+                    self.disconnecting.insert(handle & 0x0FFF, packet.at);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn event(&mut self, packet: &Packet) {
+        let (Some(&code), Some(params)) = (packet.data.first(), packet.data.get(2..)) else {
+            return;
+        };
+        match (code, params.split_first()) {
+            (DISCONNECTION_COMPLETE, Some((0, rest))) => {
+                if let Some(handle) = le_u16(rest, 0) {
+                    self.disconnected(packet.at, handle & 0x0FFF);
+                }
+            }
+            (LE_META, Some((&subevent, rest))) => match subevent {
+                LE_CONNECTION_COMPLETE
+                | LE_ENHANCED_CONNECTION_COMPLETE
+                | LE_ENHANCED_CONNECTION_COMPLETE_V2 => self.connected(packet.at, rest),
+                LE_ADVERTISING_REPORT => self.advertising_report(packet.at, rest),
+                LE_EXTENDED_ADVERTISING_REPORT => self.extended_advertising_report(packet.at, rest),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Opens a connection, records it as asked for by the tool.
+    fn connected(&mut self, at: DateTime<Utc>, params: &[u8]) {
+        let since = self.connecting.take().unwrap_or(at);
+        let (Some(0), Some(handle), Some(address)) =
+            (params.first(), le_u16(params, 1), address(params, 5))
+        else {
+            return;
+        };
+        let device = address.to_string();
+        let records = [
+            (
+                since,
+                since,
+                Record::Peripheral {
+                    adapter: 0,
+                    device: device.clone(),
+                    result: Outcome::Ok(Address(address)),
+                },
+            ),
+            (
+                since,
+                since,
+                Record::IsConnected {
+                    device: device.clone(),
+                    result: Outcome::Ok(false),
+                },
+            ),
+            (
+                since,
+                at,
+                Record::Connect {
+                    device: device.clone(),
+                    result: Outcome::Ok(()),
+                },
+            ),
+            (
+                at,
+                at,
+                Record::IsConnected {
+                    device: device.clone(),
+                    result: Outcome::Ok(true),
+                },
+            ),
+        ];
+        for (at, until, record) in records {
+            self.trace.push(at, Some(until), record);
+        }
+        self.open(handle & 0x0FFF, device, Some(at));
+    }
+
+    fn open(&mut self, handle: u16, device: String, connected_at: Option<DateTime<Utc>>) -> usize {
+        let index = self.connections.len();
+        self.connections.push(Connection {
+            device,
+            handle,
+            connected_at,
+            pdus: Vec::new(),
+        });
+        self.open.insert(handle, index);
+        index
+    }
+
+    fn disconnected(&mut self, at: DateTime<Utc>, handle: u16) {
+        self.fragments
+            .retain(|(fragmented, _), _| *fragmented != handle);
+        let Some(index) = self.open.remove(&handle) else {
+            return;
+        };
+        let device = self.connections[index].device.clone();
+        if let Some(since) = self.disconnecting.remove(&handle) {
+            let record = Record::Disconnect {
+                device: device.clone(),
+                result: Outcome::Ok(()),
+            };
+            self.trace.push(since, Some(at), record);
+        }
+        let event = EventRecord::DeviceDisconnected { device };
+        self.trace
+            .push(at, None, Record::Event { adapter: 0, event });
+    }
+
+    fn advertising_report(&mut self, at: DateTime<Utc>, params: &[u8]) {
+        let Some((&reports, mut rest)) = params.split_first() else {
+            return;
+        };
+        for _ in 0..reports {
+            let (Some(address), Some(&length)) = (address(rest, 2), rest.get(8)) else {
+                return;
+            };
+            let end = 9 + usize::from(length);
+            let (Some(data), Some(&rssi)) = (rest.get(9..end), rest.get(end)) else {
+                return;
+            };
+            self.advert(at, address, data, rssi as i8, NOT_AVAILABLE);
+            rest = &rest[end + 1..];
+        }
+    }
+
+    fn extended_advertising_report(&mut self, at: DateTime<Utc>, params: &[u8]) {
+        let Some((&reports, mut rest)) = params.split_first() else {
+            return;
+        };
+        for _ in 0..reports {
+            let (Some(address), Some(&length)) = (address(rest, 3), rest.get(23)) else {
+                return;
+            };
+            let end = 24 + usize::from(length);
+            let Some(data) = rest.get(24..end) else {
+                return;
+            };
+            self.advert(at, address, data, rest[13] as i8, rest[12] as i8);
+            rest = &rest[end..];
+        }
+    }
+
+    fn advert(&mut self, at: DateTime<Utc>, address: BDAddr, data: &[u8], rssi: i8, tx: i8) {
+        let discovered = !self.adverts.contains_key(&address);
+        let properties = self
+            .adverts
+            .entry(address)
+            .or_insert_with(|| PropertiesRecord {
+                address: Address(address),
+                local_name: None,
+                tx_power_level: None,
+                rssi: None,
+                manufacturer_data: Vec::new(),
+                service_data: Vec::new(),
+                services: Vec::new(),
+            });
+        properties.rssi = Some(rssi)
+            .filter(|rssi| *rssi != NOT_AVAILABLE)
+            .map(i16::from);
+        if tx != NOT_AVAILABLE {
+            properties.tx_power_level = Some(i16::from(tx));
+        }
+        apply_advert(properties, data);
+        let properties = properties.clone();
+
+        let device = address.to_string();
+        let event = if discovered {
+            let record = Record::Peripheral {
+                adapter: 0,
+                device: device.clone(),
+                result: Outcome::Ok(Address(address)),
+            };
+            self.trace.push(at, Some(at), record);
+            EventRecord::DeviceDiscovered {
+                device: device.clone(),
+            }
+        } else {
+            EventRecord::DeviceUpdated {
+                device: device.clone(),
+            }
+        };
+        self.trace
+            .push(at, None, Record::Event { adapter: 0, event });
+        let record = Record::Properties {
+            device,
+            result: Outcome::Ok(Some(properties)),
+        };
+        self.trace.push(at, Some(at), record);
+    }
+
+    fn acl(&mut self, packet: &Packet) {
+        let (Some(header), Some(length)) = (le_u16(&packet.data, 0), le_u16(&packet.data, 2))
+        else {
+            return;
+        };
+        let handle = header & 0x0FFF;
+        let continuation = (header >> 12) & 0x03 == 0x01;
+        let data = &packet.data[4..];
+        let data = &data[..data.len().min(usize::from(length))];
+        let key = (handle, packet.received);
+
+        let complete = {
+            let frame = if continuation {
+                match self.fragments.get_mut(&key) {
+                    Some(frame) => frame,
+                    None => return,
+                }
+            } else {
+                let frame = self.fragments.entry(key).or_default();
+                frame.clear();
+                frame
+            };
+            frame.extend_from_slice(data);
+            le_u16(frame, 0).is_some_and(|length| frame.len() >= 4 + usize::from(length))
+        };
+        if !complete {
+            return;
+        }
+        let frame = self
+            .fragments
+            .remove(&key)
+            .expect("frame being reassembled");
+        let end = 4 + usize::from(le_u16(&frame, 0).expect("complete frame"));
+        if le_u16(&frame, 2) == Some(ATT_CID) && end > 4 {
+            self.att(packet.at, handle, packet.received, frame[4..end].to_vec());
+        }
+    }
+
+    fn att(&mut self, at: DateTime<Utc>, handle: u16, from_peer: bool, data: Vec<u8>) {
+        self.att_pdus += 1;
+        let index = match self.open.get(&handle) {
+            Some(index) => *index,
+            None => self.open(handle, format!("handle 0x{:04X}", handle), None),
+        };
+        self.connections[index].pdus.push(AttPdu {
+            at,
+            from_peer,
+            data,
+        });
+    }
+
+    fn finish(self, packets: usize) -> Import {
+        let Importer {
+            mut trace,
+            connections,
+            adverts,
+            att_pdus,
+            ..
+        } = self;
+        let mut summary = Summary {
+            started_at: trace.origin,
+            packets,
+            att_pdus,
+            advertisers: adverts.len(),
+            connections: Vec::new(),
+            auth: Vec::new(),
+        };
+        for connection in &connections {
+            let (connection, auth) = connection.replay(&mut trace);
+            summary.connections.push(connection);
+            summary.auth.extend(auth);
+        }
+        trace.entries.sort_by_key(|entry| entry.at_us);
+        Import {
+            header: Header::new(trace.origin),
+            entries: trace.entries,
+            summary,
+        }
+    }
+}
+
+/// Applies the AD structures of an advert to what the device advertised so far.
+fn apply_advert(properties: &mut PropertiesRecord, mut data: &[u8]) {
+    while let Some((&length, rest)) = data.split_first() {
+        let length = usize::from(length);
+        if length == 0 || rest.len() < length {
+            break;
+        }
+        let (structure, next) = rest.split_at(length);
+        data = next;
+        let (kind, value) = (structure[0], &structure[1..]);
+        match kind {
+            0x02 | 0x03 | 0x06 | 0x07 => {
+                let size = if kind < 0x06 { 2 } else { 16 };
+                for uuid in value.chunks_exact(size).filter_map(uuid_from_le) {
+                    if !properties.services.contains(&uuid) {
+                        properties.services.push(uuid);
+                    }
+                }
+            }
+            0x08 if properties.local_name.is_some() => {}
+            0x08 | 0x09 => {
+                properties.local_name = Some(String::from_utf8_lossy(value).into_owned())
+            }
+            0x0A => {
+                if let Some(&power) = value.first() {
+                    properties.tx_power_level = Some(i16::from(power as i8));
+                }
+            }
+            0x16 if value.len() >= 2 => {
+                let uuid = uuid_from_u16(u16::from_le_bytes([value[0], value[1]]));
+                set(&mut properties.service_data, uuid, &value[2..]);
+            }
+            0xFF if value.len() >= 2 => {
+                let company = u16::from_le_bytes([value[0], value[1]]);
+                set(&mut properties.manufacturer_data, company, &value[2..]);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Sets the entry of `key`, keeping the entries sorted as the trace has them.
+fn set<K: Ord>(entries: &mut Vec<(K, Bytes)>, key: K, value: &[u8]) {
+    match entries.binary_search_by(|(existing, _)| existing.cmp(&key)) {
+        Ok(index) => entries[index].1 = Bytes(value.to_vec()),
+        Err(index) => entries.insert(index, (key, Bytes(value.to_vec()))),
+    }
+}
+
+struct Connection {
+    device: String,
+    handle: u16,
+    connected_at: Option<DateTime<Utc>>,
+    pdus: Vec<AttPdu>,
+}
+
+struct AttPdu {
+    at: DateTime<Utc>,
+    /// Whether the watch sent it.
+    from_peer: bool,
+    /// Opcode and parameters.
+    data: Vec<u8>,
+}
+
+impl AttPdu {
+    fn opcode(&self) -> u8 {
+        self.data[0]
+    }
+}
+
+/// ATT traffic of the phone as a client.
+enum Step<'a> {
+    /// A request with its response, if the log has it.
+    Request(&'a AttPdu, Option<&'a AttPdu>),
+    /// A write command, notification or indication.
+    Unsolicited(&'a AttPdu),
+}
+
+impl Connection {
+    /// Pairs the requests of the phone with the responses of the watch. The
+    /// phone's own GATT server is left out.
+    fn steps(&self) -> Vec<Step<'_>> {
+        let mut steps = Vec::new();
+        let mut pending = None;
+        for pdu in &self.pdus {
+            match (pdu.from_peer, pdu.opcode()) {
+                (false, opcode) if is_request(opcode) => {
+                    pending = Some(steps.len());
+                    steps.push(Step::Request(pdu, None));
+                }
+                (true, opcode) if is_response(opcode) => {
+                    if let Some(index) = pending.take() {
+                        if let Step::Request(_, response) = &mut steps[index] {
+                            *response = Some(pdu);
+                        }
+                    }
+                }
+                (false, WRITE_COMMAND) | (true, NOTIFICATION | INDICATION) => {
+                    steps.push(Step::Unsolicited(pdu))
+                }
+                _ => {}
+            }
+        }
+        steps
+    }
+
+    /// Adds the operations of the connection to `trace`.
+    fn replay(&self, trace: &mut Trace) -> (ConnectionSummary, Option<AuthExchange>) {
+        let steps = self.steps();
+        let mut database = Database::default();
+        for step in &steps {
+            if let Step::Request(request, Some(response)) = step {
+                database.learn(request, response);
+            }
+        }
+        let mut summary = ConnectionSummary {
+            device: self.device.clone(),
+            handle: self.handle,
+            connected_at: self.connected_at,
+            att_pdus: self.pdus.len(),
+            attributes: database.attributes(),
+            operations: 0,
+            skipped: 0,
+        };
+        if let Some((since, until)) = database.discovery {
+            let record = Record::DiscoverServices {
+                device: self.device.clone(),
+                result: Outcome::Ok(database.services()),
+            };
+            trace.push(since, Some(until), record);
+        }
+
+        let mut auth = AuthExchange {
+            device: self.device.clone(),
+            ..AuthExchange::default()
+        };
+        // Values longer than a PDU are read in parts, joined to the read they continue.
+        let mut reads = HashMap::new();
+        for step in &steps {
+            let (pdu, response) = match step {
+                Step::Request(request, response) => (*request, *response),
+                Step::Unsolicited(pdu) => (*pdu, None),
+            };
+            let opcode = pdu.opcode();
+            if !matches!(
+                opcode,
+                READ_REQUEST
+                    | READ_BLOB_REQUEST
+                    | WRITE_REQUEST
+                    | WRITE_COMMAND
+                    | NOTIFICATION
+                    | INDICATION
+            ) {
+                continue;
+            }
+            let Some(handle) = le_u16(&pdu.data, 1) else {
+                continue;
+            };
+            let Some(attribute) = database.attribute(handle) else {
+                summary.skipped += 1;
+                continue;
+            };
+            let device = self.device.clone();
+            let value = pdu.data.get(3..).unwrap_or_default().to_vec();
+            let until = response.map_or(pdu.at, |response| response.at);
+            let result = match response {
+                Some(response) if response.opcode() == ERROR_RESPONSE => {
+                    Outcome::Err(att_error(&response.data))
+                }
+                _ => Outcome::Ok(()),
+            };
+
+            let (record, until) = match (opcode, attribute) {
+                (READ_BLOB_REQUEST, _) => {
+                    if let (Some(response), Some(&index)) = (response, reads.get(&handle)) {
+                        if response.opcode() == READ_BLOB_RESPONSE {
+                            trace.extend_read(index, &response.data[1..], response.at);
+                        }
+                    }
+                    continue;
+                }
+                (READ_REQUEST, attribute) => {
+                    let answered = |response: &&AttPdu| {
+                        matches!(response.opcode(), READ_RESPONSE | ERROR_RESPONSE)
+                    };
+                    let Some(response) = response.filter(answered) else {
+                        summary.skipped += 1;
+                        continue;
+                    };
+                    let result = match result {
+                        Outcome::Ok(()) => Outcome::Ok(Bytes(response.data[1..].to_vec())),
+                        Outcome::Err(err) => Outcome::Err(err),
+                    };
+                    reads.insert(handle, trace.entries.len());
+                    let record = match attribute {
+                        Attribute::Characteristic(characteristic) => Record::Read {
+                            device,
+                            characteristic,
+                            result,
+                        },
+                        Attribute::Descriptor {
+                            characteristic,
+                            descriptor,
+                        } => Record::ReadDescriptor {
+                            device,
+                            characteristic,
+                            descriptor,
+                            result,
+                        },
+                    };
+                    (record, Some(until))
+                }
+                (NOTIFICATION | INDICATION, Attribute::Characteristic(characteristic)) => {
+                    auth.notified(characteristic, &value);
+                    let record = Record::Notification {
+                        device,
+                        characteristic,
+                        value: Bytes(value),
+                    };
+                    (record, None)
+                }
+                (WRITE_REQUEST | WRITE_COMMAND, Attribute::Characteristic(characteristic)) => {
+                    auth.written(characteristic, &value);
+                    let record = Record::Write {
+                        device,
+                        characteristic,
+                        data: Bytes(value),
+                        with_response: opcode == WRITE_REQUEST,
+                        result,
+                    };
+                    (record, Some(until))
+                }
+                (
+                    WRITE_REQUEST | WRITE_COMMAND,
+                    Attribute::Descriptor {
+                        characteristic,
+                        descriptor: CCCD_UUID,
+                    },
+                ) => {
+                    let record = if value.first().is_some_and(|flags| flags & 0x03 != 0) {
+                        Record::Subscribe {
+                            device,
+                            characteristic,
+                            result,
+                        }
+                    } else {
+                        Record::Unsubscribe {
+                            device,
+                            characteristic,
+                            result,
+                        }
+                    };
+                    (record, Some(until))
+                }
+                _ => {
+                    summary.skipped += 1;
+                    continue;
+                }
+            };
+            trace.push(pdu.at, until, record);
+            summary.operations += 1;
+        }
+        (summary, Some(auth).filter(|auth| !auth.is_empty()))
+    }
+}
+
+fn is_request(opcode: u8) -> bool {
+    matches!(
+        opcode,
+        0x02 | 0x04 | 0x06 | 0x08 | 0x0A | 0x0C | 0x0E | 0x10 | 0x12 | 0x16 | 0x18 | 0x20
+    )
+}
+
+fn is_response(opcode: u8) -> bool {
+    opcode == ERROR_RESPONSE || (opcode & 0x01 == 0x01 && is_request(opcode - 1))
+}
+
+/// What a handle of the watch holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Attribute {
+    Characteristic(Uuid),
+    Descriptor {
+        characteristic: Uuid,
+        descriptor: Uuid,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ServiceRange {
+    start: u16,
+    end: u16,
+    uuid: Uuid,
+    primary: bool,
+}
+
+/// The attributes of the watch, as far as the discovery in the log went.
+#[derive(Default)]
+struct Database {
+    services: Vec<ServiceRange>,
+    /// UUID and properties of the characteristics, by value handle.
+    characteristics: BTreeMap<u16, (Uuid, CharPropFlags)>,
+    /// Types of the attributes found by Find Information, by handle.
+    found: BTreeMap<u16, Uuid>,
+    /// First discovery request and last discovery response.
+    discovery: Option<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl Database {
+    /// Learns from a discovery request and its response.
+    fn learn(&mut self, request: &AttPdu, response: &AttPdu) {
+        let (req, res) = (&request.data, &response.data);
+        let learned = match (request.opcode(), response.opcode()) {
+            (READ_BY_GROUP_TYPE_REQUEST, READ_BY_GROUP_TYPE_RESPONSE) => {
+                let primary = match req.get(5..).and_then(uuid_from_le) {
+                    Some(PRIMARY_SERVICE_UUID) => true,
+                    Some(SECONDARY_SERVICE_UUID) => false,
+                    _ => return,
+                };
+                for group in records(res) {
+                    if let (Some(start), Some(end), Some(uuid)) = (
+                        le_u16(group, 0),
+                        le_u16(group, 2),
+                        group.get(4..).and_then(uuid_from_le),
+                    ) {
+                        self.services.push(ServiceRange {
+                            start,
+                            end,
+                            uuid,
+                            primary,
+                        });
+                    }
+                }
+                true
+            }
+            (FIND_BY_TYPE_VALUE_REQUEST, FIND_BY_TYPE_VALUE_RESPONSE) => {
+                let (Some(0x2800), Some(uuid)) =
+                    (le_u16(req, 5), req.get(7..).and_then(uuid_from_le))
+                else {
+                    return;
+                };
+                for found in res[1..].chunks_exact(4) {
+                    self.services.push(ServiceRange {
+                        start: u16::from_le_bytes([found[0], found[1]]),
+                        end: u16::from_le_bytes([found[2], found[3]]),
+                        uuid,
+                        primary: true,
+                    });
+                }
+                true
+            }
+            (READ_BY_TYPE_REQUEST, READ_BY_TYPE_RESPONSE) => {
+                if req.get(5..).and_then(uuid_from_le) != Some(CHARACTERISTIC_UUID) {
+                    return;
+                }
+                for declaration in records(res) {
+                    if let (Some(&properties), Some(handle), Some(uuid)) = (
+                        declaration.get(2),
+                        le_u16(declaration, 3),
+                        declaration.get(5..).and_then(uuid_from_le),
+                    ) {
+                        let properties = CharPropFlags::from_bits_truncate(properties);
+                        self.characteristics.insert(handle, (uuid, properties));
+                    }
+                }
+                true
+            }
+            (FIND_INFORMATION_REQUEST, FIND_INFORMATION_RESPONSE) => {
+                let size = if res.get(1) == Some(&0x01) { 4 } else { 18 };
+                for information in res.get(2..).unwrap_or_default().chunks_exact(size) {
+                    let handle = u16::from_le_bytes([information[0], information[1]]);
+                    if let Some(uuid) = uuid_from_le(&information[2..]) {
+                        self.found.insert(handle, uuid);
+                    }
+                }
+                true
+            }
+            // The watch ends each discovery with Attribute Not Found.
+            (
+                READ_BY_GROUP_TYPE_REQUEST
+                | FIND_BY_TYPE_VALUE_REQUEST
+                | READ_BY_TYPE_REQUEST
+                | FIND_INFORMATION_REQUEST,
+                ERROR_RESPONSE,
+            ) => true,
+            _ => false,
+        };
+        if learned {
+            let (since, until) = self.discovery.unwrap_or((request.at, response.at));
+            self.discovery = Some((since.min(request.at), until.max(response.at)));
+        }
+    }
+
+    fn service_of(&self, handle: u16) -> Option<&ServiceRange> {
+        self.services
+            .iter()
+            .find(|service| (service.start..=service.end).contains(&handle))
+    }
+
+    /// Value handle of the characteristic a descriptor handle belongs to.
+    fn owner(&self, handle: u16) -> Option<u16> {
+        let uuid = self.found.get(&handle)?;
+        if self.characteristics.contains_key(&handle)
+            || [
+                PRIMARY_SERVICE_UUID,
+                SECONDARY_SERVICE_UUID,
+                INCLUDE_UUID,
+                CHARACTERISTIC_UUID,
+            ]
+            .contains(uuid)
+        {
+            return None;
+        }
+        let (&owner, _) = self.characteristics.range(..handle).next_back()?;
+        match self.service_of(handle) {
+            Some(service) if owner < service.start => None,
+            _ => Some(owner),
+        }
+    }
+
+    fn attribute(&self, handle: u16) -> Option<Attribute> {
+        if let Some((uuid, _)) = self.characteristics.get(&handle) {
+            return Some(Attribute::Characteristic(*uuid));
+        }
+        let owner = self.owner(handle)?;
+        Some(Attribute::Descriptor {
+            characteristic: self.characteristics[&owner].0,
+            descriptor: self.found[&handle],
+        })
+    }
+
+    fn attributes(&self) -> usize {
+        let descriptors = self
+            .found
+            .keys()
+            .filter(|handle| self.owner(**handle).is_some())
+            .count();
+        self.characteristics.len() + descriptors
+    }
+
+    fn services(&self) -> Vec<ServiceRecord> {
+        let mut services = self.services.clone();
+        services.sort_by_key(|service| service.start);
+        services.dedup_by_key(|service| service.start);
+        services
+            .iter()
+            .map(|service| ServiceRecord {
+                uuid: service.uuid,
+                primary: service.primary,
+                characteristics: self
+                    .characteristics
+                    .range(service.start..=service.end)
+                    .map(|(&handle, &(uuid, properties))| CharacteristicRecord {
+                        uuid,
+                        properties: dump::property_names(properties),
+                        descriptors: self
+                            .found
+                            .iter()
+                            .filter(|(found, _)| self.owner(**found) == Some(handle))
+                            .map(|(_, descriptor)| *descriptor)
+                            .collect(),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// The records of a Read By Type or Read By Group Type response, whose second
+/// byte is their length.
+fn records(response: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let length = response.get(1).map_or(0, |length| usize::from(*length));
+    response
+        .get(2..)
+        .unwrap_or_default()
+        .chunks_exact(length.max(1))
+        .filter(move |_| length > 0)
+}
+
+fn att_error(response: &[u8]) -> ErrorRecord {
+    let code = response.get(4).copied().unwrap_or_default();
+    let name = match code {
+        0x01 => "invalid handle",
+        0x02 => "read not permitted",
+        0x03 => "write not permitted",
+        0x04 => "invalid PDU",
+        0x05 => "insufficient authentication",
+        0x06 => "request not supported",
+        0x07 => "invalid offset",
+        0x08 => "insufficient authorization",
+        0x09 => "prepare queue full",
+        0x0A => "attribute not found",
+        0x0B => "attribute not long",
+        0x0C => "insufficient encryption key size",
+        0x0D => "invalid attribute value length",
+        0x0E => "unlikely error",
+        0x0F => "insufficient encryption",
+        0x10 => "unsupported group type",
+        0x11 => "insufficient resources",
+        _ => "application error",
+    };
+    ErrorRecord {
+        kind: ErrorKind::Other,
+        message: format!("ATT error 0x{:02X}: {}", code, name),
+    }
+}
+
+/// A UUID as ATT sends it, little-endian in 2, 4 or 16 bytes.
+fn uuid_from_le(bytes: &[u8]) -> Option<Uuid> {
+    match bytes.len() {
+        2 => Some(uuid_from_u16(u16::from_le_bytes([bytes[0], bytes[1]]))),
+        4 => Some(uuid_from_u32(u32::from_le_bytes(
+            bytes.try_into().expect("4 bytes"),
+        ))),
+        16 => {
+            let mut big_endian: [u8; 16] = bytes.try_into().expect("16 bytes");
+            big_endian.reverse();
+            Some(Uuid::from_bytes(big_endian))
+        }
+        _ => None,
+    }
+}
+
+/// The device address at `offset`, sent least significant byte first.
+fn address(bytes: &[u8], offset: usize) -> Option<BDAddr> {
+    let mut address: [u8; 6] = bytes.get(offset..offset + 6)?.try_into().ok()?;
+    address.reverse();
+    Some(BDAddr::from(address))
+}
+
+fn le_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    let bytes = bytes.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes(bytes[..4].try_into().expect("4 bytes"))
+}
+
+fn micros(delta: TimeDelta) -> u64 {
+    delta
+        .num_microseconds()
+        .and_then(|micros| u64::try_from(micros).ok())
+        .unwrap_or_default()
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
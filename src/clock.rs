@@ -0,0 +1,175 @@
+//! Sources of the current time.
+//!
+//! Everything that needs "now" asks a [`Clock`] instead of calling
+//! [`Utc::now`], so that time handling can be pinned to exact instants.
+
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use serde::de::{self, Deserialize, Deserializer};
+use std::sync::Mutex;
+
+/// A source of the current time.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The host clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock frozen at one instant.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+/// A clock running a constant distance ahead of (or behind) another clock.
+#[derive(Debug, Clone, Copy)]
+pub struct OffsetClock<C> {
+    inner: C,
+    offset: Duration,
+}
+
+impl<C: Clock> OffsetClock<C> {
+    pub fn new(inner: C, offset: Duration) -> OffsetClock<C> {
+        OffsetClock { inner, offset }
+    }
+
+    /// A clock that shows `at` right now and then ticks along with `inner`.
+    pub fn starting_at(inner: C, at: DateTime<Utc>) -> OffsetClock<C> {
+        let offset = at - inner.now();
+        OffsetClock { inner, offset }
+    }
+
+    pub fn offset(&self) -> Duration {
+        self.offset
+    }
+}
+
+impl<C: Clock> Clock for OffsetClock<C> {
+    fn now(&self) -> DateTime<Utc> {
+        self.inner.now() + self.offset
+    }
+}
+
+/// A clock that only moves when told to.
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl MockClock {
+    pub fn new(now: DateTime<Utc>) -> MockClock {
+        MockClock {
+            now: Mutex::new(now),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, step: Duration) {
+        *self.now.lock().unwrap() += step;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> DateTime<Utc> {
+        (**self).now()
+    }
+}
+
+/// Parses a signed duration such as `5m`, `-90s`, `+1h30m` or `250ms`.
+///
+/// Accepted units are `ms`, `s`, `m`/`min`, `h` and `d`.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let (negative, mut rest) = match text.as_bytes().first() {
+        Some(b'-') => (true, &text[1..]),
+        Some(b'+') => (false, &text[1..]),
+        _ => (false, text),
+    };
+    if rest.is_empty() {
+        return Err(format!("invalid duration {:?}", text));
+    }
+
+    let mut total = Duration::zero();
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let amount: i64 = rest[..digits]
+            .parse()
+            .map_err(|_| format!("invalid duration {:?}: expected a number", text))?;
+        rest = &rest[digits..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let part = match &rest[..unit_len] {
+            "ms" => Duration::try_milliseconds(amount),
+            "s" => Duration::try_seconds(amount),
+            "m" | "min" => Duration::try_minutes(amount),
+            "h" => Duration::try_hours(amount),
+            "d" => Duration::try_days(amount),
+            unit => {
+                return Err(format!(
+                    "invalid duration {:?}: unknown unit {:?}",
+                    text, unit
+                ))
+            }
+        };
+        total = part
+            .and_then(|part| total.checked_add(&part))
+            .ok_or_else(|| format!("duration {:?} is too large", text))?;
+        rest = &rest[unit_len..];
+    }
+    Ok(if negative { -total } else { total })
+}
+
+/// Parses an RFC 3339 timestamp such as `2026-10-25T02:59:50+02:00`.
+pub fn parse_rfc3339(text: &str) -> Result<DateTime<FixedOffset>, String> {
+    DateTime::parse_from_rfc3339(text).map_err(|err| format!("invalid time {:?}: {}", text, err))
+}
+
+/// Deserializes a signed duration written as for [`parse_duration`].
+pub(crate) fn deserialize_offset<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    parse_duration(&String::deserialize(deserializer)?).map_err(de::Error::custom)
+}
+
+/// Deserializes a duration written as for [`parse_duration`] that must not be negative.
+pub(crate) fn deserialize_interval<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<std::time::Duration, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    parse_duration(&text)
+        .and_then(|duration| {
+            duration
+                .to_std()
+                .map_err(|_| format!("duration {:?} is negative", text))
+        })
+        .map_err(de::Error::custom)
+}
+
+pub(crate) fn deserialize_optional_interval<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<std::time::Duration>, D::Error> {
+    deserialize_interval(deserializer).map(Some)
+}
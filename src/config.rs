@@ -0,0 +1,308 @@
+//! Settings file: the known watches and the defaults of the command line.
+//!
+//! ```toml
+//! [defaults]
+//! adapter = 0
+//! scan_timeout = "15s"
+//! output = "json"
+//! time_zone = "Europe/Rome"
+//!
+//! [defaults.retry]
+//! attempts = 3
+//! delay = "2s"
+//!
+//! [[devices]]
+//! alias = "kitchen-watch"
+//! address = "C0:FF:EE:00:00:01"
+//! name = "^Amazfit"
+//! profile = "Amazfit GTS 4 Mini"
+//! auth_key = "0x30313233343536373839616263646566"
+//!
+//! [devices.sync]
+//! error_bound = "500ms"
+//! min_interval = "1h"
+//! max_interval = "7d"
+//! ```
+//!
+//! The file is `--config` if given, else `smartwatch/config.toml` in the XDG
+//! config directory when it exists. Durations are written as for `--offset`.
+//! Options on the command line win over a device entry, which wins over the
+//! defaults.
+
+use crate::clock;
+use crate::error::{Result, SmartwatchError};
+use crate::gatt;
+use crate::profile::{DeviceProfile, KNOWN_PROFILES};
+use crate::schedule::SchedulePolicy;
+use crate::zone;
+use regex::Regex;
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Name of the settings file in the config directory.
+const CONFIG_FILE: &str = "smartwatch/config.toml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub defaults: Defaults,
+    #[serde(default)]
+    pub devices: Vec<KnownDevice>,
+}
+
+/// Settings of every command and device.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Defaults {
+    /// Position of the adapter to use among those of the host; all of them if omitted.
+    pub adapter: Option<usize>,
+    /// Give up scanning for devices after this long.
+    #[serde(default, deserialize_with = "clock::deserialize_optional_interval")]
+    pub scan_timeout: Option<Duration>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    #[serde(default)]
+    pub output: OutputFormat,
+    /// IANA zone watches are set to unless their entry names one; the host zone if omitted.
+    pub time_zone: Option<String>,
+}
+
+/// How often connecting to a device is tried.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Connection attempts per device, the first one included.
+    #[serde(default = "one_attempt")]
+    pub attempts: u32,
+    /// Wait between two attempts.
+    #[serde(
+        default = "default_retry_delay",
+        deserialize_with = "clock::deserialize_interval"
+    )]
+    pub delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            attempts: one_attempt(),
+            delay: default_retry_delay(),
+        }
+    }
+}
+
+/// How commands print their results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Tables and trees for people.
+    #[default]
+    Text,
+    /// JSON, as with `--json`.
+    Json,
+}
+
+/// A watch known by an alias, see `--device`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KnownDevice {
+    pub alias: String,
+    /// MAC address or platform identifier.
+    pub address: Option<String>,
+    /// Regular expression the advertised name matches.
+    #[serde(default, deserialize_with = "pattern")]
+    pub name: Option<Regex>,
+    /// Model of the watch, by the name of one of [`KNOWN_PROFILES`].
+    #[serde(default, deserialize_with = "model")]
+    pub profile: Option<DeviceProfile>,
+    /// Huami authentication key, as `0x` and 32 hex digits. Checked and kept
+    /// for when commands authenticate; selecting the device warns until then.
+    #[serde(default, deserialize_with = "auth_key")]
+    pub auth_key: Option<[u8; 16]>,
+    /// IANA zone the watch is set to, overriding the default one.
+    pub time_zone: Option<String>,
+    /// Bounds of the scheduled syncs, overriding the `schedule` defaults.
+    #[serde(default)]
+    pub sync: SyncPolicy,
+}
+
+/// Parts of a [`SchedulePolicy`]; the
+/// `schedule` options win over them and the built-in bounds fill those omitted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyncPolicy {
+    #[serde(default, deserialize_with = "optional_offset")]
+    pub error_bound: Option<chrono::Duration>,
+    #[serde(default, deserialize_with = "optional_offset")]
+    pub min_interval: Option<chrono::Duration>,
+    #[serde(default, deserialize_with = "optional_offset")]
+    pub max_interval: Option<chrono::Duration>,
+}
+
+impl Config {
+    /// Loads `path`, or the file in the config directory if there is one.
+    pub fn locate(path: Option<&Path>) -> Result<Config> {
+        match path {
+            Some(path) => Config::load(path),
+            None => match default_path() {
+                Some(path) if path.exists() => Config::load(&path),
+                _ => Ok(Config::default()),
+            },
+        }
+    }
+
+    /// Loads and checks a settings file.
+    pub fn load(path: &Path) -> Result<Config> {
+        let text = fs::read_to_string(path).map_err(SmartwatchError::io(path))?;
+        let invalid = |message: String| SmartwatchError::Config {
+            path: path.to_path_buf(),
+            message,
+        };
+        let config: Config = toml::from_str(&text).map_err(|err| invalid(err.to_string()))?;
+        config.check().map_err(invalid)?;
+        Ok(config)
+    }
+
+    /// The device called `alias`.
+    pub fn device(&self, alias: &str) -> Result<&KnownDevice> {
+        self.devices
+            .iter()
+            .find(|device| device.alias == alias)
+            .ok_or_else(|| SmartwatchError::UnknownDevice {
+                alias: alias.to_string(),
+                known: self.devices.iter().map(|d| d.alias.clone()).collect(),
+            })
+    }
+
+    fn check(&self) -> std::result::Result<(), String> {
+        let defaults = &self.defaults;
+        if defaults
+            .scan_timeout
+            .is_some_and(|timeout| timeout.is_zero())
+        {
+            return Err(String::from("scan timeout must not be zero"));
+        }
+        if defaults.retry.attempts == 0 {
+            return Err(String::from("retry attempts must be at least 1"));
+        }
+        if let Some(name) = &defaults.time_zone {
+            zone::resolve(Some(name)).map_err(|err| format!("defaults: {}", err))?;
+        }
+        let mut aliases = HashSet::new();
+        for device in &self.devices {
+            if device.alias.trim().is_empty() {
+                return Err(String::from("device with an empty alias"));
+            }
+            if !aliases.insert(device.alias.as_str()) {
+                return Err(format!("device {:?} is listed twice", device.alias));
+            }
+            device
+                .check()
+                .map_err(|message| format!("device {:?}: {}", device.alias, message))?;
+        }
+        Ok(())
+    }
+}
+
+impl KnownDevice {
+    fn check(&self) -> std::result::Result<(), String> {
+        if self
+            .address
+            .as_deref()
+            .is_some_and(|address| address.trim().is_empty())
+        {
+            return Err(String::from("empty address"));
+        }
+        if self.address.is_none() && self.name.is_none() && self.profile.is_none() {
+            return Err(String::from(
+                "no address, name or profile to find the device by",
+            ));
+        }
+        if let Some(name) = &self.time_zone {
+            zone::resolve(Some(name)).map_err(|err| err.to_string())?;
+        }
+        self.sync.check()
+    }
+}
+
+impl SyncPolicy {
+    /// The policy these settings give, with the built-in bounds for those omitted.
+    pub fn policy(&self) -> SchedulePolicy {
+        let defaults = SchedulePolicy::default();
+        SchedulePolicy {
+            error_bound: self.error_bound.unwrap_or(defaults.error_bound),
+            min_interval: self.min_interval.unwrap_or(defaults.min_interval),
+            max_interval: self.max_interval.unwrap_or(defaults.max_interval),
+        }
+    }
+
+    /// Checks the policy as completed by the built-in bounds, so that a lone
+    /// minimum interval cannot exceed the default maximum.
+    fn check(&self) -> std::result::Result<(), String> {
+        self.policy()
+            .check()
+            .map_err(|message| format!("sync {}", message))
+    }
+}
+
+/// `smartwatch/config.toml` in `$XDG_CONFIG_HOME`, or in `~/.config` when unset.
+pub fn default_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join(CONFIG_FILE))
+}
+
+fn one_attempt() -> u32 {
+    1
+}
+
+fn default_retry_delay() -> Duration {
+    Duration::from_secs(2)
+}
+
+fn pattern<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Regex>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    Regex::new(&text).map(Some).map_err(de::Error::custom)
+}
+
+fn model<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<DeviceProfile>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    match KNOWN_PROFILES.iter().find(|profile| profile.model == text) {
+        Some(profile) => Ok(Some(*profile)),
+        None => {
+            let known: Vec<&str> = KNOWN_PROFILES.iter().map(|p| p.model).collect();
+            Err(de::Error::custom(format!(
+                "unknown profile {:?}, expected one of {:?}",
+                text, known
+            )))
+        }
+    }
+}
+
+fn auth_key<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<[u8; 16]>, D::Error> {
+    gatt::parse_hex(&String::deserialize(deserializer)?)
+        .ok()
+        .and_then(|key| <[u8; 16]>::try_from(key).ok())
+        .map(Some)
+        .ok_or_else(|| de::Error::custom("auth key must be 16 bytes written as 32 hex digits"))
+}
+
+fn optional_offset<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<chrono::Duration>, D::Error> {
+    clock::deserialize_offset(deserializer).map(Some)
+}
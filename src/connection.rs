@@ -0,0 +1,238 @@
+//! Connecting to a watch, locating its characteristics and talking to them.
+//!
+//! The wrappers here put a time limit on every GATT operation and tag any
+//! failure with the device and characteristic involved.
+
+use crate::backend::Peripheral;
+use crate::error::{Result, SmartwatchError};
+use crate::gatt::descriptor::{PresentationFormat, PRESENTATION_FORMAT_UUID};
+use btleplug::api::{CharPropFlags, Characteristic, Descriptor, ValueNotification, WriteType};
+use futures::stream::{Stream, StreamExt};
+use std::future::Future;
+use std::time::Duration;
+use tokio::time;
+use uuid::Uuid;
+
+/// Longest wait for a connection to be established.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Longest wait for the service discovery to complete.
+pub const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(30);
+/// Longest wait for a single read or write.
+pub const GATT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Name of the device used in error messages and history files.
+pub fn device_label(peripheral: &impl Peripheral) -> String {
+    peripheral.address().to_string()
+}
+
+/// Connects to `peripheral` unless already connected and discovers its services.
+///
+/// Returns whether the peripheral ended up connected.
+pub async fn connect(peripheral: &impl Peripheral) -> Result<bool> {
+    let device = device_label(peripheral);
+    let connect_error = |source| SmartwatchError::Connect {
+        device: device.clone(),
+        source,
+    };
+    if !peripheral.is_connected().await.map_err(connect_error)? {
+        limit(&device, "connect", CONNECT_TIMEOUT, peripheral.connect())
+            .await?
+            .map_err(connect_error)?;
+    }
+    let is_connected = peripheral.is_connected().await.map_err(connect_error)?;
+    if is_connected {
+        limit(
+            &device,
+            "service discovery",
+            DISCOVERY_TIMEOUT,
+            peripheral.discover_services(),
+        )
+        .await?
+        .map_err(|source| SmartwatchError::Discovery {
+            device: device.clone(),
+            source,
+        })?;
+    }
+    Ok(is_connected)
+}
+
+pub async fn disconnect(peripheral: &impl Peripheral) -> Result<()> {
+    peripheral
+        .disconnect()
+        .await
+        .map_err(|source| SmartwatchError::Connect {
+            device: device_label(peripheral),
+            source,
+        })
+}
+
+/// Reads `characteristic` within [`GATT_TIMEOUT`].
+pub async fn read(
+    peripheral: &impl Peripheral,
+    characteristic: &Characteristic,
+) -> Result<Vec<u8>> {
+    let device = device_label(peripheral);
+    limit(
+        &device,
+        "read",
+        GATT_TIMEOUT,
+        peripheral.read(characteristic),
+    )
+    .await?
+    .map_err(|source| SmartwatchError::Read {
+        device,
+        characteristic: characteristic.uuid,
+        source,
+    })
+}
+
+/// Reads `descriptor` within [`GATT_TIMEOUT`].
+pub async fn read_descriptor(
+    peripheral: &impl Peripheral,
+    descriptor: &Descriptor,
+) -> Result<Vec<u8>> {
+    let device = device_label(peripheral);
+    limit(
+        &device,
+        "descriptor read",
+        GATT_TIMEOUT,
+        peripheral.read_descriptor(descriptor),
+    )
+    .await?
+    .map_err(|source| SmartwatchError::Read {
+        device,
+        characteristic: descriptor.uuid,
+        source,
+    })
+}
+
+/// Writes `characteristic` within [`GATT_TIMEOUT`].
+pub async fn write(
+    peripheral: &impl Peripheral,
+    characteristic: &Characteristic,
+    data: &[u8],
+    write_type: WriteType,
+) -> Result<()> {
+    let device = device_label(peripheral);
+    limit(
+        &device,
+        "write",
+        GATT_TIMEOUT,
+        peripheral.write(characteristic, data, write_type),
+    )
+    .await?
+    .map_err(|source| SmartwatchError::Write {
+        device,
+        characteristic: characteristic.uuid,
+        source,
+    })
+}
+
+/// Subscribes to `characteristic` and returns the stream of its notifications.
+///
+/// Notifications of other characteristics the peripheral is subscribed to are
+/// filtered out.
+pub async fn subscribe(
+    peripheral: &impl Peripheral,
+    characteristic: &Characteristic,
+) -> Result<impl Stream<Item = ValueNotification> + Send> {
+    let device = device_label(peripheral);
+    let subscribe_error = |source| SmartwatchError::Subscribe {
+        device: device.clone(),
+        characteristic: characteristic.uuid,
+        source,
+    };
+    let notifications = peripheral.notifications().await.map_err(subscribe_error)?;
+    limit(
+        &device,
+        "subscribe",
+        GATT_TIMEOUT,
+        peripheral.subscribe(characteristic),
+    )
+    .await?
+    .map_err(subscribe_error)?;
+    let uuid = characteristic.uuid;
+    Ok(notifications.filter(move |n| futures::future::ready(n.uuid == uuid)))
+}
+
+pub async fn unsubscribe(
+    peripheral: &impl Peripheral,
+    characteristic: &Characteristic,
+) -> Result<()> {
+    let device = device_label(peripheral);
+    limit(
+        &device,
+        "unsubscribe",
+        GATT_TIMEOUT,
+        peripheral.unsubscribe(characteristic),
+    )
+    .await?
+    .map_err(|source| SmartwatchError::Subscribe {
+        device,
+        characteristic: characteristic.uuid,
+        source,
+    })
+}
+
+/// Looks up a discovered characteristic by UUID.
+pub fn find_characteristic(peripheral: &impl Peripheral, uuid: Uuid) -> Option<Characteristic> {
+    peripheral
+        .characteristics()
+        .into_iter()
+        .find(|c| c.uuid == uuid)
+}
+
+/// Looks up a characteristic the caller cannot do without.
+pub fn require_characteristic(peripheral: &impl Peripheral, uuid: Uuid) -> Result<Characteristic> {
+    find_characteristic(peripheral, uuid).ok_or_else(|| SmartwatchError::MissingCharacteristic {
+        device: device_label(peripheral),
+        characteristic: uuid,
+    })
+}
+
+/// Reads an optional characteristic, `None` if the watch does not expose it as readable.
+pub async fn read_if_supported(
+    peripheral: &impl Peripheral,
+    uuid: Uuid,
+) -> Result<Option<Vec<u8>>> {
+    match find_characteristic(peripheral, uuid) {
+        Some(characteristic) if characteristic.properties.contains(CharPropFlags::READ) => {
+            Ok(Some(read(peripheral, &characteristic).await?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Reads the Presentation Format descriptor of `characteristic`, `None` if it has none.
+pub async fn read_presentation_format(
+    peripheral: &impl Peripheral,
+    characteristic: &Characteristic,
+) -> Result<Option<PresentationFormat>> {
+    let Some(descriptor) = characteristic
+        .descriptors
+        .iter()
+        .find(|d| d.uuid == PRESENTATION_FORMAT_UUID)
+    else {
+        return Ok(None);
+    };
+    let value = read_descriptor(peripheral, descriptor).await?;
+    PresentationFormat::decode(&value)
+        .map(Some)
+        .map_err(|err| SmartwatchError::codec(&device_label(peripheral), descriptor.uuid, err))
+}
+
+/// Runs `operation`, giving up with a timeout error after `after`.
+async fn limit<T>(
+    device: &str,
+    operation: &'static str,
+    after: Duration,
+    future: impl Future<Output = T>,
+) -> Result<T> {
+    time::timeout(after, future)
+        .await
+        .map_err(|_| SmartwatchError::Timeout {
+            device: device.to_string(),
+            operation,
+            after,
+        })
+}
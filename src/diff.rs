@@ -0,0 +1,316 @@
+//! Comparison of two GATT dumps, e.g. before and after a firmware update.
+//!
+//! Attributes are matched by UUID within their parent; an attribute whose UUID
+//! occurs several times is matched by order of occurrence. Descriptor values are
+//! compared too, except the Client Characteristic Configuration, which only
+//! reflects what the client subscribed to.
+
+use crate::assigned::NamedUuid;
+use crate::dump::{CharacteristicDump, DescriptorDump, GattDump, Reading, ServiceDump};
+use crate::gatt::descriptor::CLIENT_CONFIGURATION_UUID;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use uuid::Uuid;
+
+/// One difference between two GATT layouts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    ServiceAdded {
+        service: Uuid,
+    },
+    ServiceRemoved {
+        service: Uuid,
+    },
+    CharacteristicAdded {
+        service: Uuid,
+        characteristic: Uuid,
+    },
+    CharacteristicRemoved {
+        service: Uuid,
+        characteristic: Uuid,
+    },
+    PropertiesChanged {
+        service: Uuid,
+        characteristic: Uuid,
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    DescriptorAdded {
+        service: Uuid,
+        characteristic: Uuid,
+        descriptor: Uuid,
+    },
+    DescriptorRemoved {
+        service: Uuid,
+        characteristic: Uuid,
+        descriptor: Uuid,
+    },
+    DescriptorChanged {
+        service: Uuid,
+        characteristic: Uuid,
+        descriptor: Uuid,
+        old: Reading,
+        new: Reading,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |uuid: &Uuid| NamedUuid::new(*uuid);
+        match self {
+            Change::ServiceAdded { service } => write!(f, "+ service {}", name(service)),
+            Change::ServiceRemoved { service } => write!(f, "- service {}", name(service)),
+            Change::CharacteristicAdded {
+                service,
+                characteristic,
+            } => write!(
+                f,
+                "+ characteristic {} in {}",
+                name(characteristic),
+                name(service)
+            ),
+            Change::CharacteristicRemoved {
+                service,
+                characteristic,
+            } => write!(
+                f,
+                "- characteristic {} in {}",
+                name(characteristic),
+                name(service)
+            ),
+            Change::PropertiesChanged {
+                characteristic,
+                added,
+                removed,
+                ..
+            } => {
+                write!(f, "~ properties of {}:", name(characteristic))?;
+                for property in added {
+                    write!(f, " +{}", property)?;
+                }
+                for property in removed {
+                    write!(f, " -{}", property)?;
+                }
+                Ok(())
+            }
+            Change::DescriptorAdded {
+                characteristic,
+                descriptor,
+                ..
+            } => write!(
+                f,
+                "+ descriptor {} of {}",
+                name(descriptor),
+                name(characteristic)
+            ),
+            Change::DescriptorRemoved {
+                characteristic,
+                descriptor,
+                ..
+            } => write!(
+                f,
+                "- descriptor {} of {}",
+                name(descriptor),
+                name(characteristic)
+            ),
+            Change::DescriptorChanged {
+                characteristic,
+                descriptor,
+                old,
+                new,
+                ..
+            } => write!(
+                f,
+                "~ descriptor {} of {}: {} -> {}",
+                name(descriptor),
+                name(characteristic),
+                old,
+                new
+            ),
+        }
+    }
+}
+
+/// Which dump a side of the comparison came from.
+#[derive(Debug, Clone, Serialize)]
+pub struct Snapshot {
+    pub device: String,
+    pub firmware: Option<String>,
+    pub captured_at: DateTime<Utc>,
+}
+
+impl Snapshot {
+    fn of(dump: &GattDump) -> Snapshot {
+        Snapshot {
+            device: dump.device.clone(),
+            firmware: dump.firmware.clone(),
+            captured_at: dump.captured_at,
+        }
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.device)?;
+        if let Some(firmware) = &self.firmware {
+            write!(f, " firmware {}", firmware)?;
+        }
+        write!(f, " at {}", self.captured_at.to_rfc3339())
+    }
+}
+
+/// Differences between an old and a new GATT dump.
+#[derive(Debug, Clone, Serialize)]
+pub struct LayoutDiff {
+    pub old: Snapshot,
+    pub new: Snapshot,
+    pub changes: Vec<Change>,
+}
+
+impl LayoutDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for LayoutDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "--- {}", self.old)?;
+        writeln!(f, "+++ {}", self.new)?;
+        if self.changes.is_empty() {
+            return writeln!(f, "GATT layouts are identical");
+        }
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Attributes keyed by UUID and occurrence of that UUID.
+fn keyed<T>(items: &[T], uuid: impl Fn(&T) -> Uuid) -> BTreeMap<(Uuid, usize), &T> {
+    let mut seen: BTreeMap<Uuid, usize> = BTreeMap::new();
+    items
+        .iter()
+        .map(|item| {
+            let occurrence = seen.entry(uuid(item)).or_default();
+            *occurrence += 1;
+            ((uuid(item), *occurrence - 1), item)
+        })
+        .collect()
+}
+
+/// Lists what changed from `old` to `new`.
+pub fn compare(old: &GattDump, new: &GattDump) -> LayoutDiff {
+    let mut changes = Vec::new();
+    let old_services = keyed(&old.services, |s| s.uuid);
+    let new_services = keyed(&new.services, |s| s.uuid);
+    for (key, service) in &old_services {
+        match new_services.get(key) {
+            Some(newer) => compare_services(service, newer, &mut changes),
+            None => changes.push(Change::ServiceRemoved {
+                service: service.uuid,
+            }),
+        }
+    }
+    for (key, service) in &new_services {
+        if !old_services.contains_key(key) {
+            changes.push(Change::ServiceAdded {
+                service: service.uuid,
+            });
+        }
+    }
+    LayoutDiff {
+        old: Snapshot::of(old),
+        new: Snapshot::of(new),
+        changes,
+    }
+}
+
+fn compare_services(old: &ServiceDump, new: &ServiceDump, changes: &mut Vec<Change>) {
+    let service = old.uuid;
+    let old_characteristics = keyed(&old.characteristics, |c| c.uuid);
+    let new_characteristics = keyed(&new.characteristics, |c| c.uuid);
+    for (key, characteristic) in &old_characteristics {
+        match new_characteristics.get(key) {
+            Some(newer) => compare_characteristics(service, characteristic, newer, changes),
+            None => changes.push(Change::CharacteristicRemoved {
+                service,
+                characteristic: characteristic.uuid,
+            }),
+        }
+    }
+    for (key, characteristic) in &new_characteristics {
+        if !old_characteristics.contains_key(key) {
+            changes.push(Change::CharacteristicAdded {
+                service,
+                characteristic: characteristic.uuid,
+            });
+        }
+    }
+}
+
+fn compare_characteristics(
+    service: Uuid,
+    old: &CharacteristicDump,
+    new: &CharacteristicDump,
+    changes: &mut Vec<Change>,
+) {
+    let characteristic = old.uuid;
+    let added: Vec<String> = new
+        .properties
+        .iter()
+        .filter(|p| !old.properties.contains(p))
+        .cloned()
+        .collect();
+    let removed: Vec<String> = old
+        .properties
+        .iter()
+        .filter(|p| !new.properties.contains(p))
+        .cloned()
+        .collect();
+    if !added.is_empty() || !removed.is_empty() {
+        changes.push(Change::PropertiesChanged {
+            service,
+            characteristic,
+            added,
+            removed,
+        });
+    }
+
+    let old_descriptors = keyed(&old.descriptors, |d| d.uuid);
+    let new_descriptors = keyed(&new.descriptors, |d| d.uuid);
+    for (key, descriptor) in &old_descriptors {
+        match new_descriptors.get(key) {
+            Some(newer) if differs(descriptor, newer) => changes.push(Change::DescriptorChanged {
+                service,
+                characteristic,
+                descriptor: descriptor.uuid,
+                old: descriptor.value.clone(),
+                new: newer.value.clone(),
+            }),
+            Some(_) => {}
+            None => changes.push(Change::DescriptorRemoved {
+                service,
+                characteristic,
+                descriptor: descriptor.uuid,
+            }),
+        }
+    }
+    for (key, descriptor) in &new_descriptors {
+        if !old_descriptors.contains_key(key) {
+            changes.push(Change::DescriptorAdded {
+                service,
+                characteristic,
+                descriptor: descriptor.uuid,
+            });
+        }
+    }
+}
+
+fn differs(old: &DescriptorDump, new: &DescriptorDump) -> bool {
+    old.uuid != CLIENT_CONFIGURATION_UUID && old.value != new.value
+}
@@ -0,0 +1,146 @@
+//! Finding watches in range.
+//!
+//! Discovery follows the adapter's event stream rather than sleeping for a
+//! fixed time: every advertisement is checked against the selector as it
+//! arrives, so a scan can end as soon as the wanted watch shows up.
+
+use crate::backend::{Adapter, Manager, Peripheral};
+use crate::error::{Result, SmartwatchError};
+use crate::selector::DeviceSelector;
+use btleplug::api::PeripheralProperties;
+use futures::stream::StreamExt;
+use std::time::Duration;
+use tokio::time::{self, Instant};
+
+/// Longest a scan waits for devices to show up.
+pub const DEFAULT_SCAN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Name shown for peripherals that do not advertise one.
+pub const UNKNOWN_NAME: &str = "(peripheral name unknown)";
+
+/// A peripheral seen during a scan.
+#[derive(Debug, Clone)]
+pub struct Discovered<P> {
+    pub peripheral: P,
+    pub local_name: String,
+    /// What the peripheral advertised, empty if it vanished before being listed.
+    pub properties: PeripheralProperties,
+}
+
+/// When a scan ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopWhen {
+    /// As soon as a device matches the selector, or at the timeout.
+    FirstMatch,
+    /// At the timeout, so that every device in range has a chance to show up.
+    Timeout,
+}
+
+/// Devices found by a scan and how long it took.
+#[derive(Debug, Clone)]
+pub struct Scan<P> {
+    /// Matching devices, in the order they were first seen.
+    pub devices: Vec<Discovered<P>>,
+    /// Time from the start of the scan until it was stopped.
+    pub elapsed: Duration,
+    /// Whether the scan ended before the timeout because a device matched.
+    pub stopped_early: bool,
+}
+
+/// Lists the Bluetooth adapters of the host, failing if there is none.
+pub async fn adapters<M: Manager>(manager: &M) -> Result<Vec<M::Adapter>> {
+    let adapters = manager
+        .adapters()
+        .await
+        .map_err(|source| SmartwatchError::Adapter { source })?;
+    if adapters.is_empty() {
+        return Err(SmartwatchError::NoAdapter);
+    }
+    Ok(adapters)
+}
+
+/// Scans until `timeout` and returns every peripheral that advertised meanwhile.
+pub async fn scan<A: Adapter>(adapter: &A, timeout: Duration) -> Result<Scan<A::Peripheral>> {
+    find(
+        adapter,
+        &DeviceSelector::default(),
+        timeout,
+        StopWhen::Timeout,
+    )
+    .await
+}
+
+/// Scans for the peripherals `selector` matches, for at most `timeout`.
+///
+/// A device is kept with the most recent properties it advertised. The scan
+/// is stopped explicitly before returning, whatever ended it.
+pub async fn find<A: Adapter>(
+    adapter: &A,
+    selector: &DeviceSelector,
+    timeout: Duration,
+    stop: StopWhen,
+) -> Result<Scan<A::Peripheral>> {
+    let scan_error = |source| SmartwatchError::Scan { source };
+    // Subscribe first so that no advertisement slips through before the scan starts.
+    let mut events = adapter.events().await.map_err(scan_error)?;
+    let started = Instant::now();
+    adapter.start_scan().await.map_err(scan_error)?;
+
+    let deadline = started + timeout;
+    let mut devices: Vec<Discovered<A::Peripheral>> = Vec::new();
+    let mut stopped_early = false;
+    loop {
+        if stop == StopWhen::FirstMatch && !devices.is_empty() {
+            stopped_early = true;
+            break;
+        }
+        let id = match time::timeout_at(deadline, events.next()).await {
+            Ok(Some(event)) if event.is_advertisement() => event.id().clone(),
+            Ok(Some(_)) => continue,
+            // The adapter went away or the time is up.
+            Ok(None) | Err(_) => break,
+        };
+        let seen = match adapter.peripheral(&id).await {
+            Ok(peripheral) => describe(peripheral).await,
+            Err(source) => Err(scan_error(source)),
+        };
+        match seen {
+            Ok(device) if selector.matches(&device) => {
+                match devices.iter_mut().find(|d| d.peripheral.id() == id) {
+                    Some(known) => *known = device,
+                    None => devices.push(device),
+                }
+            }
+            Ok(_) => {}
+            // On a busy radio a device can vanish between its advert and the
+            // lookup; the others are still worth finding.
+            Err(err) => log::debug!("Skipping peripheral {}: {}", id, err),
+        }
+    }
+    let elapsed = started.elapsed();
+    adapter.stop_scan().await.map_err(scan_error)?;
+    Ok(Scan {
+        devices,
+        elapsed,
+        stopped_early,
+    })
+}
+
+/// Reads what `peripheral` advertised so far.
+pub(crate) async fn describe<P: Peripheral>(peripheral: P) -> Result<Discovered<P>> {
+    // Peripherals that vanished meanwhile have no properties left.
+    let properties = peripheral
+        .properties()
+        .await
+        .map_err(|source| SmartwatchError::Scan { source })?
+        .unwrap_or_default();
+    let local_name = properties
+        .local_name
+        .clone()
+        .unwrap_or(String::from(UNKNOWN_NAME));
+    Ok(Discovered {
+        peripheral,
+        local_name,
+        properties,
+    })
+}
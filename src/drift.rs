@@ -0,0 +1,231 @@
+//! Measurement of the offset between the watch clock and the host clock.
+
+use crate::backend::Peripheral;
+use crate::clock::Clock;
+use crate::connection;
+use crate::error::{Result, SmartwatchError};
+use crate::gatt::cts::CurrentTime;
+use btleplug::api::Characteristic;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+/// Header of the drift history CSV file.
+const HISTORY_HEADER: &str = "measured_at,device,phase,offset_ms,round_trip_ms";
+
+/// Intervals shorter than this are too noisy to derive a drift rate from.
+const MIN_DRIFT_INTERVAL_HOURS: i64 = 1;
+
+/// One comparison of the watch clock with the host clock.
+#[derive(Debug, Clone)]
+pub struct Measurement {
+    /// Reference time at which the watch is assumed to have sampled its clock.
+    pub measured_at: DateTime<Utc>,
+    /// Watch time minus reference time; positive when the watch is ahead.
+    pub offset: Duration,
+    /// Duration of the GATT read the measurement is based on.
+    pub round_trip: Duration,
+    pub watch_time: CurrentTime,
+}
+
+/// Reads the Current Time characteristic and compares it with `clock`.
+///
+/// The watch samples its clock at an unknown point during the read, so the
+/// reference time is taken halfway through the round trip.
+pub async fn measure(
+    peripheral: &impl Peripheral,
+    characteristic: &Characteristic,
+    zone: &Tz,
+    clock: &dyn Clock,
+) -> Result<Measurement> {
+    let device = connection::device_label(peripheral);
+    let sent = clock.now();
+    let value = connection::read(peripheral, characteristic).await?;
+    let received = clock.now();
+
+    let codec_error = |err| SmartwatchError::codec(&device, characteristic.uuid, err);
+    let watch_time = CurrentTime::decode(&value).map_err(codec_error)?;
+    let local = watch_time.to_naive_datetime().map_err(codec_error)?;
+    let watch = zone
+        .from_local_datetime(&local)
+        .earliest()
+        .ok_or_else(|| SmartwatchError::NonexistentLocalTime {
+            device: device.clone(),
+            time: local,
+        })?
+        .with_timezone(&Utc);
+    let round_trip = received - sent;
+    let measured_at = sent + round_trip / 2;
+    Ok(Measurement {
+        measured_at,
+        offset: watch - measured_at,
+        round_trip,
+        watch_time,
+    })
+}
+
+/// Whether a measurement was taken before or after setting the watch, or
+/// marks a sync that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    BeforeSync,
+    AfterSync,
+    /// The watch could not be synced; the record holds no measurement.
+    Failed,
+}
+
+impl Phase {
+    fn as_str(self) -> &'static str {
+        match self {
+            Phase::BeforeSync => "before",
+            Phase::AfterSync => "after",
+            Phase::Failed => "failed",
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} sync", self.as_str())
+    }
+}
+
+/// A line of the drift history file.
+#[derive(Debug, Clone)]
+pub struct DriftRecord {
+    pub measured_at: DateTime<Utc>,
+    pub device: String,
+    pub phase: Phase,
+    pub offset_ms: i64,
+    pub round_trip_ms: i64,
+}
+
+impl DriftRecord {
+    pub fn new(device: &str, phase: Phase, measurement: &Measurement) -> DriftRecord {
+        DriftRecord {
+            measured_at: measurement.measured_at,
+            device: device.to_string(),
+            phase,
+            offset_ms: measurement.offset.num_milliseconds(),
+            round_trip_ms: measurement.round_trip.num_milliseconds(),
+        }
+    }
+
+    /// Marks a sync of `device` that failed at `at`.
+    pub fn failure(device: &str, at: DateTime<Utc>) -> DriftRecord {
+        DriftRecord {
+            measured_at: at,
+            device: device.to_string(),
+            phase: Phase::Failed,
+            offset_ms: 0,
+            round_trip_ms: 0,
+        }
+    }
+
+    fn parse(line: &str) -> Option<DriftRecord> {
+        let mut fields = line.split(',');
+        let record = DriftRecord {
+            measured_at: DateTime::parse_from_rfc3339(fields.next()?)
+                .ok()?
+                .with_timezone(&Utc),
+            device: fields.next()?.to_string(),
+            phase: match fields.next()? {
+                "before" => Phase::BeforeSync,
+                "after" => Phase::AfterSync,
+                "failed" => Phase::Failed,
+                _ => return None,
+            },
+            offset_ms: fields.next()?.parse().ok()?,
+            round_trip_ms: fields.next()?.parse().ok()?,
+        };
+        fields.next().is_none().then_some(record)
+    }
+}
+
+impl fmt::Display for DriftRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{}",
+            self.measured_at.to_rfc3339(),
+            self.device,
+            self.phase.as_str(),
+            self.offset_ms,
+            self.round_trip_ms
+        )
+    }
+}
+
+/// Appends `record` to the history file, creating it with a header if needed.
+pub fn append(path: &Path, record: &DriftRecord) -> Result<()> {
+    let io_error = SmartwatchError::io(path);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(SmartwatchError::io(path))?;
+    let write = |file: &mut File| -> io::Result<()> {
+        if file.metadata()?.len() == 0 {
+            writeln!(file, "{}", HISTORY_HEADER)?;
+        }
+        writeln!(file, "{}", record)
+    };
+    write(&mut file).map_err(io_error)
+}
+
+/// Loads every record of the history file; a missing file is an empty history.
+pub fn load(path: &Path) -> Result<Vec<DriftRecord>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(SmartwatchError::io(path)(err)),
+    };
+    let mut records = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(SmartwatchError::io(path))?;
+        if number == 0 && line == HISTORY_HEADER || line.trim().is_empty() {
+            continue;
+        }
+        let record = DriftRecord::parse(&line).ok_or_else(|| {
+            SmartwatchError::io(path)(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: malformed drift record", number + 1),
+            ))
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Average drift of `device` in milliseconds per day.
+///
+/// Every sync followed by a later "before sync" measurement tells how far the
+/// watch wandered off on its own in between.
+pub fn drift_per_day(records: &[DriftRecord], device: &str) -> Option<f64> {
+    let mut records: Vec<&DriftRecord> = records
+        .iter()
+        .filter(|r| r.device == device && r.phase != Phase::Failed)
+        .collect();
+    records.sort_by_key(|r| r.measured_at);
+
+    let mut drift_ms = 0i64;
+    let mut elapsed = Duration::zero();
+    for pair in records.windows(2) {
+        let (synced, next) = (pair[0], pair[1]);
+        let interval = next.measured_at - synced.measured_at;
+        if synced.phase == Phase::AfterSync
+            && next.phase == Phase::BeforeSync
+            && interval >= Duration::hours(MIN_DRIFT_INTERVAL_HOURS)
+        {
+            drift_ms += next.offset_ms - synced.offset_ms;
+            elapsed += interval;
+        }
+    }
+    if elapsed.is_zero() {
+        return None;
+    }
+    Some(drift_ms as f64 / (elapsed.num_milliseconds() as f64 / 86_400_000.0))
+}
@@ -0,0 +1,266 @@
+//! Snapshots of the GATT database of a device.
+//!
+//! A dump lists every service, characteristic and descriptor with the
+//! properties and descriptor values the device reported, so that the layout
+//! of each firmware version can be archived as JSON and compared later.
+
+use crate::assigned::{self, NamedUuid};
+use crate::backend::Peripheral;
+use crate::clock::Clock;
+use crate::connection;
+use crate::error::{Result, SmartwatchError};
+use crate::gatt::descriptor::{
+    self, ClientConfiguration, PresentationFormat, CLIENT_CONFIGURATION_UUID,
+    PRESENTATION_FORMAT_UUID, USER_DESCRIPTION_UUID,
+};
+use crate::gatt::{self, FIRMWARE_REVISION_UUID};
+use btleplug::api::{CharPropFlags, Characteristic};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Names of the characteristic properties, as they appear in dumps.
+pub const PROPERTY_NAMES: &[(CharPropFlags, &str)] = &[
+    (CharPropFlags::BROADCAST, "broadcast"),
+    (CharPropFlags::READ, "read"),
+    (
+        CharPropFlags::WRITE_WITHOUT_RESPONSE,
+        "write_without_response",
+    ),
+    (CharPropFlags::WRITE, "write"),
+    (CharPropFlags::NOTIFY, "notify"),
+    (CharPropFlags::INDICATE, "indicate"),
+    (
+        CharPropFlags::AUTHENTICATED_SIGNED_WRITES,
+        "authenticated_signed_writes",
+    ),
+    (CharPropFlags::EXTENDED_PROPERTIES, "extended_properties"),
+];
+
+/// Names of the properties set in `flags`.
+pub fn property_names(flags: CharPropFlags) -> Vec<String> {
+    PROPERTY_NAMES
+        .iter()
+        .filter(|(flag, _)| flags.contains(*flag))
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+/// GATT database of a device at one point in time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GattDump {
+    pub device: String,
+    pub name: String,
+    pub captured_at: DateTime<Utc>,
+    /// Firmware Revision String of the Device Information service, if readable.
+    pub firmware: Option<String>,
+    pub services: Vec<ServiceDump>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceDump {
+    pub uuid: Uuid,
+    pub name: Option<String>,
+    pub primary: bool,
+    pub characteristics: Vec<CharacteristicDump>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CharacteristicDump {
+    pub uuid: Uuid,
+    pub name: Option<String>,
+    /// Names of the properties, see [`PROPERTY_NAMES`].
+    pub properties: Vec<String>,
+    /// Decoded Characteristic User Description.
+    pub user_description: Option<String>,
+    /// Decoded Characteristic Presentation Format.
+    pub presentation_format: Option<PresentationFormat>,
+    /// Decoded Client Characteristic Configuration.
+    pub client_configuration: Option<ClientConfiguration>,
+    /// Value, only read when asked for.
+    pub value: Option<Reading>,
+    pub descriptors: Vec<DescriptorDump>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DescriptorDump {
+    pub uuid: Uuid,
+    pub name: Option<String>,
+    pub value: Reading,
+}
+
+/// Outcome of reading an attribute while dumping.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reading {
+    /// Bytes read, in hex as printed by [`gatt::hex`].
+    Value(String),
+    /// Why the read failed, e.g. because the attribute requires encryption.
+    Error(String),
+}
+
+impl Reading {
+    /// The bytes read, `None` if the read failed.
+    pub fn bytes(&self) -> Option<Vec<u8>> {
+        match self {
+            Reading::Value(hex) => gatt::parse_hex(hex).ok(),
+            Reading::Error(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Reading {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reading::Value(hex) => write!(f, "{}", hex),
+            Reading::Error(err) => write!(f, "unreadable: {}", err),
+        }
+    }
+}
+
+/// Loads a dump saved as JSON.
+pub fn load(path: &Path) -> Result<GattDump> {
+    let text = fs::read_to_string(path).map_err(SmartwatchError::io(path))?;
+    serde_json::from_str(&text).map_err(|err| SmartwatchError::io(path)(err.into()))
+}
+
+fn name_of(uuid: &Uuid) -> Option<String> {
+    assigned::registry().uuid_name(uuid).map(str::to_string)
+}
+
+/// Walks the discovered services of a connected device, reading every
+/// descriptor and, when `read_values` is set, every readable characteristic.
+///
+/// A failed read does not end the dump; its error is recorded in place of the value.
+pub async fn capture(
+    peripheral: &impl Peripheral,
+    local_name: &str,
+    read_values: bool,
+    clock: &dyn Clock,
+) -> GattDump {
+    let captured_at = clock.now();
+    let firmware = match connection::read_if_supported(peripheral, FIRMWARE_REVISION_UUID).await {
+        Ok(value) => value.map(|bytes| String::from_utf8_lossy(&bytes).into_owned()),
+        Err(_) => None,
+    };
+    let mut services = Vec::new();
+    for service in peripheral.services() {
+        let mut characteristics = Vec::new();
+        for characteristic in &service.characteristics {
+            characteristics
+                .push(capture_characteristic(peripheral, characteristic, read_values).await);
+        }
+        services.push(ServiceDump {
+            uuid: service.uuid,
+            name: name_of(&service.uuid),
+            primary: service.primary,
+            characteristics,
+        });
+    }
+    GattDump {
+        device: connection::device_label(peripheral),
+        name: local_name.to_string(),
+        captured_at,
+        firmware,
+        services,
+    }
+}
+
+async fn capture_characteristic(
+    peripheral: &impl Peripheral,
+    characteristic: &Characteristic,
+    read_values: bool,
+) -> CharacteristicDump {
+    let value = if read_values && characteristic.properties.contains(CharPropFlags::READ) {
+        Some(reading(connection::read(peripheral, characteristic).await))
+    } else {
+        None
+    };
+    let mut dump = CharacteristicDump {
+        uuid: characteristic.uuid,
+        name: name_of(&characteristic.uuid),
+        properties: property_names(characteristic.properties),
+        user_description: None,
+        presentation_format: None,
+        client_configuration: None,
+        value,
+        descriptors: Vec::new(),
+    };
+    for descriptor in &characteristic.descriptors {
+        let read = connection::read_descriptor(peripheral, descriptor).await;
+        if let Ok(bytes) = &read {
+            if descriptor.uuid == USER_DESCRIPTION_UUID {
+                dump.user_description = Some(descriptor::decode_user_description(bytes));
+            } else if descriptor.uuid == PRESENTATION_FORMAT_UUID {
+                dump.presentation_format = PresentationFormat::decode(bytes).ok();
+            } else if descriptor.uuid == CLIENT_CONFIGURATION_UUID {
+                dump.client_configuration = ClientConfiguration::decode(bytes).ok();
+            }
+        }
+        dump.descriptors.push(DescriptorDump {
+            uuid: descriptor.uuid,
+            name: name_of(&descriptor.uuid),
+            value: reading(read),
+        });
+    }
+    dump
+}
+
+fn reading(read: Result<Vec<u8>>) -> Reading {
+    match read {
+        Ok(bytes) => Reading::Value(gatt::hex(&bytes)),
+        Err(err) => Reading::Error(err.to_string()),
+    }
+}
+
+/// The dump as an indented tree.
+impl fmt::Display for GattDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:?}", self.device, self.name)?;
+        if let Some(firmware) = &self.firmware {
+            write!(f, " firmware {}", firmware)?;
+        }
+        writeln!(f, " at {}", self.captured_at.to_rfc3339())?;
+        for service in &self.services {
+            let kind = if service.primary {
+                "primary"
+            } else {
+                "secondary"
+            };
+            writeln!(f, "Service {} ({})", NamedUuid::new(service.uuid), kind)?;
+            for characteristic in &service.characteristics {
+                write!(
+                    f,
+                    "  Characteristic {} [{}]",
+                    NamedUuid::new(characteristic.uuid),
+                    characteristic.properties.join(", ")
+                )?;
+                if let Some(value) = &characteristic.value {
+                    write!(f, " = {}", value)?;
+                }
+                writeln!(f)?;
+                if let Some(description) = &characteristic.user_description {
+                    writeln!(f, "    Description: {:?}", description)?;
+                }
+                if let Some(format) = &characteristic.presentation_format {
+                    writeln!(f, "    Format: {}", format)?;
+                }
+                if let Some(configuration) = &characteristic.client_configuration {
+                    writeln!(f, "    Subscription: {}", configuration)?;
+                }
+                for descriptor in &characteristic.descriptors {
+                    writeln!(
+                        f,
+                        "    Descriptor {}: {}",
+                        NamedUuid::new(descriptor.uuid),
+                        descriptor.value
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,377 @@
+//! Errors returned by the library.
+//!
+//! Every failure names the device and characteristic involved when there is
+//! one, and falls into an [`ErrorCategory`] that the command line maps to a
+//! distinct exit code.
+
+use crate::assigned;
+use crate::gatt::CodecError;
+use crate::zone::ZoneError;
+use chrono::NaiveDateTime;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Everything that can go wrong while talking to a watch.
+#[derive(Debug)]
+pub enum SmartwatchError {
+    /// The Bluetooth manager or adapter could not be used.
+    Adapter { source: btleplug::Error },
+    /// The host has no Bluetooth adapter.
+    NoAdapter,
+    /// The adapter asked for is not among those of the host.
+    AdapterNotFound { index: usize, count: usize },
+    /// Scanning for peripherals failed.
+    Scan { source: btleplug::Error },
+    /// Connecting to or disconnecting from a device failed.
+    Connect {
+        device: String,
+        source: btleplug::Error,
+    },
+    /// The services of a device could not be discovered.
+    Discovery {
+        device: String,
+        source: btleplug::Error,
+    },
+    /// Reading a characteristic failed.
+    Read {
+        device: String,
+        characteristic: Uuid,
+        source: btleplug::Error,
+    },
+    /// Writing a characteristic failed.
+    Write {
+        device: String,
+        characteristic: Uuid,
+        source: btleplug::Error,
+    },
+    /// Subscribing to or receiving notifications of a characteristic failed.
+    Subscribe {
+        device: String,
+        characteristic: Uuid,
+        source: btleplug::Error,
+    },
+    /// A characteristic value could not be encoded or decoded.
+    Codec {
+        device: Option<String>,
+        characteristic: Option<Uuid>,
+        source: CodecError,
+    },
+    /// An operation did not complete in time.
+    Timeout {
+        device: String,
+        operation: &'static str,
+        after: Duration,
+    },
+    /// The device does not offer an operation the caller relies on.
+    Unsupported {
+        device: String,
+        characteristic: Uuid,
+        operation: &'static str,
+    },
+    /// The device does not have a characteristic the caller asked for.
+    MissingCharacteristic {
+        device: String,
+        characteristic: Uuid,
+    },
+    /// The time zone to sync to could not be determined.
+    Zone(ZoneError),
+    /// The watch shows a wall clock time that does not exist in its time zone.
+    NonexistentLocalTime { device: String, time: NaiveDateTime },
+    /// A time computation left the range that can be represented.
+    TimeOutOfRange,
+    /// Reading or writing a local file failed.
+    Io { path: PathBuf, source: io::Error },
+    /// The drift history holds no sync of the device.
+    NoSyncRecorded { device: String },
+    /// The GATT layout of a device differs from the one it was compared with.
+    LayoutChanged { device: String, changes: usize },
+    /// The settings file is invalid.
+    Config { path: PathBuf, message: String },
+    /// The command line options are invalid, on their own or with the settings.
+    InvalidOptions { message: String },
+    /// No device of the settings goes by the alias asked for.
+    UnknownDevice { alias: String, known: Vec<String> },
+}
+
+/// Result type used throughout the library.
+pub type Result<T> = std::result::Result<T, SmartwatchError>;
+
+/// Broad kind of a [`SmartwatchError`], for callers that react per kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    Adapter,
+    Scan,
+    Connect,
+    Discovery,
+    Read,
+    Write,
+    /// Subscribing to notifications failed.
+    Notify,
+    Codec,
+    Timeout,
+    Unsupported,
+    Time,
+    Io,
+    /// The drift history has nothing to plan from.
+    History,
+    /// A comparison found differences, reported like `diff` does.
+    Changed,
+    /// The settings or options are invalid, or the settings lack the device asked for.
+    Config,
+}
+
+impl ErrorCategory {
+    /// Process exit code reported by the command line for this category.
+    ///
+    /// Failures use codes from 10 up. Differences found by a comparison exit
+    /// with 1 as `diff` does, which stays apart from the 2 that clap uses for
+    /// command line errors.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorCategory::Adapter => 10,
+            ErrorCategory::Scan => 11,
+            ErrorCategory::Connect => 12,
+            ErrorCategory::Discovery => 13,
+            ErrorCategory::Read => 14,
+            ErrorCategory::Write => 15,
+            ErrorCategory::Codec => 16,
+            ErrorCategory::Timeout => 17,
+            ErrorCategory::Unsupported => 18,
+            ErrorCategory::Time => 19,
+            ErrorCategory::Io => 20,
+            ErrorCategory::Config => 21,
+            ErrorCategory::Notify => 22,
+            ErrorCategory::History => 23,
+            ErrorCategory::Changed => 1,
+        }
+    }
+}
+
+impl SmartwatchError {
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            SmartwatchError::Adapter { .. }
+            | SmartwatchError::NoAdapter
+            | SmartwatchError::AdapterNotFound { .. } => ErrorCategory::Adapter,
+            SmartwatchError::Scan { .. } => ErrorCategory::Scan,
+            SmartwatchError::Connect { .. } => ErrorCategory::Connect,
+            SmartwatchError::Discovery { .. } => ErrorCategory::Discovery,
+            SmartwatchError::Read { .. } => ErrorCategory::Read,
+            SmartwatchError::Write { .. } => ErrorCategory::Write,
+            SmartwatchError::Subscribe { .. } => ErrorCategory::Notify,
+            SmartwatchError::Codec { .. } => ErrorCategory::Codec,
+            SmartwatchError::Timeout { .. } => ErrorCategory::Timeout,
+            SmartwatchError::Unsupported { .. } | SmartwatchError::MissingCharacteristic { .. } => {
+                ErrorCategory::Unsupported
+            }
+            SmartwatchError::Zone(_)
+            | SmartwatchError::NonexistentLocalTime { .. }
+            | SmartwatchError::TimeOutOfRange => ErrorCategory::Time,
+            SmartwatchError::Io { .. } => ErrorCategory::Io,
+            SmartwatchError::NoSyncRecorded { .. } => ErrorCategory::History,
+            SmartwatchError::LayoutChanged { .. } => ErrorCategory::Changed,
+            SmartwatchError::Config { .. }
+            | SmartwatchError::InvalidOptions { .. }
+            | SmartwatchError::UnknownDevice { .. } => ErrorCategory::Config,
+        }
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        self.category().exit_code()
+    }
+
+    /// Attaches the device and characteristic a codec error came from.
+    pub fn codec(device: &str, characteristic: Uuid, source: CodecError) -> SmartwatchError {
+        SmartwatchError::Codec {
+            device: Some(device.to_string()),
+            characteristic: Some(characteristic),
+            source,
+        }
+    }
+
+    pub(crate) fn io(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> SmartwatchError {
+        let path = path.into();
+        move |source| SmartwatchError::Io { path, source }
+    }
+}
+
+impl fmt::Display for SmartwatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SmartwatchError::Adapter { source } => write!(f, "Bluetooth adapter error: {}", source),
+            SmartwatchError::NoAdapter => write!(f, "no Bluetooth adapters found"),
+            SmartwatchError::AdapterNotFound { index, count } => write!(
+                f,
+                "no Bluetooth adapter {}, the host has {} (counting from 0)",
+                index, count
+            ),
+            SmartwatchError::Scan { source } => write!(f, "scan failed: {}", source),
+            SmartwatchError::Connect { device, source } => {
+                write!(f, "{}: connection failed: {}", device, source)
+            }
+            SmartwatchError::Discovery { device, source } => {
+                write!(f, "{}: service discovery failed: {}", device, source)
+            }
+            SmartwatchError::Read {
+                device,
+                characteristic,
+                source,
+            } => write!(
+                f,
+                "{}: reading {} failed: {}",
+                device,
+                assigned::describe(characteristic),
+                source
+            ),
+            SmartwatchError::Write {
+                device,
+                characteristic,
+                source,
+            } => write!(
+                f,
+                "{}: writing {} failed: {}",
+                device,
+                assigned::describe(characteristic),
+                source
+            ),
+            SmartwatchError::Subscribe {
+                device,
+                characteristic,
+                source,
+            } => write!(
+                f,
+                "{}: notifications of {} failed: {}",
+                device,
+                assigned::describe(characteristic),
+                source
+            ),
+            SmartwatchError::Codec {
+                device,
+                characteristic,
+                source,
+            } => {
+                if let Some(device) = device {
+                    write!(f, "{}: ", device)?;
+                }
+                match characteristic {
+                    Some(uuid) => write!(
+                        f,
+                        "invalid value of {}: {}",
+                        assigned::describe(uuid),
+                        source
+                    ),
+                    None => write!(f, "invalid characteristic value: {}", source),
+                }
+            }
+            SmartwatchError::Timeout {
+                device,
+                operation,
+                after,
+            } => write!(
+                f,
+                "{}: {} timed out after {} ms",
+                device,
+                operation,
+                after.as_millis()
+            ),
+            SmartwatchError::Unsupported {
+                device,
+                characteristic,
+                operation,
+            } => write!(
+                f,
+                "{}: characteristic {} does not support {}",
+                device,
+                assigned::describe(characteristic),
+                operation
+            ),
+            SmartwatchError::MissingCharacteristic {
+                device,
+                characteristic,
+            } => write!(
+                f,
+                "{}: no characteristic {}",
+                device,
+                assigned::describe(characteristic)
+            ),
+            SmartwatchError::Zone(err) => write!(f, "{}", err),
+            SmartwatchError::NonexistentLocalTime { device, time } => write!(
+                f,
+                "{}: local time {} does not exist in the watch time zone",
+                device, time
+            ),
+            SmartwatchError::TimeOutOfRange => write!(f, "time out of range"),
+            SmartwatchError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            SmartwatchError::NoSyncRecorded { device } => {
+                write!(f, "drift history holds no sync of {}", device)
+            }
+            SmartwatchError::LayoutChanged { device, changes } => {
+                write!(f, "{}: GATT layout has {} change(s)", device, changes)
+            }
+            SmartwatchError::Config { path, message } => {
+                write!(f, "invalid settings in {}: {}", path.display(), message)
+            }
+            SmartwatchError::InvalidOptions { message } => {
+                write!(f, "invalid options: {}", message)
+            }
+            SmartwatchError::UnknownDevice { alias, known } if known.is_empty() => {
+                write!(f, "no device {:?} in the settings, which list none", alias)
+            }
+            SmartwatchError::UnknownDevice { alias, known } => write!(
+                f,
+                "no device {:?} in the settings, known devices: {}",
+                alias,
+                known.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SmartwatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SmartwatchError::Adapter { source }
+            | SmartwatchError::Scan { source }
+            | SmartwatchError::Connect { source, .. }
+            | SmartwatchError::Discovery { source, .. }
+            | SmartwatchError::Read { source, .. }
+            | SmartwatchError::Write { source, .. }
+            | SmartwatchError::Subscribe { source, .. } => Some(source),
+            SmartwatchError::Codec { source, .. } => Some(source),
+            SmartwatchError::Zone(err) => Some(err),
+            SmartwatchError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<CodecError> for SmartwatchError {
+    fn from(source: CodecError) -> SmartwatchError {
+        SmartwatchError::Codec {
+            device: None,
+            characteristic: None,
+            source,
+        }
+    }
+}
+
+impl From<ZoneError> for SmartwatchError {
+    fn from(err: ZoneError) -> SmartwatchError {
+        SmartwatchError::Zone(err)
+    }
+}
+
+impl From<chrono::OutOfRangeError> for SmartwatchError {
+    fn from(_: chrono::OutOfRangeError) -> SmartwatchError {
+        SmartwatchError::TimeOutOfRange
+    }
+}
+
+impl From<chrono::RoundingError> for SmartwatchError {
+    fn from(_: chrono::RoundingError) -> SmartwatchError {
+        SmartwatchError::TimeOutOfRange
+    }
+}
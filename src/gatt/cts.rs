@@ -0,0 +1,647 @@
+//! Encoding and decoding of the Current Time Service (0x1805) characteristics.
+//!
+//! All multi-byte fields are little endian, as everywhere else in GATT.
+
+use super::{out_of_range, CodecError};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike, Weekday};
+use std::fmt;
+
+/// Size in bytes of a Current Time (0x2A2B) value: Exact Time 256 + Adjust Reason.
+pub const CURRENT_TIME_LEN: usize = 10;
+
+/// Adjust Reason flags telling the watch why its time was changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AdjustReason(u8);
+
+impl AdjustReason {
+    pub const NONE: AdjustReason = AdjustReason(0);
+    pub const MANUAL_TIME_UPDATE: AdjustReason = AdjustReason(1 << 0);
+    pub const EXTERNAL_REFERENCE_TIME_UPDATE: AdjustReason = AdjustReason(1 << 1);
+    pub const CHANGE_OF_TIME_ZONE: AdjustReason = AdjustReason(1 << 2);
+    pub const CHANGE_OF_DST: AdjustReason = AdjustReason(1 << 3);
+
+    const ALL: u8 = 0x0F;
+
+    /// Returns the flags for `bits`, or `None` if any reserved bit is set.
+    pub fn from_bits(bits: u8) -> Option<AdjustReason> {
+        (bits & !Self::ALL == 0).then_some(AdjustReason(bits))
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn contains(self, other: AdjustReason) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl fmt::Display for AdjustReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (AdjustReason::MANUAL_TIME_UPDATE, "manual"),
+            (
+                AdjustReason::EXTERNAL_REFERENCE_TIME_UPDATE,
+                "external reference",
+            ),
+            (AdjustReason::CHANGE_OF_TIME_ZONE, "time zone"),
+            (AdjustReason::CHANGE_OF_DST, "DST"),
+        ];
+        let set: Vec<&str> = names
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect();
+        if set.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", set.join(", "))
+        }
+    }
+}
+
+/// Value of the Current Time characteristic (0x2A2B).
+///
+/// Zero in `year`, `month` or `day` means "not known", exactly as on the wire,
+/// so that a decoded value always encodes back to the same bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CurrentTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// `None` when the watch reports the day of week as unknown.
+    pub day_of_week: Option<Weekday>,
+    /// Sub-second part in 1/256 s.
+    pub fractions256: u8,
+    pub adjust_reason: AdjustReason,
+}
+
+impl CurrentTime {
+    /// Builds the value for a calendar date and time, rounding the sub-second
+    /// part down to the nearest 1/256 s.
+    pub fn from_datetime<T: Datelike + Timelike>(
+        datetime: &T,
+        adjust_reason: AdjustReason,
+    ) -> Result<CurrentTime, CodecError> {
+        let year = u16::try_from(datetime.year())
+            .map_err(|_| out_of_range("year", datetime.year().unsigned_abs()))?;
+        // chrono encodes a leap second as nanoseconds past 1_000_000_000.
+        let nanos = u64::from(datetime.nanosecond().min(999_999_999));
+        let time = CurrentTime {
+            year,
+            month: datetime.month() as u8,
+            day: datetime.day() as u8,
+            hour: datetime.hour() as u8,
+            minute: datetime.minute() as u8,
+            second: datetime.second() as u8,
+            day_of_week: Some(datetime.weekday()),
+            fractions256: (nanos * 256 / 1_000_000_000) as u8,
+            adjust_reason,
+        };
+        time.validate()?;
+        Ok(time)
+    }
+
+    /// Parses the bytes read back from the characteristic.
+    pub fn decode(bytes: &[u8]) -> Result<CurrentTime, CodecError> {
+        if bytes.len() != CURRENT_TIME_LEN {
+            return Err(CodecError::Length {
+                expected: CURRENT_TIME_LEN,
+                actual: bytes.len(),
+            });
+        }
+        let day_of_week = match bytes[7] {
+            0 => None,
+            n @ 1..=7 => Some(weekday_from_number(n)),
+            n => {
+                return Err(CodecError::OutOfRange {
+                    field: "day of week",
+                    value: n.into(),
+                })
+            }
+        };
+        let adjust_reason = AdjustReason::from_bits(bytes[9]).ok_or(CodecError::ReservedBits {
+            field: "adjust reason",
+            value: bytes[9],
+        })?;
+        let time = CurrentTime {
+            year: u16::from_le_bytes([bytes[0], bytes[1]]),
+            month: bytes[2],
+            day: bytes[3],
+            hour: bytes[4],
+            minute: bytes[5],
+            second: bytes[6],
+            day_of_week,
+            fractions256: bytes[8],
+            adjust_reason,
+        };
+        time.validate()?;
+        Ok(time)
+    }
+
+    /// Serializes the value in the layout expected by the characteristic.
+    pub fn encode(&self) -> Result<[u8; CURRENT_TIME_LEN], CodecError> {
+        self.validate()?;
+        let year = self.year.to_le_bytes();
+        Ok([
+            year[0],
+            year[1],
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+            self.day_of_week.map_or(0, |d| d.number_from_monday() as u8),
+            self.fractions256,
+            self.adjust_reason.bits(),
+        ])
+    }
+
+    /// The date and time as a calendar value, failing if any date field is unknown.
+    pub fn to_naive_datetime(&self) -> Result<NaiveDateTime, CodecError> {
+        let nanos = (u64::from(self.fractions256) * 1_000_000_000 / 256) as u32;
+        NaiveDate::from_ymd_opt(self.year.into(), self.month.into(), self.day.into())
+            .and_then(|date| {
+                date.and_hms_nano_opt(
+                    self.hour.into(),
+                    self.minute.into(),
+                    self.second.into(),
+                    nanos,
+                )
+            })
+            .ok_or(CodecError::InvalidDate {
+                year: self.year,
+                month: self.month,
+                day: self.day,
+            })
+    }
+
+    /// Checks every field against the ranges allowed by the specification.
+    pub fn validate(&self) -> Result<(), CodecError> {
+        if self.year != 0 && !(1582..=9999).contains(&self.year) {
+            return Err(out_of_range("year", self.year));
+        }
+        if self.month > 12 {
+            return Err(out_of_range("month", self.month));
+        }
+        if self.day > 31 {
+            return Err(out_of_range("day", self.day));
+        }
+        if self.hour > 23 {
+            return Err(out_of_range("hour", self.hour));
+        }
+        if self.minute > 59 {
+            return Err(out_of_range("minute", self.minute));
+        }
+        if self.second > 59 {
+            return Err(out_of_range("second", self.second));
+        }
+        if self.adjust_reason.bits() & !AdjustReason::ALL != 0 {
+            return Err(CodecError::ReservedBits {
+                field: "adjust reason",
+                value: self.adjust_reason.bits(),
+            });
+        }
+        if self.year != 0
+            && self.month != 0
+            && self.day != 0
+            && NaiveDate::from_ymd_opt(self.year.into(), self.month.into(), self.day.into())
+                .is_none()
+        {
+            return Err(CodecError::InvalidDate {
+                year: self.year,
+                month: self.month,
+                day: self.day,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CurrentTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{}",
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+            u32::from(self.fractions256) * 10 / 256
+        )?;
+        match self.day_of_week {
+            Some(day) => write!(f, " ({})", day),
+            None => write!(f, " (?)"),
+        }
+    }
+}
+
+/// Maps the CTS day-of-week numbering (Monday = 1 ... Sunday = 7).
+fn weekday_from_number(n: u8) -> Weekday {
+    match n {
+        1 => Weekday::Mon,
+        2 => Weekday::Tue,
+        3 => Weekday::Wed,
+        4 => Weekday::Thu,
+        5 => Weekday::Fri,
+        6 => Weekday::Sat,
+        _ => Weekday::Sun,
+    }
+}
+
+/// Size in bytes of a Local Time Information (0x2A0F) value.
+pub const LOCAL_TIME_INFORMATION_LEN: usize = 2;
+
+/// Time zone value meaning "offset not known".
+const TIME_ZONE_UNKNOWN: i8 = -128;
+
+/// Daylight saving offset applied on top of the standard time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DstOffset {
+    Standard,
+    HalfHour,
+    Daylight,
+    DoubleDaylight,
+    Unknown,
+}
+
+impl DstOffset {
+    /// Maps an offset in minutes to its value, failing unless it is 0, 30, 60 or
+    /// 120 minutes, the only ones the specification can express.
+    pub fn from_minutes(minutes: i64) -> Result<DstOffset, CodecError> {
+        match minutes {
+            0 => Ok(DstOffset::Standard),
+            30 => Ok(DstOffset::HalfHour),
+            60 => Ok(DstOffset::Daylight),
+            120 => Ok(DstOffset::DoubleDaylight),
+            _ => Err(out_of_range(
+                "DST offset minutes",
+                minutes.unsigned_abs() as u32,
+            )),
+        }
+    }
+
+    pub fn minutes(self) -> Option<i64> {
+        match self {
+            DstOffset::Standard => Some(0),
+            DstOffset::HalfHour => Some(30),
+            DstOffset::Daylight => Some(60),
+            DstOffset::DoubleDaylight => Some(120),
+            DstOffset::Unknown => None,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<DstOffset, CodecError> {
+        match byte {
+            0 => Ok(DstOffset::Standard),
+            2 => Ok(DstOffset::HalfHour),
+            4 => Ok(DstOffset::Daylight),
+            8 => Ok(DstOffset::DoubleDaylight),
+            255 => Ok(DstOffset::Unknown),
+            n => Err(out_of_range("DST offset", n)),
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            DstOffset::Standard => 0,
+            DstOffset::HalfHour => 2,
+            DstOffset::Daylight => 4,
+            DstOffset::DoubleDaylight => 8,
+            DstOffset::Unknown => 255,
+        }
+    }
+}
+
+/// Value of the Local Time Information characteristic (0x2A0F).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalTimeInformation {
+    /// Standard offset from UTC in 15 minute steps, `None` when unknown.
+    pub time_zone: Option<i8>,
+    pub dst_offset: DstOffset,
+}
+
+impl LocalTimeInformation {
+    /// Builds the value from the standard and daylight saving parts of a UTC offset.
+    pub fn from_offsets(
+        base_offset: chrono::Duration,
+        dst_offset: chrono::Duration,
+    ) -> Result<LocalTimeInformation, CodecError> {
+        let minutes = base_offset.num_minutes();
+        if minutes % 15 != 0 || !(-48 * 15..=56 * 15).contains(&minutes) {
+            return Err(out_of_range(
+                "time zone minutes",
+                minutes.unsigned_abs() as u32,
+            ));
+        }
+        Ok(LocalTimeInformation {
+            time_zone: Some((minutes / 15) as i8),
+            dst_offset: DstOffset::from_minutes(dst_offset.num_minutes())?,
+        })
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<LocalTimeInformation, CodecError> {
+        if bytes.len() != LOCAL_TIME_INFORMATION_LEN {
+            return Err(CodecError::Length {
+                expected: LOCAL_TIME_INFORMATION_LEN,
+                actual: bytes.len(),
+            });
+        }
+        let time_zone = match bytes[0] as i8 {
+            TIME_ZONE_UNKNOWN => None,
+            zone @ -48..=56 => Some(zone),
+            _ => return Err(out_of_range("time zone", bytes[0])),
+        };
+        Ok(LocalTimeInformation {
+            time_zone,
+            dst_offset: DstOffset::from_byte(bytes[1])?,
+        })
+    }
+
+    pub fn encode(&self) -> Result<[u8; LOCAL_TIME_INFORMATION_LEN], CodecError> {
+        let zone = match self.time_zone {
+            None => TIME_ZONE_UNKNOWN,
+            Some(zone @ -48..=56) => zone,
+            Some(zone) => return Err(out_of_range("time zone", zone as u8)),
+        };
+        Ok([zone as u8, self.dst_offset.to_byte()])
+    }
+}
+
+impl fmt::Display for LocalTimeInformation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.time_zone {
+            Some(zone) => {
+                let minutes = i32::from(zone) * 15;
+                let sign = if minutes < 0 { '-' } else { '+' };
+                let minutes = minutes.abs();
+                write!(f, "UTC{}{:02}:{:02}", sign, minutes / 60, minutes % 60)?;
+            }
+            None => write!(f, "UTC offset unknown")?,
+        }
+        match self.dst_offset.minutes() {
+            Some(0) => write!(f, ", standard time"),
+            Some(minutes) => write!(f, ", DST +{} min", minutes),
+            None => write!(f, ", DST unknown"),
+        }
+    }
+}
+
+/// Size in bytes of a Reference Time Information (0x2A14) value.
+pub const REFERENCE_TIME_INFORMATION_LEN: usize = 4;
+
+/// Where the time last written to the watch came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeSource {
+    Unknown,
+    NetworkTimeProtocol,
+    Gps,
+    RadioTimeSignal,
+    Manual,
+    AtomicClock,
+    CellularNetwork,
+    NotSynchronized,
+}
+
+impl TimeSource {
+    fn from_byte(byte: u8) -> Result<TimeSource, CodecError> {
+        match byte {
+            0 => Ok(TimeSource::Unknown),
+            1 => Ok(TimeSource::NetworkTimeProtocol),
+            2 => Ok(TimeSource::Gps),
+            3 => Ok(TimeSource::RadioTimeSignal),
+            4 => Ok(TimeSource::Manual),
+            5 => Ok(TimeSource::AtomicClock),
+            6 => Ok(TimeSource::CellularNetwork),
+            7 => Ok(TimeSource::NotSynchronized),
+            n => Err(out_of_range("time source", n)),
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            TimeSource::Unknown => 0,
+            TimeSource::NetworkTimeProtocol => 1,
+            TimeSource::Gps => 2,
+            TimeSource::RadioTimeSignal => 3,
+            TimeSource::Manual => 4,
+            TimeSource::AtomicClock => 5,
+            TimeSource::CellularNetwork => 6,
+            TimeSource::NotSynchronized => 7,
+        }
+    }
+}
+
+impl fmt::Display for TimeSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TimeSource::Unknown => "unknown",
+            TimeSource::NetworkTimeProtocol => "network (NTP)",
+            TimeSource::Gps => "GPS",
+            TimeSource::RadioTimeSignal => "radio time signal",
+            TimeSource::Manual => "manual",
+            TimeSource::AtomicClock => "atomic clock",
+            TimeSource::CellularNetwork => "cellular network",
+            TimeSource::NotSynchronized => "not synchronized",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Accuracy of the reference time, as carried by Reference Time Information.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeAccuracy {
+    /// Drift in 1/8 s steps, at most 253 (31.625 s).
+    Eighths(u8),
+    OutOfRange,
+    Unknown,
+}
+
+impl TimeAccuracy {
+    /// Rounds `accuracy` up to the next 1/8 s step.
+    pub fn from_duration(accuracy: std::time::Duration) -> TimeAccuracy {
+        let eighths = accuracy.as_micros().div_ceil(125_000);
+        match u8::try_from(eighths) {
+            Ok(eighths @ 0..=253) => TimeAccuracy::Eighths(eighths),
+            _ => TimeAccuracy::OutOfRange,
+        }
+    }
+}
+
+/// Value of the Reference Time Information characteristic (0x2A14).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReferenceTimeInformation {
+    pub source: TimeSource,
+    pub accuracy: TimeAccuracy,
+    /// Days since the last update, 255 meaning 255 or more.
+    pub days_since_update: u8,
+    /// Hours since the last update on top of `days_since_update`.
+    pub hours_since_update: u8,
+}
+
+impl ReferenceTimeInformation {
+    /// Describes a time that has just been set from `source`.
+    pub fn just_updated(source: TimeSource, accuracy: TimeAccuracy) -> ReferenceTimeInformation {
+        ReferenceTimeInformation {
+            source,
+            accuracy,
+            days_since_update: 0,
+            hours_since_update: 0,
+        }
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<ReferenceTimeInformation, CodecError> {
+        if bytes.len() != REFERENCE_TIME_INFORMATION_LEN {
+            return Err(CodecError::Length {
+                expected: REFERENCE_TIME_INFORMATION_LEN,
+                actual: bytes.len(),
+            });
+        }
+        let accuracy = match bytes[1] {
+            254 => TimeAccuracy::OutOfRange,
+            255 => TimeAccuracy::Unknown,
+            n => TimeAccuracy::Eighths(n),
+        };
+        let info = ReferenceTimeInformation {
+            source: TimeSource::from_byte(bytes[0])?,
+            accuracy,
+            days_since_update: bytes[2],
+            hours_since_update: bytes[3],
+        };
+        info.validate()?;
+        Ok(info)
+    }
+
+    pub fn encode(&self) -> Result<[u8; REFERENCE_TIME_INFORMATION_LEN], CodecError> {
+        self.validate()?;
+        let accuracy = match self.accuracy {
+            TimeAccuracy::Eighths(n) => n,
+            TimeAccuracy::OutOfRange => 254,
+            TimeAccuracy::Unknown => 255,
+        };
+        Ok([
+            self.source.to_byte(),
+            accuracy,
+            self.days_since_update,
+            self.hours_since_update,
+        ])
+    }
+
+    fn validate(&self) -> Result<(), CodecError> {
+        if let TimeAccuracy::Eighths(n @ 254..) = self.accuracy {
+            return Err(out_of_range("time accuracy", n));
+        }
+        // 255 hours is only allowed together with 255 days ("255 days or more").
+        let hours_ok = match self.days_since_update {
+            255 => self.hours_since_update <= 23 || self.hours_since_update == 255,
+            _ => self.hours_since_update <= 23,
+        };
+        if !hours_ok {
+            return Err(out_of_range("hours since update", self.hours_since_update));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ReferenceTimeInformation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "source {}, accuracy ", self.source)?;
+        match self.accuracy {
+            TimeAccuracy::Eighths(n) => write!(f, "{} ms", u32::from(n) * 125)?,
+            TimeAccuracy::OutOfRange => write!(f, "out of range")?,
+            TimeAccuracy::Unknown => write!(f, "unknown")?,
+        }
+        if self.days_since_update == 255 {
+            write!(f, ", updated 255 or more days ago")
+        } else {
+            write!(
+                f,
+                ", updated {} d {} h ago",
+                self.days_since_update, self.hours_since_update
+            )
+        }
+    }
+}
+
+/// Size in bytes of a Time with DST (0x2A11) value.
+pub const TIME_WITH_DST_LEN: usize = 8;
+
+/// Value of the Time with DST characteristic (0x2A11) of the Next DST Change Service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWithDst {
+    /// Local wall clock time of the next change, `None` when no change is known.
+    pub date_time: Option<NaiveDateTime>,
+    /// DST offset that applies after the change.
+    pub dst_offset: DstOffset,
+}
+
+impl TimeWithDst {
+    pub fn decode(bytes: &[u8]) -> Result<TimeWithDst, CodecError> {
+        if bytes.len() != TIME_WITH_DST_LEN {
+            return Err(CodecError::Length {
+                expected: TIME_WITH_DST_LEN,
+                actual: bytes.len(),
+            });
+        }
+        let date_time = if bytes[..7].iter().all(|b| *b == 0) {
+            None
+        } else {
+            let year = u16::from_le_bytes([bytes[0], bytes[1]]);
+            let (month, day) = (bytes[2], bytes[3]);
+            let date = NaiveDate::from_ymd_opt(year.into(), month.into(), day.into())
+                .ok_or(CodecError::InvalidDate { year, month, day })?;
+            let (hour, minute, second) = (bytes[4], bytes[5], bytes[6]);
+            if hour > 23 {
+                return Err(out_of_range("hour", hour));
+            }
+            if minute > 59 {
+                return Err(out_of_range("minute", minute));
+            }
+            if second > 59 {
+                return Err(out_of_range("second", second));
+            }
+            date.and_hms_opt(hour.into(), minute.into(), second.into())
+        };
+        Ok(TimeWithDst {
+            date_time,
+            dst_offset: DstOffset::from_byte(bytes[7])?,
+        })
+    }
+
+    pub fn encode(&self) -> Result<[u8; TIME_WITH_DST_LEN], CodecError> {
+        let mut bytes = [0u8; TIME_WITH_DST_LEN];
+        if let Some(date_time) = &self.date_time {
+            let year = u16::try_from(date_time.year())
+                .ok()
+                .filter(|year| (1582..=9999).contains(year))
+                .ok_or(out_of_range("year", date_time.year().unsigned_abs()))?;
+            bytes[..2].copy_from_slice(&year.to_le_bytes());
+            bytes[2] = date_time.month() as u8;
+            bytes[3] = date_time.day() as u8;
+            bytes[4] = date_time.hour() as u8;
+            bytes[5] = date_time.minute() as u8;
+            bytes[6] = date_time.second() as u8;
+        }
+        bytes[7] = self.dst_offset.to_byte();
+        Ok(bytes)
+    }
+}
+
+impl fmt::Display for TimeWithDst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.date_time {
+            Some(date_time) => write!(f, "next DST change at {}", date_time)?,
+            None => write!(f, "no DST change scheduled")?,
+        }
+        match self.dst_offset.minutes() {
+            Some(0) => write!(f, " to standard time"),
+            Some(minutes) => write!(f, " to DST +{} min", minutes),
+            None => write!(f, " to unknown DST"),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Decoding of the standard characteristic descriptors.
+
+use super::CodecError;
+use btleplug::api::bleuuid::uuid_from_u16;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use uuid::Uuid;
+
+pub const USER_DESCRIPTION_UUID: Uuid = uuid_from_u16(0x2901);
+pub const CLIENT_CONFIGURATION_UUID: Uuid = uuid_from_u16(0x2902);
+pub const PRESENTATION_FORMAT_UUID: Uuid = uuid_from_u16(0x2904);
+
+/// Size in bytes of a Characteristic Presentation Format (0x2904) value.
+pub const PRESENTATION_FORMAT_LEN: usize = 7;
+/// Size in bytes of a Client Characteristic Configuration (0x2902) value.
+pub const CLIENT_CONFIGURATION_LEN: usize = 2;
+
+/// Characteristic User Description (0x2901), free text chosen by the vendor.
+pub fn decode_user_description(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// Characteristic Presentation Format (0x2904): how to read a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresentationFormat {
+    /// Format of the value, e.g. `0x06` for uint16.
+    pub format: u8,
+    /// Power of ten the value is to be multiplied with.
+    pub exponent: i8,
+    /// Unit of the value, the 16-bit alias of its GATT unit UUID, e.g. `0x272F` for °C.
+    pub unit: u16,
+    pub namespace: u8,
+    pub description: u16,
+}
+
+impl PresentationFormat {
+    pub fn decode(bytes: &[u8]) -> Result<PresentationFormat, CodecError> {
+        if bytes.len() != PRESENTATION_FORMAT_LEN {
+            return Err(CodecError::Length {
+                expected: PRESENTATION_FORMAT_LEN,
+                actual: bytes.len(),
+            });
+        }
+        Ok(PresentationFormat {
+            format: bytes[0],
+            exponent: bytes[1] as i8,
+            unit: u16::from_le_bytes([bytes[2], bytes[3]]),
+            namespace: bytes[4],
+            description: u16::from_le_bytes([bytes[5], bytes[6]]),
+        })
+    }
+
+    /// Name of the format as in the specification, e.g. `uint16` or `utf8s`.
+    pub fn format_name(&self) -> Option<&'static str> {
+        let name = match self.format {
+            0x01 => "boolean",
+            0x02 => "2bit",
+            0x03 => "nibble",
+            0x04 => "uint8",
+            0x05 => "uint12",
+            0x06 => "uint16",
+            0x07 => "uint24",
+            0x08 => "uint32",
+            0x09 => "uint48",
+            0x0A => "uint64",
+            0x0B => "uint128",
+            0x0C => "sint8",
+            0x0D => "sint12",
+            0x0E => "sint16",
+            0x0F => "sint24",
+            0x10 => "sint32",
+            0x11 => "sint48",
+            0x12 => "sint64",
+            0x13 => "sint128",
+            0x14 => "float32",
+            0x15 => "float64",
+            0x16 => "SFLOAT",
+            0x17 => "FLOAT",
+            0x18 => "duint16",
+            0x19 => "utf8s",
+            0x1A => "utf16s",
+            0x1B => "struct",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+impl fmt::Display for PresentationFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.format_name() {
+            Some(name) => write!(f, "{}", name)?,
+            None => write!(f, "format {:#04x}", self.format)?,
+        }
+        write!(f, ", exponent {}, unit 0x{:04X}", self.exponent, self.unit)
+    }
+}
+
+/// Client Characteristic Configuration (0x2902): what the client subscribed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ClientConfiguration {
+    pub notifications: bool,
+    pub indications: bool,
+}
+
+impl ClientConfiguration {
+    const NOTIFICATIONS: u16 = 1 << 0;
+    const INDICATIONS: u16 = 1 << 1;
+
+    pub fn decode(bytes: &[u8]) -> Result<ClientConfiguration, CodecError> {
+        if bytes.len() != CLIENT_CONFIGURATION_LEN {
+            return Err(CodecError::Length {
+                expected: CLIENT_CONFIGURATION_LEN,
+                actual: bytes.len(),
+            });
+        }
+        let bits = u16::from_le_bytes([bytes[0], bytes[1]]);
+        if bits & !(Self::NOTIFICATIONS | Self::INDICATIONS) != 0 {
+            // Report the byte holding the offending bits.
+            let value = if bytes[1] != 0 { bytes[1] } else { bytes[0] };
+            return Err(CodecError::ReservedBits {
+                field: "client characteristic configuration",
+                value,
+            });
+        }
+        Ok(ClientConfiguration {
+            notifications: bits & Self::NOTIFICATIONS != 0,
+            indications: bits & Self::INDICATIONS != 0,
+        })
+    }
+}
+
+impl fmt::Display for ClientConfiguration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.notifications, self.indications) {
+            (false, false) => write!(f, "not subscribed"),
+            (true, false) => write!(f, "notifications on"),
+            (false, true) => write!(f, "indications on"),
+            (true, true) => write!(f, "notifications and indications on"),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! GATT identifiers and codecs for the characteristic values the tool understands.
+
+use btleplug::api::bleuuid::{uuid_from_u16, uuid_from_u32};
+use std::fmt;
+use uuid::Uuid;
+
+pub mod cts;
+pub mod descriptor;
+pub mod value;
+
+/// Current Time, the mandatory characteristic of the Current Time Service (0x1805).
+pub const CURRENT_TIME_UUID: Uuid = uuid_from_u16(0x2A2B);
+pub const LOCAL_TIME_INFORMATION_UUID: Uuid = uuid_from_u16(0x2A0F);
+pub const REFERENCE_TIME_INFORMATION_UUID: Uuid = uuid_from_u16(0x2A14);
+/// Time with DST, the characteristic of the Next DST Change Service (0x1807).
+pub const TIME_WITH_DST_UUID: Uuid = uuid_from_u16(0x2A11);
+/// Firmware Revision String of the Device Information Service (0x180A).
+pub const FIRMWARE_REVISION_UUID: Uuid = uuid_from_u16(0x2A26);
+/// The other strings of the Device Information Service.
+pub const MANUFACTURER_NAME_UUID: Uuid = uuid_from_u16(0x2A29);
+pub const MODEL_NUMBER_UUID: Uuid = uuid_from_u16(0x2A24);
+pub const SERIAL_NUMBER_UUID: Uuid = uuid_from_u16(0x2A25);
+pub const HARDWARE_REVISION_UUID: Uuid = uuid_from_u16(0x2A27);
+pub const SOFTWARE_REVISION_UUID: Uuid = uuid_from_u16(0x2A28);
+/// Battery Level of the Battery Service (0x180F), in percent.
+pub const BATTERY_LEVEL_UUID: Uuid = uuid_from_u16(0x2A19);
+
+/// Errors produced while encoding or decoding a characteristic value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodecError {
+    /// The value does not have the size mandated by the specification.
+    Length { expected: usize, actual: usize },
+    /// A field holds a value outside of its allowed range.
+    OutOfRange { field: &'static str, value: u32 },
+    /// Year, month and day are individually valid but do not form a date.
+    InvalidDate { year: u16, month: u8, day: u8 },
+    /// A field sets bits the specification reserves for future use.
+    ReservedBits { field: &'static str, value: u8 },
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Length { expected, actual } => {
+                write!(f, "expected {} bytes, got {}", expected, actual)
+            }
+            CodecError::OutOfRange { field, value } => {
+                write!(f, "{} out of range: {}", field, value)
+            }
+            CodecError::InvalidDate { year, month, day } => {
+                write!(f, "invalid date {:04}-{:02}-{:02}", year, month, day)
+            }
+            CodecError::ReservedBits { field, value } => {
+                write!(f, "{} sets reserved bits: {:#04x}", field, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+pub(crate) fn out_of_range(field: &'static str, value: impl Into<u32>) -> CodecError {
+    CodecError::OutOfRange {
+        field,
+        value: value.into(),
+    }
+}
+
+/// Parses a UUID given in full or as a 16 or 32-bit Bluetooth SIG alias such as `2A2B`.
+pub fn parse_uuid(text: &str) -> Result<Uuid, String> {
+    let digits = text.trim();
+    let digits = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+        .unwrap_or(digits);
+    let is_alias =
+        |bits: usize| digits.len() == bits / 4 && digits.chars().all(|c| c.is_ascii_hexdigit());
+    if is_alias(16) {
+        Ok(uuid_from_u16(
+            u16::from_str_radix(digits, 16).unwrap_or_default(),
+        ))
+    } else if is_alias(32) {
+        Ok(uuid_from_u32(
+            u32::from_str_radix(digits, 16).unwrap_or_default(),
+        ))
+    } else {
+        Uuid::parse_str(digits).map_err(|err| format!("invalid UUID {:?}: {}", text, err))
+    }
+}
+
+/// Parses bytes written in hex, e.g. `e8 07 0a`, `e8:07:0a` or `0xe8070a`.
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim();
+    let digits: String = text
+        .strip_prefix("0x")
+        .unwrap_or(text)
+        .chars()
+        .filter(|c| !matches!(c, ' ' | ':' | '-'))
+        .collect();
+    if let Some(c) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(format!("invalid hex {:?}: unexpected {:?}", text, c));
+    }
+    if digits.len() % 2 != 0 {
+        return Err(format!("invalid hex {:?}: odd number of digits", text));
+    }
+    Ok(digits
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| (hex_digit(pair[0]) << 4) | hex_digit(pair[1]))
+        .collect())
+}
+
+fn hex_digit(c: u8) -> u8 {
+    (c as char).to_digit(16).unwrap_or_default() as u8
+}
+
+/// Formats bytes as space separated hex, the way values are printed throughout the tool.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
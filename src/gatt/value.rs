@@ -0,0 +1,448 @@
+//! Generic decoding and encoding of values the tool has no dedicated codec for.
+//!
+//! The type of a value comes from its Characteristic Presentation Format
+//! descriptor when there is one, and from a hint such as `u16le` otherwise.
+//! Values to write are given as `<type>:<value>`, e.g. `u16le:2026` or
+//! `hex:e8 07`; plain hex is taken as is.
+
+use super::descriptor::PresentationFormat;
+use super::{parse_hex, CodecError};
+use std::fmt;
+use std::str::FromStr;
+
+/// Byte order of a multi-byte integer; GATT itself uses little endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+/// How to interpret the bytes of a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    U8,
+    U16(Endian),
+    U32(Endian),
+    I16(Endian),
+    Utf8,
+    /// IEEE-11073 16-bit SFLOAT.
+    SFloat,
+    /// IEEE-11073 32-bit FLOAT.
+    Float,
+    /// Raw bytes, shown as a hex dump with ASCII.
+    Hex,
+}
+
+const TYPE_NAMES: &[(ValueType, &str)] = &[
+    (ValueType::U8, "u8"),
+    (ValueType::U16(Endian::Little), "u16le"),
+    (ValueType::U16(Endian::Big), "u16be"),
+    (ValueType::U32(Endian::Little), "u32le"),
+    (ValueType::U32(Endian::Big), "u32be"),
+    (ValueType::I16(Endian::Little), "i16le"),
+    (ValueType::I16(Endian::Big), "i16be"),
+    (ValueType::Utf8, "utf8"),
+    (ValueType::SFloat, "sfloat"),
+    (ValueType::Float, "float"),
+    (ValueType::Hex, "hex"),
+];
+
+impl ValueType {
+    /// The type a Presentation Format describes, `None` for formats the tool
+    /// cannot decode.
+    pub fn from_presentation_format(format: &PresentationFormat) -> Option<ValueType> {
+        let value_type = match format.format {
+            0x04 => ValueType::U8,
+            0x06 => ValueType::U16(Endian::Little),
+            0x08 => ValueType::U32(Endian::Little),
+            0x0E => ValueType::I16(Endian::Little),
+            0x16 => ValueType::SFloat,
+            0x17 => ValueType::Float,
+            0x19 => ValueType::Utf8,
+            _ => return None,
+        };
+        Some(value_type)
+    }
+
+    /// Size in bytes of a value of this type, `None` if it varies.
+    fn len(self) -> Option<usize> {
+        match self {
+            ValueType::U8 => Some(1),
+            ValueType::U16(_) | ValueType::I16(_) | ValueType::SFloat => Some(2),
+            ValueType::U32(_) | ValueType::Float => Some(4),
+            ValueType::Utf8 | ValueType::Hex => None,
+        }
+    }
+
+    pub fn decode(self, bytes: &[u8]) -> Result<Value, CodecError> {
+        if let Some(expected) = self.len() {
+            if bytes.len() != expected {
+                return Err(CodecError::Length {
+                    expected,
+                    actual: bytes.len(),
+                });
+            }
+        }
+        let value = match self {
+            ValueType::U8 => Value::Integer(i64::from(bytes[0])),
+            ValueType::U16(endian) => {
+                let bytes = [bytes[0], bytes[1]];
+                Value::Integer(i64::from(match endian {
+                    Endian::Little => u16::from_le_bytes(bytes),
+                    Endian::Big => u16::from_be_bytes(bytes),
+                }))
+            }
+            ValueType::U32(endian) => {
+                let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+                Value::Integer(i64::from(match endian {
+                    Endian::Little => u32::from_le_bytes(bytes),
+                    Endian::Big => u32::from_be_bytes(bytes),
+                }))
+            }
+            ValueType::I16(endian) => {
+                let bytes = [bytes[0], bytes[1]];
+                Value::Integer(i64::from(match endian {
+                    Endian::Little => i16::from_le_bytes(bytes),
+                    Endian::Big => i16::from_be_bytes(bytes),
+                }))
+            }
+            ValueType::Utf8 => Value::Text(String::from_utf8_lossy(bytes).into_owned()),
+            ValueType::SFloat => SFLOAT.decode(u32::from(u16::from_le_bytes([bytes[0], bytes[1]]))),
+            ValueType::Float => {
+                FLOAT.decode(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            }
+            ValueType::Hex => Value::Bytes(bytes.to_vec()),
+        };
+        Ok(value)
+    }
+
+    /// Encodes a value written as text, e.g. `2026` for `u16le`.
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, String> {
+        let invalid = |err: &dyn fmt::Display| format!("invalid {} {:?}: {}", self, text, err);
+        let bytes = match self {
+            ValueType::U8 => vec![text.parse::<u8>().map_err(|e| invalid(&e))?],
+            ValueType::U16(endian) => {
+                let value = text.parse::<u16>().map_err(|e| invalid(&e))?;
+                match endian {
+                    Endian::Little => value.to_le_bytes().to_vec(),
+                    Endian::Big => value.to_be_bytes().to_vec(),
+                }
+            }
+            ValueType::U32(endian) => {
+                let value = text.parse::<u32>().map_err(|e| invalid(&e))?;
+                match endian {
+                    Endian::Little => value.to_le_bytes().to_vec(),
+                    Endian::Big => value.to_be_bytes().to_vec(),
+                }
+            }
+            ValueType::I16(endian) => {
+                let value = text.parse::<i16>().map_err(|e| invalid(&e))?;
+                match endian {
+                    Endian::Little => value.to_le_bytes().to_vec(),
+                    Endian::Big => value.to_be_bytes().to_vec(),
+                }
+            }
+            ValueType::Utf8 => text.as_bytes().to_vec(),
+            ValueType::SFloat => {
+                let raw = SFLOAT.encode(text).map_err(|e| invalid(&e))?;
+                (raw as u16).to_le_bytes().to_vec()
+            }
+            ValueType::Float => {
+                let raw = FLOAT.encode(text).map_err(|e| invalid(&e))?;
+                raw.to_le_bytes().to_vec()
+            }
+            ValueType::Hex => parse_hex(text)?,
+        };
+        Ok(bytes)
+    }
+}
+
+impl FromStr for ValueType {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<ValueType, String> {
+        TYPE_NAMES
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(text))
+            .map(|(value_type, _)| *value_type)
+            .ok_or_else(|| {
+                let names: Vec<&str> = TYPE_NAMES.iter().map(|(_, name)| *name).collect();
+                format!(
+                    "invalid type {:?}: expected one of {}",
+                    text,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = TYPE_NAMES
+            .iter()
+            .find(|(value_type, _)| value_type == self)
+            .map_or("?", |(_, name)| *name);
+        write!(f, "{}", name)
+    }
+}
+
+/// Parses a value to write given as `<type>:<value>`, or as plain hex.
+pub fn parse_typed(text: &str) -> Result<Vec<u8>, String> {
+    let typed = text
+        .split_once(':')
+        .and_then(|(name, value)| Some((name.parse::<ValueType>().ok()?, value)));
+    match typed {
+        Some((value_type, value)) => value_type.encode(value),
+        // Colons may also separate hex bytes, e.g. `e8:07`.
+        None => parse_hex(text),
+    }
+}
+
+/// A decoded value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Integer(i64),
+    /// `mantissa` × 10^`exponent`, kept exact.
+    Decimal {
+        mantissa: i64,
+        exponent: i32,
+    },
+    Text(String),
+    Bytes(Vec<u8>),
+    /// A reserved IEEE-11073 value such as NaN or +INFINITY.
+    Special(&'static str),
+}
+
+impl Value {
+    /// Multiplies a number by 10^`exponent`; other values are left alone.
+    pub fn scaled(self, exponent: i32) -> Value {
+        match self {
+            Value::Integer(mantissa) if exponent != 0 => Value::Decimal { mantissa, exponent },
+            Value::Decimal {
+                mantissa,
+                exponent: own,
+            } => Value::Decimal {
+                mantissa,
+                exponent: own + exponent,
+            },
+            value => value,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Decimal { mantissa: 0, .. } => write!(f, "0"),
+            Value::Decimal { mantissa, exponent } if *exponent >= 0 => {
+                write!(f, "{}{}", mantissa, "0".repeat(*exponent as usize))
+            }
+            Value::Decimal { mantissa, exponent } => {
+                let places = exponent.unsigned_abs() as usize;
+                let digits = format!("{:0>width$}", mantissa.unsigned_abs(), width = places + 1);
+                let (whole, fraction) = digits.split_at(digits.len() - places);
+                let sign = if *mantissa < 0 { "-" } else { "" };
+                write!(f, "{}{}.{}", sign, whole, fraction)
+            }
+            Value::Text(text) => write!(f, "{:?}", text),
+            Value::Bytes(bytes) => write!(f, "{}", hex_dump(bytes)),
+            Value::Special(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A value decoded according to its Presentation Format, with its unit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quantity {
+    pub value: Value,
+    /// Alias of the GATT unit UUID, e.g. `0x272F` for °C.
+    pub unit: u16,
+}
+
+impl Quantity {
+    /// Decodes `bytes` as `format` says, applying its exponent; formats the
+    /// tool cannot decode leave the value as bytes.
+    pub fn decode(format: &PresentationFormat, bytes: &[u8]) -> Result<Quantity, CodecError> {
+        let value = match ValueType::from_presentation_format(format) {
+            Some(value_type) => value_type.decode(bytes)?.scaled(i32::from(format.exponent)),
+            None => Value::Bytes(bytes.to_vec()),
+        };
+        Ok(Quantity {
+            value,
+            unit: format.unit,
+        })
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.value)?;
+        match unit_symbol(self.unit) {
+            Some("") => Ok(()),
+            Some(symbol) => write!(f, " {}", symbol),
+            None => write!(f, " (unit 0x{:04X})", self.unit),
+        }
+    }
+}
+
+/// Shows `bytes` as the `hint` type if given, as their Presentation Format
+/// says otherwise, and as a hex dump when neither is known.
+pub fn show(
+    bytes: &[u8],
+    hint: Option<ValueType>,
+    format: Option<&PresentationFormat>,
+) -> Result<String, CodecError> {
+    match (hint, format) {
+        (Some(value_type), _) => Ok(value_type.decode(bytes)?.to_string()),
+        (None, Some(format)) => Ok(Quantity::decode(format, bytes)?.to_string()),
+        (None, None) => Ok(hex_dump(bytes)),
+    }
+}
+
+/// Symbol of a GATT unit, empty for unitless values.
+pub fn unit_symbol(unit: u16) -> Option<&'static str> {
+    let symbol = match unit {
+        0x2700 => "",
+        0x2701 => "m",
+        0x2702 => "kg",
+        0x2703 => "s",
+        0x2704 => "A",
+        0x2705 => "K",
+        0x2724 => "Pa",
+        0x2725 => "J",
+        0x2726 => "W",
+        0x2728 => "V",
+        0x272F => "°C",
+        0x2760 => "min",
+        0x2761 => "h",
+        0x2762 => "d",
+        0x2763 => "°",
+        0x27AD => "%",
+        0x27AE => "‰",
+        0x27AF => "bpm",
+        _ => return None,
+    };
+    Some(symbol)
+}
+
+/// Layout of an IEEE-11073 floating point type: a signed mantissa in the
+/// low bits and a signed power of ten in the high bits.
+struct Ieee11073 {
+    mantissa_bits: u32,
+    exponent_bits: u32,
+    /// Reserved mantissas, which do not denote numbers.
+    specials: [(u32, &'static str); 5],
+}
+
+const SFLOAT: Ieee11073 = Ieee11073 {
+    mantissa_bits: 12,
+    exponent_bits: 4,
+    specials: [
+        (0x07FF, "NaN"),
+        (0x0800, "NRes"),
+        (0x07FE, "+INFINITY"),
+        (0x0802, "-INFINITY"),
+        (0x0801, "reserved"),
+    ],
+};
+
+const FLOAT: Ieee11073 = Ieee11073 {
+    mantissa_bits: 24,
+    exponent_bits: 8,
+    specials: [
+        (0x7F_FFFF, "NaN"),
+        (0x80_0000, "NRes"),
+        (0x7F_FFFE, "+INFINITY"),
+        (0x80_0002, "-INFINITY"),
+        (0x80_0001, "reserved"),
+    ],
+};
+
+impl Ieee11073 {
+    fn decode(&self, raw: u32) -> Value {
+        let mantissa = raw & ((1 << self.mantissa_bits) - 1);
+        if let Some((_, name)) = self.specials.iter().find(|(m, _)| *m == mantissa) {
+            return Value::Special(name);
+        }
+        let exponent = (raw >> self.mantissa_bits) & ((1 << self.exponent_bits) - 1);
+        Value::Decimal {
+            mantissa: sign_extend(mantissa, self.mantissa_bits),
+            exponent: sign_extend(exponent, self.exponent_bits) as i32,
+        }
+    }
+
+    /// Largest mantissa magnitude that does not collide with a reserved value.
+    fn max_mantissa(&self) -> u64 {
+        (1 << (self.mantissa_bits - 1)) - 3
+    }
+
+    fn encode(&self, text: &str) -> Result<u32, String> {
+        let (mut mantissa, mut exponent) = parse_decimal(text)?;
+        let max_exponent = (1i64 << (self.exponent_bits - 1)) - 1;
+        let min_exponent = -(1i64 << (self.exponent_bits - 1));
+        // Trade trailing zeros for a larger exponent until both fit.
+        while (mantissa.unsigned_abs() > self.max_mantissa() || exponent < min_exponent)
+            && mantissa % 10 == 0
+        {
+            mantissa /= 10;
+            exponent += 1;
+        }
+        while exponent > max_exponent && mantissa.unsigned_abs() <= self.max_mantissa() / 10 {
+            mantissa *= 10;
+            exponent -= 1;
+        }
+        if mantissa.unsigned_abs() > self.max_mantissa() {
+            return Err(String::from("too many significant digits"));
+        }
+        if !(min_exponent..=max_exponent).contains(&exponent) {
+            return Err(String::from("out of range"));
+        }
+        let mantissa_mask = (1u32 << self.mantissa_bits) - 1;
+        let exponent_mask = (1u32 << self.exponent_bits) - 1;
+        Ok(((exponent as u32 & exponent_mask) << self.mantissa_bits)
+            | (mantissa as u32 & mantissa_mask))
+    }
+}
+
+fn sign_extend(value: u32, bits: u32) -> i64 {
+    let value = i64::from(value);
+    if value >= 1 << (bits - 1) {
+        value - (1 << bits)
+    } else {
+        value
+    }
+}
+
+/// Splits a decimal such as `-36.60` into mantissa and power of ten, `(-3660, -2)`.
+fn parse_decimal(text: &str) -> Result<(i64, i64), String> {
+    let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+    if fraction.starts_with(['+', '-']) {
+        return Err(String::from("not a decimal number"));
+    }
+    let mantissa = format!("{}{}", whole, fraction)
+        .parse::<i64>()
+        .map_err(|_| String::from("not a decimal number"))?;
+    Ok((mantissa, -(fraction.len() as i64)))
+}
+
+/// Formats bytes as hex followed by their printable ASCII, 16 bytes per line.
+pub fn hex_dump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .map(|line| {
+            let ascii: String = line
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!("{:<47}  |{}|", super::hex(line), ascii)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,25 @@
+//! Scanning, connection management and time synchronisation for Bluetooth LE
+//! smartwatches.
+
+pub mod advertisement;
+pub mod assigned;
+pub mod backend;
+pub mod btsnoop;
+pub mod clock;
+pub mod config;
+pub mod connection;
+pub mod diff;
+pub mod discovery;
+pub mod drift;
+pub mod dump;
+pub mod error;
+pub mod gatt;
+pub mod monitor;
+pub mod precise;
+pub mod profile;
+pub mod schedule;
+pub mod selector;
+pub mod sync;
+pub mod zone;
+
+pub use error::{ErrorCategory, Result, SmartwatchError};
@@ -1,193 +1,1232 @@
 // See the "macOS permissions note" in README.md before running this on macOS
 // Big Sur or later.
 
-use btleplug::api::{
-    Central, CharPropFlags, Characteristic, Manager as _, Peripheral, ScanFilter, WriteType,
-};
-use btleplug::platform::Manager;
-use chrono::{Datelike, Timelike, Utc};
+use btleplug::api::{CharPropFlags, Characteristic, WriteType};
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use clap::{Parser, Subcommand};
 use futures::stream::StreamExt;
-use std::error::Error;
+use regex::Regex;
+use serde::Serialize;
+use smartwatch::advertisement::Advertisement;
+use smartwatch::assigned::{self, Registry};
+use smartwatch::backend::record::{Recorder, RecordingManager};
+use smartwatch::backend::replay::ReplayManager;
+use smartwatch::backend::sim::{SimManager, SimProfile};
+use smartwatch::backend::{Adapter, BackendSpec, Manager, Peripheral};
+use smartwatch::btsnoop;
+use smartwatch::clock::{self, Clock, OffsetClock, SystemClock};
+use smartwatch::config::{Config, Defaults, KnownDevice, OutputFormat, SyncPolicy};
+use smartwatch::diff::{self, LayoutDiff};
+use smartwatch::discovery::{self, Discovered, StopWhen};
+use smartwatch::drift::{self, DriftRecord, Phase};
+use smartwatch::dump;
+use smartwatch::gatt::cts::{
+    CurrentTime, LocalTimeInformation, ReferenceTimeInformation, TimeSource, TimeWithDst,
+};
+use smartwatch::gatt::value::{self, ValueType};
+use smartwatch::gatt::{
+    self, CodecError, BATTERY_LEVEL_UUID, CURRENT_TIME_UUID, FIRMWARE_REVISION_UUID,
+    HARDWARE_REVISION_UUID, LOCAL_TIME_INFORMATION_UUID, MANUFACTURER_NAME_UUID, MODEL_NUMBER_UUID,
+    REFERENCE_TIME_INFORMATION_UUID, SERIAL_NUMBER_UUID, SOFTWARE_REVISION_UUID,
+    TIME_WITH_DST_UUID,
+};
+use smartwatch::schedule::{self, SchedulePolicy};
+use smartwatch::selector::{self, DeviceSelector, MatchPolicy};
+use smartwatch::sync::{TimeSync, WriteOutcome};
+use smartwatch::{
+    connection, monitor, precise, profile, zone, ErrorCategory, Result, SmartwatchError,
+};
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use std::process::ExitCode;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time;
 use uuid::Uuid;
 
-/// Only devices whose name contains this string will be tried.
-const PERIPHERAL_NAME_MATCH_FILTER: &str = "Amazfit GTS 4 Mini";
-/// UUID of the characteristic for which we should subscribe to notifications.
-const NOTIFY_CHARACTERISTIC_UUID: Uuid = Uuid::from_u128(0x6e400002_b534_f393_67a9_e50e24dccA9e);
-const TIME_CHARACTERISTIC_UUID: Uuid = Uuid::from_u128(0x00002a2b_0000_1000_8000_00805f9b34fb); // 00002a2b-0000-1000-8000-00805f9b34fb
+/// Source reported to the watch in Reference Time Information.
+const REFERENCE_TIME_SOURCE: TimeSource = TimeSource::NetworkTimeProtocol;
+/// Default file the drift measurements are appended to.
+const DRIFT_HISTORY_FILE: &str = "drift_history.csv";
+
+/// Talks to Bluetooth LE smartwatches.
+#[derive(Parser)]
+struct Args {
+    /// File of extra UUID, company and appearance names, see the `assigned` module.
+    #[arg(long, global = true)]
+    names: Option<PathBuf>,
+    /// Bluetooth stack to use: `platform`, `sim:<profile.toml>` for a simulated
+    /// watch or `replay:<trace.jsonl>` to replay a recorded session.
+    #[arg(long, global = true, default_value_t = BackendSpec::Platform)]
+    backend: BackendSpec,
+    /// Record every Bluetooth operation to this trace file, see `--backend replay:<file>`.
+    #[arg(long, global = true)]
+    record: Option<PathBuf>,
+    /// Settings file of known devices and defaults [default:
+    /// `smartwatch/config.toml` in the XDG config directory, if present].
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    #[command(flatten)]
+    device: DeviceOptions,
+    #[command(subcommand)]
+    command: Command,
+}
+
+impl Args {
+    /// Fills in what the command line leaves to the settings.
+    fn configure(&mut self, config: Config) -> Result<()> {
+        let known = match &self.device.alias {
+            Some(alias) => Some(config.device(alias)?.clone()),
+            None => None,
+        };
+        if let Some(known) = known.as_ref().filter(|known| known.auth_key.is_some()) {
+            eprintln!(
+                "Warning: the auth key of {:?} is not used, no command authenticates yet",
+                known.alias
+            );
+        }
+        if config.defaults.output == OutputFormat::Json {
+            self.command.prefer_json();
+        }
+        if let Command::Schedule(options) = &mut self.command {
+            let configured = known.as_ref().map(|known| known.sync.clone());
+            options.policy = options.merge(&configured.unwrap_or_default())?;
+        }
+        self.device.known = known;
+        self.device.defaults = config.defaults;
+        Ok(())
+    }
+
+    /// IANA zone the watches are set to, `None` following the host.
+    fn time_zone(&self) -> Option<&str> {
+        self.device
+            .known
+            .as_ref()
+            .and_then(|known| known.time_zone.as_deref())
+            .or(self.device.defaults.time_zone.as_deref())
+    }
+}
+
+/// Which devices a command acts on.
+#[derive(clap::Args)]
+struct DeviceOptions {
+    /// Act on the device with this alias in the settings file.
+    #[arg(long = "device", global = true)]
+    alias: Option<String>,
+    /// Only act on the device with this MAC address or platform identifier.
+    #[arg(long, global = true)]
+    address: Option<String>,
+    /// Only act on devices whose name matches this regular expression.
+    #[arg(long, global = true)]
+    name: Option<Regex>,
+    /// Only act on devices advertising this service; may be repeated.
+    #[arg(long = "service", global = true, value_parser = gatt::parse_uuid)]
+    services: Vec<Uuid>,
+    /// Only act on devices with manufacturer data of this company, e.g. `0x0157`.
+    #[arg(long, global = true, value_parser = selector::parse_company_id)]
+    manufacturer: Option<u16>,
+    /// Only act on devices received at least this strongly, in dBm.
+    #[arg(long, global = true, allow_hyphen_values = true)]
+    min_rssi: Option<i16>,
+    /// Which of several matching devices to act on: first, strongest, all or ask.
+    #[arg(long, global = true, default_value_t = MatchPolicy::All)]
+    pick: MatchPolicy,
+    /// Give up scanning for devices after this many seconds [default: the
+    /// settings, else 10].
+    #[arg(long, global = true)]
+    scan_timeout: Option<u64>,
+    /// Entry of `--device` in the settings.
+    #[arg(skip)]
+    known: Option<KnownDevice>,
+    #[arg(skip)]
+    defaults: Defaults,
+}
+
+impl DeviceOptions {
+    /// Devices selected by the options, then the `--device` entry; with no
+    /// criteria given, the model of that entry if any, else every device
+    /// when `everything` is set and the known models otherwise.
+    fn selector(&self, everything: bool) -> DeviceSelector {
+        let known = self.known.as_ref();
+        let selector = DeviceSelector {
+            address: self.address.clone().or_else(|| known?.address.clone()),
+            name: self.name.clone().or_else(|| known?.name.clone()),
+            services: self.services.clone(),
+            manufacturer_id: self.manufacturer,
+            min_rssi: self.min_rssi,
+        };
+        match known.and_then(|known| known.profile) {
+            _ if !selector.is_empty() => selector,
+            Some(profile) => DeviceSelector::for_profiles(&[profile]),
+            None if everything => selector,
+            None => DeviceSelector::for_profiles(profile::KNOWN_PROFILES),
+        }
+    }
+
+    /// The scan can end at the first match when a single device is wanted and
+    /// no other could be preferred over it.
+    fn stop_when(&self) -> StopWhen {
+        if self.pick == MatchPolicy::First || self.selector(false).address.is_some() {
+            StopWhen::FirstMatch
+        } else {
+            StopWhen::Timeout
+        }
+    }
+
+    /// Scans `adapter` and keeps the selected devices, applying the pick policy.
+    async fn find<A: Adapter>(
+        &self,
+        adapter: &A,
+        everything: bool,
+    ) -> Result<Vec<Discovered<A::Peripheral>>> {
+        let scan = discovery::find(
+            adapter,
+            &self.selector(everything),
+            self.scan_timeout(),
+            self.stop_when(),
+        )
+        .await?;
+        eprintln!(
+            "Discovery took {} ms, {} matching device(s){}",
+            scan.elapsed.as_millis(),
+            scan.devices.len(),
+            if scan.stopped_early {
+                ""
+            } else {
+                " when the scan timed out"
+            }
+        );
+        Ok(selector::choose(scan.devices, self.pick, ask_device))
+    }
+
+    fn scan_timeout(&self) -> Duration {
+        self.scan_timeout
+            .map(Duration::from_secs)
+            .or(self.defaults.scan_timeout)
+            .unwrap_or(discovery::DEFAULT_SCAN_TIMEOUT)
+    }
+
+    /// The adapters to scan: the one picked in the settings, or all of them.
+    async fn adapters<M: Manager>(&self, manager: &M) -> Result<Vec<M::Adapter>> {
+        let mut adapters = discovery::adapters(manager).await?;
+        match self.defaults.adapter {
+            None => Ok(adapters),
+            Some(index) if index < adapters.len() => Ok(vec![adapters.swap_remove(index)]),
+            Some(index) => Err(SmartwatchError::AdapterNotFound {
+                index,
+                count: adapters.len(),
+            }),
+        }
+    }
+
+    /// Connects to `peripheral`, trying again as often as the settings allow.
+    async fn connect(&self, peripheral: &impl Peripheral) -> Result<bool> {
+        let retry = &self.defaults.retry;
+        let mut attempt = 1;
+        loop {
+            match connection::connect(peripheral).await {
+                Err(err) if attempt < retry.attempts => {
+                    eprintln!(
+                        "Connection attempt {} of {} failed, retrying in {} ms: {}",
+                        attempt,
+                        retry.attempts,
+                        retry.delay.as_millis(),
+                        err
+                    );
+                    time::sleep(retry.delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Lets the user pick one of `devices` on the terminal.
+fn ask_device<P: Peripheral>(devices: &[Discovered<P>]) -> Option<usize> {
+    for (i, device) in devices.iter().enumerate() {
+        eprintln!(
+            "[{}] {} {:?} {}",
+            i + 1,
+            connection::device_label(&device.peripheral),
+            device.local_name,
+            device
+                .properties
+                .rssi
+                .map_or(String::new(), |rssi| format!("{} dBm", rssi))
+        );
+    }
+    eprint!("Device to use (1-{}): ", devices.len());
+    io::stderr().flush().ok()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).ok()?;
+    answer.trim().parse::<usize>().ok()?.checked_sub(1)
+}
+
+#[derive(clap::Args)]
+struct SyncOptions {
+    /// Compensate the write latency and align the write to a second boundary.
+    #[arg(long)]
+    precise: bool,
+    /// Number of probe writes used to measure the latency in precise mode.
+    #[arg(long, default_value_t = precise::DEFAULT_PROBES)]
+    probes: usize,
+    /// Set the watch to this moment (RFC 3339) instead of the host time.
+    #[arg(long, value_parser = clock::parse_rfc3339)]
+    at: Option<DateTime<FixedOffset>>,
+    /// Set the watch this far ahead of the host time or `--at`, e.g. `5m` or `-90s`.
+    #[arg(long, value_parser = clock::parse_duration, allow_hyphen_values = true)]
+    offset: Option<chrono::Duration>,
+    /// Print the payloads that would be written without writing them.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+impl SyncOptions {
+    /// Resolves the options into time sync settings, starting the clock now.
+    fn time_sync(&self, zone: Tz) -> CliTimeSync {
+        let start = self
+            .at
+            .map_or_else(|| SystemClock.now(), |at| at.with_timezone(&Utc));
+        let offset = self.offset.unwrap_or_else(chrono::Duration::zero);
+        let clock = OffsetClock::starting_at(SystemClock, start + offset);
+        // A time picked by hand is no longer traceable to the host's reference.
+        let reference_source = if self.at.is_none() && self.offset.is_none() {
+            REFERENCE_TIME_SOURCE
+        } else {
+            TimeSource::Manual
+        };
+        TimeSync {
+            zone,
+            clock,
+            precise_probes: self.precise.then_some(self.probes),
+            dry_run: self.dry_run,
+            reference_source,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the devices in range with what they advertise.
+    Scan {
+        /// Print the devices as a JSON array instead of a table.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Stream the adverts of devices in range as JSON lines, with the bytes
+    /// that changed since the previous advert.
+    Monitor {
+        /// Stop after this many seconds instead of running until interrupted.
+        #[arg(long)]
+        seconds: Option<u64>,
+    },
+    /// Print the model, firmware and battery of the selected devices, with the
+    /// time characteristics they expose.
+    Info,
+    /// Dump the GATT services, characteristics and descriptors of the selected devices.
+    Services {
+        /// Also read the value of every readable characteristic.
+        #[arg(long)]
+        read_values: bool,
+        /// Print the dump as JSON instead of a tree.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Compare a saved GATT dump with another one, or with the selected devices.
+    Diff {
+        /// Dump of the old layout, as saved by `services --json`.
+        old: PathBuf,
+        /// Dump of the new layout; the selected devices are dumped when omitted.
+        new: Option<PathBuf>,
+        /// Print the changes as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Read a characteristic and print its value, decoded per its Presentation
+    /// Format if it has one and as a hex dump otherwise.
+    Read {
+        /// Characteristic UUID, in full or as a 16-bit alias such as `2A2B`.
+        #[arg(value_parser = gatt::parse_uuid)]
+        uuid: Uuid,
+        /// Decode the value as u8, u16le, u16be, u32le, u32be, i16le, i16be,
+        /// utf8, sfloat, float or hex.
+        #[arg(long = "as")]
+        as_type: Option<ValueType>,
+    },
+    /// Write a value to a characteristic.
+    Write {
+        /// Characteristic UUID, in full or as a 16-bit alias such as `2A2B`.
+        #[arg(value_parser = gatt::parse_uuid)]
+        uuid: Uuid,
+        /// Value to write as `<type>:<value>`, e.g. `u16le:2026` or `hex:e8 07`,
+        /// or plain hex such as `e807`.
+        #[arg(value_parser = value::parse_typed)]
+        // Spelled out so that clap takes one value holding bytes, not a list of
+        // values: it only recognizes `Vec<_>` written as such.
+        value: ::std::vec::Vec<u8>,
+        /// Wait for the device to acknowledge the write [default if supported].
+        #[arg(long, conflicts_with = "without_response")]
+        with_response: bool,
+        /// Write without waiting for an acknowledgement.
+        #[arg(long)]
+        without_response: bool,
+    },
+    /// Print the notifications of a characteristic as they arrive.
+    Subscribe {
+        /// Characteristic UUID, in full or as a 16-bit alias such as `2A2B`.
+        #[arg(value_parser = gatt::parse_uuid)]
+        uuid: Uuid,
+        /// Stop after this many notifications instead of running until interrupted.
+        #[arg(long)]
+        count: Option<usize>,
+        /// Decode the values as this type, see `read --as`.
+        #[arg(long = "as")]
+        as_type: Option<ValueType>,
+    },
+    /// Set the watch clock and the optional time services.
+    SyncTime(SyncOptions),
+    /// Compare the watch clock with the host clock before and after a time sync.
+    MeasureDrift {
+        /// CSV file the measurements are appended to.
+        #[arg(long, default_value = DRIFT_HISTORY_FILE)]
+        history: PathBuf,
+        #[command(flatten)]
+        sync: SyncOptions,
+    },
+    /// Keep syncing watches, each as rarely as its drift allows.
+    Schedule(ScheduleOptions),
+    /// Work with Android btsnoop HCI logs.
+    #[command(subcommand)]
+    Btsnoop(BtsnoopCommand),
+}
 
+#[derive(Subcommand)]
+enum BtsnoopCommand {
+    /// Summarise the ATT traffic of a log, with any Huami auth exchange, and
+    /// export it as a trace for `--backend replay:<file>`.
+    Import {
+        /// The log, `btsnoop_hci.log` as pulled from the phone.
+        log: PathBuf,
+        /// Trace file to write.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Print the summary as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+impl Command {
+    /// Makes JSON the output of the commands that can print it.
+    fn prefer_json(&mut self) {
+        match self {
+            Command::Scan { json }
+            | Command::Services { json, .. }
+            | Command::Diff { json, .. }
+            | Command::Btsnoop(BtsnoopCommand::Import { json, .. }) => *json = true,
+            _ => {}
+        }
+    }
+
+    /// Time sync settings of the commands that set the watch clock.
+    fn sync_options(&self) -> Option<&SyncOptions> {
+        match self {
+            Command::SyncTime(sync)
+            | Command::MeasureDrift { sync, .. }
+            | Command::Schedule(ScheduleOptions { sync, .. }) => Some(sync),
+            _ => None,
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct ScheduleOptions {
+    /// CSV file the measurements are appended to and drift is estimated from.
+    #[arg(long, default_value = DRIFT_HISTORY_FILE)]
+    history: PathBuf,
+    /// Largest clock error tolerated between two syncs, in milliseconds
+    /// [default: the settings of `--device`, else 500].
+    #[arg(long)]
+    error_bound_ms: Option<i64>,
+    /// Shortest interval between two syncs of a watch, in minutes [default:
+    /// the settings of `--device`, else 60].
+    #[arg(long)]
+    min_interval_minutes: Option<i64>,
+    /// Longest interval between two syncs of a watch, in hours [default: the
+    /// settings of `--device`, else 168].
+    #[arg(long)]
+    max_interval_hours: Option<i64>,
+    #[command(flatten)]
+    sync: SyncOptions,
+    /// Effective policy, set by [`Args::configure`].
+    #[arg(skip)]
+    policy: SchedulePolicy,
+}
+
+impl ScheduleOptions {
+    /// Fills what the options leave out from the settings of `--device`, then
+    /// from the built-in bounds, and checks the result.
+    fn merge(&self, configured: &SyncPolicy) -> Result<SchedulePolicy> {
+        let invalid = |message: String| SmartwatchError::InvalidOptions { message };
+        let option = |name: &str, value: Option<i64>, unit: fn(i64) -> Option<chrono::Duration>| {
+            value
+                .map(|value| {
+                    unit(value).ok_or_else(|| invalid(format!("{} {} is too large", name, value)))
+                })
+                .transpose()
+        };
+        let configured = configured.policy();
+        let policy = SchedulePolicy {
+            error_bound: option(
+                "--error-bound-ms",
+                self.error_bound_ms,
+                chrono::Duration::try_milliseconds,
+            )?
+            .unwrap_or(configured.error_bound),
+            min_interval: option(
+                "--min-interval-minutes",
+                self.min_interval_minutes,
+                chrono::Duration::try_minutes,
+            )?
+            .unwrap_or(configured.min_interval),
+            max_interval: option(
+                "--max-interval-hours",
+                self.max_interval_hours,
+                chrono::Duration::try_hours,
+            )?
+            .unwrap_or(configured.max_interval),
+        };
+        policy.check().map_err(invalid)?;
+        Ok(policy)
+    }
+}
+
+/// Time sync settings of the command line, ticking from the host clock.
+type CliTimeSync = TimeSync<OffsetClock<SystemClock>>;
+
+/// Exits with the code of the error category, see [`smartwatch::ErrorCategory`].
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
+async fn main() -> ExitCode {
     pretty_env_logger::init();
+    let args = Args::parse();
+    match cli(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+async fn cli(mut args: Args) -> Result<()> {
+    args.configure(Config::locate(args.config.as_deref())?)?;
+    let args = &args;
+    if let Some(path) = &args.names {
+        let mut registry = Registry::builtin();
+        registry.load(path)?;
+        if !assigned::install(registry) {
+            log::warn!(
+                "Names in {} not used, the registry was already in use",
+                path.display()
+            );
+        }
+    }
+    if let Command::Btsnoop(command) = &args.command {
+        return btsnoop(command);
+    }
+    match &args.backend {
+        BackendSpec::Platform => {
+            let manager = btleplug::platform::Manager::new()
+                .await
+                .map_err(|source| SmartwatchError::Adapter { source })?;
+            record(manager, args).await
+        }
+        BackendSpec::Sim(path) => {
+            let manager = SimManager::new(SimProfile::load(path)?, Arc::new(SystemClock))?;
+            record(manager, args).await
+        }
+        BackendSpec::Replay(path) => record(ReplayManager::load(path)?, args).await,
+    }
+}
+
+/// Runs a btsnoop command, which needs no adapter.
+fn btsnoop(command: &BtsnoopCommand) -> Result<()> {
+    let BtsnoopCommand::Import { log, output, json } = command;
+    let import = btsnoop::import(&btsnoop::read(log)?);
+    if *json {
+        println!("{}", to_json(&import.summary, true)?);
+    } else {
+        print!("{}", import.summary);
+    }
+    if let Some(path) = output {
+        import.save(path)?;
+        eprintln!(
+            "Wrote {} trace entries to {}",
+            import.entries.len(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Runs the command, recording the traffic with `manager` when asked to.
+async fn record<M: Manager>(manager: M, args: &Args) -> Result<()> {
+    let Some(path) = &args.record else {
+        return drive(&manager, args).await;
+    };
+    let recorder = Recorder::create(path, &SystemClock)?;
+    let result = drive(&RecordingManager::new(manager, recorder.clone()), args).await;
+    result.and(recorder.finish())
+}
+
+/// Runs the command through the adapters of `manager`.
+async fn drive<M: Manager>(manager: &M, args: &Args) -> Result<()> {
+    match &args.command {
+        Command::Scan { json } => return list_devices(manager, &args.device, *json).await,
+        Command::Monitor { seconds } => return monitor(manager, &args.device, *seconds).await,
+        Command::Diff {
+            old,
+            new: Some(new),
+            json,
+        } => return report_diff(&diff::compare(&dump::load(old)?, &dump::load(new)?), *json),
+        _ => {}
+    }
+    let sync = match args.command.sync_options() {
+        Some(options) => {
+            let zone = zone::resolve(args.time_zone())?;
+            println!("Using time zone {}", zone.name());
+            Some(options.time_sync(zone))
+        }
+        None => None,
+    };
+
+    loop {
+        let planned = match run(manager, args, sync.as_ref()).await {
+            Ok(planned) => planned,
+            // The radio may come back; the scheduler retries rather than
+            // leaving every watch unsynced.
+            Err(err)
+                if matches!(args.command, Command::Schedule(_))
+                    && matches!(err.category(), ErrorCategory::Adapter | ErrorCategory::Scan) =>
+            {
+                eprintln!("Error: {}", err);
+                Vec::new()
+            }
+            Err(err) => return Err(err),
+        };
+        let (Command::Schedule(options), Some(sync)) = (&args.command, &sync) else {
+            break;
+        };
+        let next = planned
+            .into_iter()
+            .min()
+            .unwrap_or_else(|| sync.clock.now() + options.policy.min_interval);
+        println!("Sleeping until {}", next.with_timezone(&sync.zone));
+        if let Ok(wait) = (next - sync.clock.now()).to_std() {
+            time::sleep(wait).await;
+        }
+    }
+    Ok(())
+}
+
+/// Scans every adapter and prints the devices seen, strongest signal first.
+async fn list_devices<M: Manager>(manager: &M, options: &DeviceOptions, json: bool) -> Result<()> {
+    let mut advertisements = Vec::new();
+    for adapter in options.adapters(manager).await?.iter() {
+        for device in options.find(adapter, true).await? {
+            advertisements.push(Advertisement::new(&device));
+        }
+    }
+    advertisements.sort_by_key(|a| std::cmp::Reverse(a.rssi.unwrap_or(i16::MIN)));
+    if json {
+        println!("{}", to_json(&advertisements, true)?);
+    } else {
+        print_advertisements(&advertisements);
+    }
+    Ok(())
+}
+
+/// Prints the adverts of the selected devices on every adapter, one JSON
+/// object per line, until interrupted or for `seconds` if given.
+async fn monitor<M: Manager>(
+    manager: &M,
+    options: &DeviceOptions,
+    seconds: Option<u64>,
+) -> Result<()> {
+    let selector = options.selector(true);
+    let adapters = options.adapters(manager).await?;
+    futures::future::try_join_all(adapters.iter().map(|adapter| {
+        let stop = async move {
+            match seconds {
+                Some(seconds) => time::sleep(Duration::from_secs(seconds)).await,
+                None => {
+                    // Without signal handling the scan could not be stopped cleanly.
+                    if tokio::signal::ctrl_c().await.is_err() {
+                        std::future::pending::<()>().await;
+                    }
+                }
+            }
+        };
+        monitor::watch(adapter, &selector, &SystemClock, stop, |update| {
+            println!("{}", to_json(&update, false)?);
+            Ok(())
+        })
+    }))
+    .await?;
+    Ok(())
+}
+
+/// Serializes `value` for printing.
+fn to_json(value: &impl Serialize, pretty: bool) -> Result<String> {
+    let text = if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    };
+    text.map_err(|err| SmartwatchError::Io {
+        path: PathBuf::from("stdout"),
+        source: err.into(),
+    })
+}
 
-    let manager = Manager::new().await?;
-    let adapter_list = manager.adapters().await?;
-    if adapter_list.is_empty() {
-        eprintln!("No Bluetooth adapters found");
+/// Prints one row per device, followed by its advertised services and data.
+fn print_advertisements(advertisements: &[Advertisement]) {
+    let dbm = |value: Option<i16>| value.map_or(String::from("-"), |v| v.to_string());
+    println!("{:<17}  {:>4}  {:>4}  NAME", "ADDRESS", "RSSI", "TX");
+    for advertisement in advertisements {
+        println!(
+            "{:<17}  {:>4}  {:>4}  {}",
+            advertisement.address,
+            dbm(advertisement.rssi),
+            dbm(advertisement.tx_power),
+            advertisement
+                .name
+                .as_deref()
+                .unwrap_or(discovery::UNKNOWN_NAME)
+        );
+        for service in &advertisement.services {
+            println!("    service {}", service);
+        }
+        for data in &advertisement.service_data {
+            println!("    service data {}", data);
+        }
+        for data in &advertisement.manufacturer_data {
+            println!("    manufacturer {}", data);
+        }
     }
+}
+
+/// Scans every adapter once and runs the command on the selected devices,
+/// returning the planned next syncs when running on a schedule.
+///
+/// On a schedule a watch that fails is recorded and retried later, and the
+/// others are still planned for.
+async fn run<M: Manager>(
+    manager: &M,
+    args: &Args,
+    sync: Option<&CliTimeSync>,
+) -> Result<Vec<DateTime<Utc>>> {
+    let scheduled = match (&args.command, sync) {
+        (Command::Schedule(options), Some(sync)) => Some((options, sync)),
+        _ => None,
+    };
+    let mut planned = Vec::new();
+    let mut failures = Vec::new();
+    let adapter_list = args.device.adapters(manager).await?;
 
     for adapter in adapter_list.iter() {
         println!("Starting scan...");
-        adapter
-            .start_scan(ScanFilter::default())
-            .await
-            .expect("Can't scan BLE adapter for connected devices...");
-        time::sleep(Duration::from_secs(2)).await;
-        let peripherals = adapter.peripherals().await?;
-
-        if peripherals.is_empty() {
-            eprintln!("->>> BLE peripheral devices were not found, sorry. Exiting...");
-        } else {
-            // All peripheral devices in range.
-            for peripheral in peripherals.iter() {
-                let properties = peripheral.properties().await?;
-                let is_connected = peripheral.is_connected().await?;
-                let local_name = properties
-                    .unwrap()
-                    .local_name
-                    .unwrap_or(String::from("(peripheral name unknown)"));
-                /* println!(
-                    "Peripheral {:?} is connected: {:?}",
-                    &local_name, is_connected
-                ); */
-                // Check if it's the peripheral we want.
-                if local_name.contains(PERIPHERAL_NAME_MATCH_FILTER) {
-                    println!("Found matching peripheral {:?}...", &local_name);
-                    if !is_connected {
-                        // Connect if we aren't already connected.
-                        if let Err(err) = peripheral.connect().await {
-                            eprintln!("Error connecting to peripheral, skipping: {}", err);
-                            continue;
-                        }
+        let watches = args.device.find(adapter, false).await?;
+        if watches.is_empty() {
+            eprintln!("->>> No matching BLE peripheral found, sorry. Exiting...");
+        }
+
+        for watch in watches.iter() {
+            let (peripheral, local_name) = (&watch.peripheral, &watch.local_name);
+            println!("Found matching peripheral {:?}...", local_name);
+            let is_connected = match args.device.connect(peripheral).await {
+                Ok(is_connected) => is_connected,
+                Err(err) => {
+                    eprintln!("Error connecting to peripheral, skipping: {}", err);
+                    if let Some((options, sync)) = scheduled {
+                        planned.push(reschedule(peripheral, options, sync)?);
                     }
-                    let is_connected = peripheral.is_connected().await?;
-                    println!(
-                        "Now connected ({:?}) to peripheral {:?}.",
-                        is_connected, &local_name
-                    );
-                    if is_connected {
-                        println!("Discover peripheral {:?} services...", local_name);
-                        peripheral.discover_services().await?;
-                        for characteristic in peripheral.characteristics() {
-                            //println!("Checking characteristic {:?}", characteristic);
-                            // Subscribe to notifications from the characteristic with the selected
-                            // UUID.
-                            if characteristic.properties.contains(CharPropFlags::READ)
-                                && characteristic.uuid == TIME_CHARACTERISTIC_UUID
-                            {
-                                println!("Reading characteristic {:?}", characteristic.uuid);
-                                let value = peripheral.read(&characteristic).await?;
-                                println!(
-                                    "Read value from {:?} [{:?}]: {:?}",
-                                    local_name, characteristic.uuid, value
-                                );
-                            }
-                            if characteristic.uuid == TIME_CHARACTERISTIC_UUID {
-                                set_current_time(peripheral, &characteristic).await?;
-
-                                /* println!("Write characteristic {:?}", characteristic.uuid);
-                                let value = 0x07e8; // 2022
-                                let value = peripheral
-                                    .write(
-                                        &characteristic,
-                                        &[
-                                            0, // Anno
-                                            0, // Anno
-                                            0, // Mese
-                                            0, // Giorno del mese
-                                            0, // Ora
-                                            0, // Minuti
-                                            0, // Secondi
-                                            0, //
-                                            0, //
-                                            0, //
-                                            1, //
-                                        ],
-                                        WriteType::WithResponse,
-                                    )
-                                    .await;
-                                println!("Value written: {:?}", value); */
-                            }
-                            if characteristic.properties.contains(CharPropFlags::READ)
-                                && characteristic.uuid == TIME_CHARACTERISTIC_UUID
-                            {
-                                println!("Reading characteristic {:?}", characteristic.uuid);
-                                let value = peripheral.read(&characteristic).await?;
-                                println!(
-                                    "Read value from {:?} [{:?}]: {:?}",
-                                    local_name, characteristic.uuid, value
-                                );
-                            }
-                            /* if characteristic.uuid == NOTIFY_CHARACTERISTIC_UUID
-                                && characteristic.properties.contains(CharPropFlags::NOTIFY)
-                            {
-                                println!("Subscribing to characteristic {:?}", characteristic.uuid);
-                                peripheral.subscribe(&characteristic).await?;
-                                // Print the first 4 notifications received.
-                                let mut notification_stream =
-                                    peripheral.notifications().await?.take(4);
-                                // Process while the BLE connection is not broken or stopped.
-                                while let Some(data) = notification_stream.next().await {
-                                    println!(
-                                        "Received data from {:?} [{:?}]: {:?}",
-                                        local_name, data.uuid, data.value
-                                    );
-                                }
-                            } */
-                        }
-                        println!("Disconnecting from peripheral {:?}...", local_name);
-                        peripheral.disconnect().await?;
+                    failures.push(err);
+                    continue;
+                }
+            };
+            println!(
+                "Now connected ({:?}) to peripheral {:?}.",
+                is_connected, local_name
+            );
+            if !is_connected {
+                continue;
+            }
+            let outcome = handle(peripheral, local_name, &args.command, sync).await;
+            println!("Disconnecting from peripheral {:?}...", local_name);
+            let disconnected = connection::disconnect(peripheral).await;
+            match outcome {
+                Ok(next) => planned.extend(next),
+                Err(err) => {
+                    eprintln!("Error on peripheral {:?}: {}", local_name, err);
+                    if let Some((options, sync)) = scheduled {
+                        planned.push(reschedule(peripheral, options, sync)?);
                     }
+                    failures.push(err);
                 }
             }
+            if let Err(err) = disconnected {
+                eprintln!("Error disconnecting from peripheral: {}", err);
+                failures.push(err);
+            }
+        }
+    }
+    // Other watches were still served, but the failure must not go unnoticed.
+    match failures.into_iter().next() {
+        Some(_) if scheduled.is_some() => Ok(planned),
+        Some(err) => Err(err),
+        None => Ok(planned),
+    }
+}
+
+/// Runs `command` on a connected device, returning its next planned sync if any.
+async fn handle(
+    peripheral: &impl Peripheral,
+    local_name: &str,
+    command: &Command,
+    sync: Option<&CliTimeSync>,
+) -> Result<Option<DateTime<Utc>>> {
+    match (command, sync) {
+        (Command::Info, _) => info(peripheral, local_name).await?,
+        (Command::Services { read_values, json }, _) => {
+            let dump = dump::capture(peripheral, local_name, *read_values, &SystemClock).await;
+            if *json {
+                println!("{}", to_json(&dump, true)?);
+            } else {
+                print!("{}", dump);
+            }
+        }
+        (Command::Diff { old, json, .. }, _) => {
+            let old = dump::load(old)?;
+            let new = dump::capture(peripheral, local_name, false, &SystemClock).await;
+            report_diff(&diff::compare(&old, &new), *json)?
+        }
+        (Command::Read { uuid, as_type }, _) => {
+            read(peripheral, local_name, *uuid, *as_type).await?
+        }
+        (
+            Command::Write {
+                uuid,
+                value,
+                with_response,
+                without_response,
+            },
+            _,
+        ) => {
+            let write_type = match (*with_response, *without_response) {
+                (true, _) => Some(WriteType::WithResponse),
+                (_, true) => Some(WriteType::WithoutResponse),
+                _ => None,
+            };
+            write(peripheral, local_name, *uuid, value, write_type).await?
+        }
+        (
+            Command::Subscribe {
+                uuid,
+                count,
+                as_type,
+            },
+            _,
+        ) => subscribe(peripheral, local_name, *uuid, *count, *as_type).await?,
+        (Command::SyncTime(_), Some(sync)) => {
+            let characteristic = connection::require_characteristic(peripheral, CURRENT_TIME_UUID)?;
+            sync_time(peripheral, &characteristic, local_name, sync).await?
+        }
+        (Command::MeasureDrift { history, .. }, Some(sync)) => {
+            let characteristic = connection::require_characteristic(peripheral, CURRENT_TIME_UUID)?;
+            measure_drift(peripheral, &characteristic, history, sync).await?
+        }
+        (Command::Schedule(options), Some(sync)) => {
+            let characteristic = connection::require_characteristic(peripheral, CURRENT_TIME_UUID)?;
+            let next = scheduled_sync(peripheral, &characteristic, options, sync).await?;
+            return Ok(Some(next));
         }
+        // Scanning needs no connection, and the time commands always come with a time sync.
+        _ => {}
     }
+    Ok(None)
+}
+
+/// Prints the changes between two GATT layouts, failing if there are any.
+fn report_diff(diff: &LayoutDiff, json: bool) -> Result<()> {
+    if json {
+        println!("{}", to_json(diff, true)?);
+    } else {
+        print!("{}", diff);
+    }
+    if diff.is_empty() {
+        Ok(())
+    } else {
+        Err(SmartwatchError::LayoutChanged {
+            device: diff.new.device.clone(),
+            changes: diff.changes.len(),
+        })
+    }
+}
+
+/// Reads a characteristic and prints its value as the `as_type` hint or its
+/// Presentation Format says.
+async fn read(
+    peripheral: &impl Peripheral,
+    local_name: &str,
+    uuid: Uuid,
+    as_type: Option<ValueType>,
+) -> Result<()> {
+    let characteristic = connection::require_characteristic(peripheral, uuid)?;
+    if !characteristic.properties.contains(CharPropFlags::READ) {
+        return Err(SmartwatchError::Unsupported {
+            device: connection::device_label(peripheral),
+            characteristic: uuid,
+            operation: "reading",
+        });
+    }
+    let format = match as_type {
+        Some(_) => None,
+        None => connection::read_presentation_format(peripheral, &characteristic).await?,
+    };
+    let value = connection::read(peripheral, &characteristic).await?;
+    let shown = value::show(&value, as_type, format.as_ref())
+        .map_err(|err| SmartwatchError::codec(&connection::device_label(peripheral), uuid, err))?;
+    // Long hex dumps start on a line of their own.
+    let separator = if shown.contains('\n') { "\n" } else { " " };
+    println!(
+        "{:?} {}:{}{}",
+        local_name,
+        assigned::describe(&uuid),
+        separator,
+        shown
+    );
+    Ok(())
+}
+
+/// Writes `value` to a characteristic, with a response when the device supports
+/// it unless `write_type` says otherwise.
+async fn write(
+    peripheral: &impl Peripheral,
+    local_name: &str,
+    uuid: Uuid,
+    value: &[u8],
+    write_type: Option<WriteType>,
+) -> Result<()> {
+    let characteristic = connection::require_characteristic(peripheral, uuid)?;
+    let write_type = write_type.unwrap_or(
+        if characteristic.properties.contains(CharPropFlags::WRITE) {
+            WriteType::WithResponse
+        } else {
+            WriteType::WithoutResponse
+        },
+    );
+    let (needed, operation) = match write_type {
+        WriteType::WithResponse => (CharPropFlags::WRITE, "writing with response"),
+        WriteType::WithoutResponse => (
+            CharPropFlags::WRITE_WITHOUT_RESPONSE,
+            "writing without response",
+        ),
+    };
+    if !characteristic.properties.contains(needed) {
+        return Err(SmartwatchError::Unsupported {
+            device: connection::device_label(peripheral),
+            characteristic: uuid,
+            operation,
+        });
+    }
+    connection::write(peripheral, &characteristic, value, write_type).await?;
+    println!(
+        "{:?} {}: wrote {}",
+        local_name,
+        assigned::describe(&uuid),
+        gatt::hex(value)
+    );
     Ok(())
 }
 
-async fn set_current_time(
+/// Prints the notifications of a characteristic, stopping after `count` of them
+/// if given, decoded like [`read`] does when a type is known.
+async fn subscribe(
+    peripheral: &impl Peripheral,
+    local_name: &str,
+    uuid: Uuid,
+    count: Option<usize>,
+    as_type: Option<ValueType>,
+) -> Result<()> {
+    let characteristic = connection::require_characteristic(peripheral, uuid)?;
+    if !characteristic
+        .properties
+        .intersects(CharPropFlags::NOTIFY | CharPropFlags::INDICATE)
+    {
+        return Err(SmartwatchError::Unsupported {
+            device: connection::device_label(peripheral),
+            characteristic: uuid,
+            operation: "notifications",
+        });
+    }
+    let format = match as_type {
+        Some(_) => None,
+        None => connection::read_presentation_format(peripheral, &characteristic).await?,
+    };
+    let mut notifications = pin!(connection::subscribe(peripheral, &characteristic).await?);
+    let name = assigned::describe(&uuid);
+    let mut received = 0;
+    while count.is_none_or(|count| received < count) {
+        let Some(notification) = notifications.next().await else {
+            break;
+        };
+        received += 1;
+        // Keep notifications on one line each unless asked to decode them.
+        let shown = if as_type.is_some() || format.is_some() {
+            value::show(&notification.value, as_type, format.as_ref())
+                .unwrap_or_else(|err| format!("{} ({})", gatt::hex(&notification.value), err))
+        } else {
+            gatt::hex(&notification.value)
+        };
+        println!(
+            "{} {:?} {}: {}",
+            SystemClock
+                .now()
+                .to_rfc3339_opts(SecondsFormat::Millis, true),
+            local_name,
+            name,
+            shown
+        );
+    }
+    connection::unsubscribe(peripheral, &characteristic).await
+}
+
+/// Reads the watch clock, sets it and the optional time services, then reads it back.
+async fn sync_time(
     peripheral: &impl Peripheral,
     characteristic: &Characteristic,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Get the current time
-    let now = Utc::now();
-    let year = now.year() as u16;
-    let month = now.month() as u8;
-    let day = now.day() as u8;
-    let hour = now.hour() as u8;
-    let minute = now.minute() as u8;
-    let second = now.second() as u8;
-    let day_of_week = now.weekday().num_days_from_sunday() as u8; // Sunday = 1, Monday = 2, ..., Saturday = 7
-    let fractions = 0u8; // Fractions of a second
-    let adjust_reason = 1u8; // No adjustment
-
-    // Construct the data to write
-    let data = [
-        (year & 0xFF) as u8,        // Year (LSB)
-        ((year >> 8) & 0xFF) as u8, // Year (MSB)
-        month,                      // Month
-        day,                        // Day
-        hour,                       // Hours
-        minute,                     // Minutes
-        second,                     // Seconds
-        day_of_week,                // Day of Week
-        fractions,                  // Fractions of a second
-        0,
-        adjust_reason, // Adjust Reason
+    local_name: &str,
+    sync: &CliTimeSync,
+) -> Result<()> {
+    if characteristic.properties.contains(CharPropFlags::READ) {
+        print_current_time(peripheral, characteristic, local_name).await?;
+    }
+    let mut failed = None;
+    for report in sync.sync(peripheral, characteristic).await? {
+        println!("{}", report);
+        if let WriteOutcome::Failed(err) = report.outcome {
+            failed.get_or_insert(err);
+        }
+    }
+    print_time_services(peripheral, local_name).await?;
+    if characteristic.properties.contains(CharPropFlags::READ) {
+        print_current_time(peripheral, characteristic, local_name).await?;
+    }
+    failed.map_or(Ok(()), Err)
+}
+
+/// Measures the clock offset around a time sync and records it in the drift history.
+async fn measure_drift(
+    peripheral: &impl Peripheral,
+    characteristic: &Characteristic,
+    history: &Path,
+    sync: &CliTimeSync,
+) -> Result<()> {
+    if !characteristic.properties.contains(CharPropFlags::READ) {
+        return Err(SmartwatchError::Unsupported {
+            device: connection::device_label(peripheral),
+            characteristic: characteristic.uuid,
+            operation: "reading, needed to measure drift",
+        });
+    }
+    let device = connection::device_label(peripheral);
+
+    let before = drift::measure(peripheral, characteristic, &sync.zone, &sync.clock).await?;
+    let (report, _) = sync.write_current_time(peripheral, characteristic).await?;
+    println!("{}", report);
+    let after = drift::measure(peripheral, characteristic, &sync.zone, &sync.clock).await?;
+
+    for (phase, measurement) in [(Phase::BeforeSync, &before), (Phase::AfterSync, &after)] {
+        println!(
+            "Offset {}: {:+} ms (watch {}, round trip {} ms)",
+            phase,
+            measurement.offset.num_milliseconds(),
+            measurement.watch_time,
+            measurement.round_trip.num_milliseconds()
+        );
+        if !sync.dry_run {
+            drift::append(history, &DriftRecord::new(&device, phase, measurement))?;
+        }
+    }
+    if sync.dry_run {
+        println!("Dry run, drift history left untouched");
+    }
+    match drift::drift_per_day(&drift::load(history)?, &device) {
+        Some(rate) => println!("{} drifts {:+.1} ms/day", device, rate),
+        None => println!("Not enough history yet to estimate drift of {}", device),
+    }
+    Ok(())
+}
+
+/// Syncs the watch if its planned sync is due and plans the next one.
+async fn scheduled_sync(
+    peripheral: &impl Peripheral,
+    characteristic: &Characteristic,
+    options: &ScheduleOptions,
+    sync: &CliTimeSync,
+) -> Result<DateTime<Utc>> {
+    let device = connection::device_label(peripheral);
+    let policy = &options.policy;
+    if let Some(plan) = schedule::next_sync(&drift::load(&options.history)?, &device, policy) {
+        if plan.at > sync.clock.now() {
+            println!(
+                "Sync of {} not due until {}",
+                device,
+                plan.at.with_timezone(&sync.zone)
+            );
+            return Ok(plan.at);
+        }
+    }
+
+    measure_drift(peripheral, characteristic, &options.history, sync).await?;
+    let plan =
+        schedule::next_sync(&drift::load(&options.history)?, &device, policy).ok_or_else(|| {
+            SmartwatchError::NoSyncRecorded {
+                device: device.clone(),
+            }
+        })?;
+    match plan.drift_per_day_ms {
+        Some(rate) => println!(
+            "Next sync of {} at {} (drift {:+.1} ms/day)",
+            device,
+            plan.at.with_timezone(&sync.zone),
+            rate
+        ),
+        None => println!(
+            "Next sync of {} at {} (drift not known yet)",
+            device,
+            plan.at.with_timezone(&sync.zone)
+        ),
+    }
+    Ok(plan.at)
+}
+
+/// Prints what the device tells about itself and which time characteristics it has.
+async fn info(peripheral: &impl Peripheral, local_name: &str) -> Result<()> {
+    let device = connection::device_label(peripheral);
+    let model = profile::KNOWN_PROFILES
+        .iter()
+        .find(|profile| profile.matches(local_name))
+        .map_or("unknown model", |profile| profile.model);
+    println!("{:?} {} ({})", local_name, device, model);
+    let strings = [
+        (MANUFACTURER_NAME_UUID, "manufacturer"),
+        (MODEL_NUMBER_UUID, "model number"),
+        (SERIAL_NUMBER_UUID, "serial number"),
+        (HARDWARE_REVISION_UUID, "hardware"),
+        (FIRMWARE_REVISION_UUID, "firmware"),
+        (SOFTWARE_REVISION_UUID, "software"),
     ];
+    for (uuid, name) in strings {
+        if let Some(value) = connection::read_if_supported(peripheral, uuid).await? {
+            println!("    {}: {}", name, String::from_utf8_lossy(&value));
+        }
+    }
+    if let Some(value) = connection::read_if_supported(peripheral, BATTERY_LEVEL_UUID).await? {
+        let [level] = value[..] else {
+            let err = CodecError::Length {
+                expected: 1,
+                actual: value.len(),
+            };
+            return Err(SmartwatchError::codec(&device, BATTERY_LEVEL_UUID, err));
+        };
+        println!("    battery: {} %", level);
+    }
+    let time_characteristics: Vec<Characteristic> = [
+        CURRENT_TIME_UUID,
+        LOCAL_TIME_INFORMATION_UUID,
+        REFERENCE_TIME_INFORMATION_UUID,
+        TIME_WITH_DST_UUID,
+    ]
+    .into_iter()
+    .filter_map(|uuid| connection::find_characteristic(peripheral, uuid))
+    .collect();
+    if time_characteristics.is_empty() {
+        println!("    time: no time characteristics");
+    }
+    for characteristic in &time_characteristics {
+        println!(
+            "    time: {} [{}]",
+            assigned::describe(&characteristic.uuid),
+            dump::property_names(characteristic.properties).join(", ")
+        );
+    }
+    Ok(())
+}
 
-    data.iter().for_each(|b| print!("{:02X} ", b));
+/// Records that the scheduled sync of a watch failed and plans to retry it
+/// after the minimum interval.
+fn reschedule(
+    peripheral: &impl Peripheral,
+    options: &ScheduleOptions,
+    sync: &CliTimeSync,
+) -> Result<DateTime<Utc>> {
+    let device = connection::device_label(peripheral);
+    let now = sync.clock.now();
+    if !sync.dry_run {
+        drift::append(&options.history, &DriftRecord::failure(&device, now))?;
+    }
+    let retry = now + options.policy.min_interval;
+    println!("Retrying {} at {}", device, retry.with_timezone(&sync.zone));
+    Ok(retry)
+}
+
+/// Reads the Current Time characteristic and prints it as a wall clock.
+async fn print_current_time(
+    peripheral: &impl Peripheral,
+    characteristic: &Characteristic,
+    local_name: &str,
+) -> Result<()> {
+    let value = connection::read(peripheral, characteristic).await?;
+    let time = CurrentTime::decode(&value).map_err(|err| {
+        SmartwatchError::codec(
+            &connection::device_label(peripheral),
+            characteristic.uuid,
+            err,
+        )
+    })?;
+    println!(
+        "{:?} says {} (adjust reason: {})",
+        local_name, time, time.adjust_reason
+    );
+    Ok(())
+}
 
-    // Write the data to the characteristic
-    match peripheral
-        .write(characteristic, &data, WriteType::WithResponse)
-        .await
+/// Reads and prints the optional time characteristics the watch exposes.
+async fn print_time_services(peripheral: &impl Peripheral, local_name: &str) -> Result<()> {
+    let device = connection::device_label(peripheral);
+    if let Some(value) =
+        connection::read_if_supported(peripheral, LOCAL_TIME_INFORMATION_UUID).await?
     {
-        Ok(_) => println!("Current time written successfully"),
-        Err(e) => eprintln!("Failed to write current time: {:?}", e),
+        let info = LocalTimeInformation::decode(&value)
+            .map_err(|err| SmartwatchError::codec(&device, LOCAL_TIME_INFORMATION_UUID, err))?;
+        println!("{:?} local time: {}", local_name, info);
+    }
+    if let Some(value) =
+        connection::read_if_supported(peripheral, REFERENCE_TIME_INFORMATION_UUID).await?
+    {
+        let info = ReferenceTimeInformation::decode(&value)
+            .map_err(|err| SmartwatchError::codec(&device, REFERENCE_TIME_INFORMATION_UUID, err))?;
+        println!("{:?} reference time: {}", local_name, info);
+    }
+    if let Some(value) = connection::read_if_supported(peripheral, TIME_WITH_DST_UUID).await? {
+        let change = TimeWithDst::decode(&value)
+            .map_err(|err| SmartwatchError::codec(&device, TIME_WITH_DST_UUID, err))?;
+        println!("{:?} {}", local_name, change);
     }
-
     Ok(())
 }
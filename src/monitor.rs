@@ -0,0 +1,212 @@
+//! Following the advertisements of devices in range without connecting.
+//!
+//! Some watches broadcast their battery level or step count in manufacturer or
+//! service data. Every advert is reported with the bytes that changed since the
+//! previous advert of the same device, so such counters stand out.
+
+use crate::advertisement::{ManufacturerData, ServiceData};
+use crate::assigned::NamedUuid;
+use crate::backend::{Adapter, AdapterEvent, PeripheralId};
+use crate::clock::Clock;
+use crate::discovery;
+use crate::error::{Result, SmartwatchError};
+use crate::selector::DeviceSelector;
+use chrono::{DateTime, Utc};
+use futures::stream::StreamExt;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::pin;
+use uuid::Uuid;
+
+/// Adapter event an update was derived from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdvertKind {
+    Discovered,
+    Updated,
+    ManufacturerData,
+    ServiceData,
+}
+
+/// One advert of a device, as streamed by the monitor.
+#[derive(Debug, Clone, Serialize)]
+pub struct AdvertUpdate {
+    pub at: DateTime<Utc>,
+    pub kind: AdvertKind,
+    pub address: String,
+    pub id: String,
+    pub name: Option<String>,
+    pub rssi: Option<i16>,
+    pub manufacturer_data: Vec<Tracked<ManufacturerData>>,
+    pub service_data: Vec<Tracked<ServiceData>>,
+}
+
+/// Advertised data along with how it changed.
+#[derive(Debug, Clone, Serialize)]
+pub struct Tracked<T> {
+    #[serde(flatten)]
+    pub data: T,
+    /// Bytes that differ from the previous advert, `None` when first seen.
+    pub changes: Option<Vec<ByteChange>>,
+}
+
+/// A byte that differs between two adverts; a missing side means the data
+/// got shorter or longer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ByteChange {
+    pub offset: usize,
+    pub old: Option<u8>,
+    pub new: Option<u8>,
+}
+
+/// Lists the bytes that differ between `old` and `new`.
+pub fn byte_changes(old: &[u8], new: &[u8]) -> Vec<ByteChange> {
+    (0..old.len().max(new.len()))
+        .filter_map(|offset| {
+            let (old, new) = (old.get(offset).copied(), new.get(offset).copied());
+            (old != new).then_some(ByteChange { offset, old, new })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum DataKey {
+    Manufacturer(u16),
+    Service(Uuid),
+}
+
+/// Remembers the last data advertised by each device.
+#[derive(Debug, Default)]
+pub struct Monitor {
+    last: HashMap<(String, DataKey), Vec<u8>>,
+}
+
+impl Monitor {
+    /// Turns an adapter event into an update, `None` for events that are not
+    /// adverts or come from devices `selector` does not match.
+    pub async fn observe<A: Adapter>(
+        &mut self,
+        adapter: &A,
+        selector: &DeviceSelector,
+        event: AdapterEvent<PeripheralId<A>>,
+        at: DateTime<Utc>,
+    ) -> Result<Option<AdvertUpdate>> {
+        let (kind, id, advertised) = match event {
+            AdapterEvent::DeviceDiscovered(id) => (AdvertKind::Discovered, id, None),
+            AdapterEvent::DeviceUpdated(id) => (AdvertKind::Updated, id, None),
+            AdapterEvent::ManufacturerData {
+                id,
+                manufacturer_data,
+            } => (
+                AdvertKind::ManufacturerData,
+                id,
+                Some((manufacturer_data, HashMap::new())),
+            ),
+            AdapterEvent::ServiceData { id, service_data } => (
+                AdvertKind::ServiceData,
+                id,
+                Some((HashMap::new(), service_data)),
+            ),
+            _ => return Ok(None),
+        };
+        let peripheral = adapter
+            .peripheral(&id)
+            .await
+            .map_err(|source| SmartwatchError::Scan { source })?;
+        let device = discovery::describe(peripheral).await?;
+        if !selector.matches(&device) {
+            return Ok(None);
+        }
+        // Discovery and update events carry nothing themselves, the data is
+        // whatever the device advertised last.
+        let (manufacturer_data, service_data) = advertised.unwrap_or_else(|| {
+            (
+                device.properties.manufacturer_data.clone(),
+                device.properties.service_data.clone(),
+            )
+        });
+        let device_id = id.to_string();
+
+        let mut manufacturer_data: Vec<Tracked<ManufacturerData>> = manufacturer_data
+            .into_iter()
+            .map(|(company_id, data)| Tracked {
+                changes: self.track(&device_id, DataKey::Manufacturer(company_id), &data),
+                data: ManufacturerData::new(company_id, data),
+            })
+            .collect();
+        manufacturer_data.sort_by_key(|m| m.data.company_id);
+        let mut service_data: Vec<Tracked<ServiceData>> = service_data
+            .into_iter()
+            .map(|(uuid, data)| Tracked {
+                changes: self.track(&device_id, DataKey::Service(uuid), &data),
+                data: ServiceData {
+                    service: NamedUuid::new(uuid),
+                    data,
+                },
+            })
+            .collect();
+        service_data.sort_by_key(|s| s.data.service.uuid);
+
+        Ok(Some(AdvertUpdate {
+            at,
+            kind,
+            address: device.properties.address.to_string(),
+            id: device_id,
+            name: device.properties.local_name,
+            rssi: device.properties.rssi,
+            manufacturer_data,
+            service_data,
+        }))
+    }
+
+    /// Records `data` and returns how it differs from what was recorded before.
+    fn track(&mut self, device_id: &str, key: DataKey, data: &[u8]) -> Option<Vec<ByteChange>> {
+        self.last
+            .insert((device_id.to_string(), key), data.to_vec())
+            .map(|previous| byte_changes(&previous, data))
+    }
+}
+
+/// Scans until `stop` completes, passing every advert of the devices
+/// `selector` matches to `emit` as it arrives.
+///
+/// Adverts of devices that cannot be looked up are skipped; only a failure
+/// of the scan itself or of `emit` ends the monitoring.
+pub async fn watch<A: Adapter>(
+    adapter: &A,
+    selector: &DeviceSelector,
+    clock: &dyn Clock,
+    stop: impl Future<Output = ()>,
+    mut emit: impl FnMut(AdvertUpdate) -> Result<()>,
+) -> Result<()> {
+    let scan_error = |source| SmartwatchError::Scan { source };
+    let mut events = adapter.events().await.map_err(scan_error)?;
+    adapter.start_scan().await.map_err(scan_error)?;
+
+    let mut stop = pin!(stop);
+    let mut monitor = Monitor::default();
+    let outcome = loop {
+        let event = tokio::select! {
+            _ = &mut stop => break Ok(()),
+            event = events.next() => match event {
+                Some(event) => event,
+                None => break Ok(()),
+            },
+        };
+        let update = match monitor.observe(adapter, selector, event, clock.now()).await {
+            Ok(update) => update,
+            // A device can vanish between its advert and the lookup, which
+            // must not end the monitoring of the others.
+            Err(err) => {
+                log::debug!("Skipping advert: {}", err);
+                continue;
+            }
+        };
+        if let Some(Err(err)) = update.map(&mut emit) {
+            break Err(err);
+        }
+    };
+    adapter.stop_scan().await.map_err(scan_error)?;
+    outcome
+}
@@ -0,0 +1,98 @@
+//! Latency-compensated setting of the watch clock.
+//!
+//! The watch applies a written time when the write request reaches it, about
+//! half a round trip after it was sent. Writing "now" therefore leaves the
+//! watch behind by that much; here the delay is measured first and the value
+//! written is the time at which the watch is expected to receive it.
+
+use crate::backend::Peripheral;
+use crate::clock::Clock;
+use crate::connection;
+use crate::error::Result;
+use crate::gatt::cts::{AdjustReason, CurrentTime};
+use btleplug::api::{Characteristic, WriteType};
+use chrono::{DateTime, DurationRound, Utc};
+use chrono_tz::Tz;
+use std::time::Duration;
+use tokio::time::{self, Instant};
+
+/// Number of probe writes used to estimate the write round trip.
+pub const DEFAULT_PROBES: usize = 5;
+
+/// Minimum time left to prepare the write before the targeted second boundary.
+const SCHEDULING_MARGIN: Duration = Duration::from_millis(50);
+
+/// Outcome of a precise time write.
+#[derive(Debug, Clone)]
+pub struct PreciseSync {
+    /// Median round trip of the probe writes.
+    pub round_trip: Duration,
+    /// Time of `clock` at which the final write was sent.
+    pub sent_at: DateTime<Utc>,
+    /// Value written, the expected time of `clock` at arrival.
+    pub written: CurrentTime,
+}
+
+/// Measures the write round trip with `probes` writes of the current time.
+pub async fn probe_round_trip(
+    peripheral: &impl Peripheral,
+    characteristic: &Characteristic,
+    zone: &Tz,
+    probes: usize,
+    clock: &dyn Clock,
+) -> Result<Duration> {
+    let mut round_trips = Vec::with_capacity(probes.max(1));
+    for _ in 0..probes.max(1) {
+        let data = encode_at(&clock.now(), zone)?.encode()?;
+        let started = Instant::now();
+        connection::write(peripheral, characteristic, &data, WriteType::WithResponse).await?;
+        round_trips.push(started.elapsed());
+    }
+    round_trips.sort();
+    Ok(round_trips[round_trips.len() / 2])
+}
+
+/// Sets the watch so that it lands within a fraction of the round trip of `clock`.
+///
+/// The final write is sent half a round trip before a whole second, so that
+/// watches ignoring Fractions256 are right too; the fractions still carry any
+/// scheduling delay.
+pub async fn set_current_time(
+    peripheral: &impl Peripheral,
+    characteristic: &Characteristic,
+    zone: &Tz,
+    probes: usize,
+    clock: &dyn Clock,
+) -> Result<PreciseSync> {
+    let round_trip = probe_round_trip(peripheral, characteristic, zone, probes, clock).await?;
+    let one_way = chrono::Duration::from_std(round_trip / 2)?;
+
+    let earliest = clock.now() + one_way + chrono::Duration::from_std(SCHEDULING_MARGIN)?;
+    let second = chrono::Duration::seconds(1);
+    let arrival = earliest.duration_trunc(second)? + second;
+    if let Ok(wait) = (arrival - one_way - clock.now()).to_std() {
+        time::sleep(wait).await;
+    }
+
+    let sent_at = clock.now();
+    let written = encode_at(&(sent_at + one_way), zone)?;
+    connection::write(
+        peripheral,
+        characteristic,
+        &written.encode()?,
+        WriteType::WithResponse,
+    )
+    .await?;
+    Ok(PreciseSync {
+        round_trip,
+        sent_at,
+        written,
+    })
+}
+
+fn encode_at(at: &DateTime<Utc>, zone: &Tz) -> Result<CurrentTime> {
+    Ok(CurrentTime::from_datetime(
+        &at.with_timezone(zone),
+        AdjustReason::MANUAL_TIME_UPDATE,
+    )?)
+}
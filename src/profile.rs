@@ -0,0 +1,28 @@
+//! Known smartwatch models and what the tool expects from them.
+
+use uuid::Uuid;
+
+/// Description of a smartwatch model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceProfile {
+    pub model: &'static str,
+    /// Only devices whose name contains this string are taken for this model.
+    pub name_pattern: &'static str,
+    /// UUID of the characteristic for which we should subscribe to notifications.
+    pub notify_characteristic: Option<Uuid>,
+}
+
+impl DeviceProfile {
+    pub fn matches(&self, local_name: &str) -> bool {
+        local_name.contains(self.name_pattern)
+    }
+}
+
+pub const AMAZFIT_GTS_4_MINI: DeviceProfile = DeviceProfile {
+    model: "Amazfit GTS 4 Mini",
+    name_pattern: "Amazfit GTS 4 Mini",
+    notify_characteristic: Some(Uuid::from_u128(0x6e400003_b5a3_f393_e0a9_e50e24dcca9e)),
+};
+
+/// Every model the tool knows about.
+pub const KNOWN_PROFILES: &[DeviceProfile] = &[AMAZFIT_GTS_4_MINI];
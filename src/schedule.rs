@@ -0,0 +1,114 @@
+//! Choice of the next time sync from the drift observed on each watch.
+
+use crate::drift::{self, DriftRecord, Phase};
+use chrono::{DateTime, Duration, Utc};
+
+/// Share of the error budget the schedule plans to use, leaving room for
+/// drift rate estimation errors.
+const SAFETY_FACTOR: f64 = 0.8;
+
+/// Longest any bound of a [`SchedulePolicy`] may be.
+pub const LONGEST_BOUND: Duration = Duration::days(365);
+
+/// Bounds within which the next sync is chosen.
+#[derive(Debug, Clone)]
+pub struct SchedulePolicy {
+    /// Largest offset the watch clock may reach before it is synced again.
+    pub error_bound: Duration,
+    /// Sync at least this far apart, however fast the watch drifts.
+    pub min_interval: Duration,
+    /// Sync at most this far apart, however slow the watch drifts.
+    pub max_interval: Duration,
+}
+
+impl Default for SchedulePolicy {
+    fn default() -> SchedulePolicy {
+        SchedulePolicy {
+            error_bound: Duration::milliseconds(500),
+            min_interval: Duration::hours(1),
+            max_interval: Duration::days(7),
+        }
+    }
+}
+
+impl SchedulePolicy {
+    /// Checks that every bound is positive and at most [`LONGEST_BOUND`], and
+    /// that the minimum interval does not exceed the maximum one.
+    pub fn check(&self) -> Result<(), String> {
+        let bounds = [
+            ("error bound", self.error_bound),
+            ("minimum interval", self.min_interval),
+            ("maximum interval", self.max_interval),
+        ];
+        for (name, bound) in bounds {
+            if bound <= Duration::zero() {
+                return Err(format!("{} must be positive", name));
+            }
+            if bound > LONGEST_BOUND {
+                return Err(format!(
+                    "{} must not exceed {} days",
+                    name,
+                    LONGEST_BOUND.num_days()
+                ));
+            }
+        }
+        if self.min_interval > self.max_interval {
+            return Err(format!(
+                "minimum interval of {} minutes exceeds the maximum interval of {} minutes",
+                self.min_interval.num_minutes(),
+                self.max_interval.num_minutes()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// When to sync a watch next, and why.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub at: DateTime<Utc>,
+    /// Estimated drift, `None` until the history allows an estimate.
+    pub drift_per_day_ms: Option<f64>,
+}
+
+/// Plans the next sync of `device` from its drift history.
+///
+/// Without a drift estimate yet the minimum interval is used, so that the
+/// history fills up quickly; afterwards the sync is planned for the moment
+/// the clock is expected to reach the error bound. A sync that failed since
+/// the last one is retried after the minimum interval. `policy` must pass
+/// [`SchedulePolicy::check`].
+pub fn next_sync(records: &[DriftRecord], device: &str, policy: &SchedulePolicy) -> Option<Plan> {
+    let last = |phase: Phase| {
+        records
+            .iter()
+            .filter(|r| r.device == device && r.phase == phase)
+            .max_by_key(|r| r.measured_at)
+    };
+    let drift_per_day_ms = drift::drift_per_day(records, device);
+    let last_sync = last(Phase::AfterSync);
+    if let Some(failure) = last(Phase::Failed)
+        .filter(|failure| last_sync.is_none_or(|sync| failure.measured_at > sync.measured_at))
+    {
+        return Some(Plan {
+            at: failure.measured_at + policy.min_interval,
+            drift_per_day_ms,
+        });
+    }
+    let last_sync = last_sync?;
+
+    let interval = match drift_per_day_ms {
+        Some(rate) if rate != 0.0 => {
+            let budget_ms = policy.error_bound.num_milliseconds() - last_sync.offset_ms.abs();
+            let days = budget_ms.max(0) as f64 / rate.abs() * SAFETY_FACTOR;
+            let millis = (days * 86_400_000.0).min(i64::MAX as f64) as i64;
+            Duration::milliseconds(millis).clamp(policy.min_interval, policy.max_interval)
+        }
+        Some(_) => policy.max_interval,
+        None => policy.min_interval,
+    };
+    Some(Plan {
+        at: last_sync.measured_at + interval,
+        drift_per_day_ms,
+    })
+}
@@ -0,0 +1,175 @@
+//! Picking the devices a command acts on among those seen during a scan.
+
+use crate::backend::Peripheral;
+use crate::discovery::Discovered;
+use crate::profile::DeviceProfile;
+use regex::Regex;
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Criteria a device must meet to be selected; unset criteria match anything.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceSelector {
+    /// MAC address or platform identifier, compared case-insensitively.
+    pub address: Option<String>,
+    /// Pattern the advertised local name must match.
+    pub name: Option<Regex>,
+    /// Services that must all be advertised.
+    pub services: Vec<Uuid>,
+    /// Company identifier the manufacturer data must be tagged with.
+    pub manufacturer_id: Option<u16>,
+    /// Weakest signal accepted, in dBm.
+    pub min_rssi: Option<i16>,
+}
+
+impl DeviceSelector {
+    /// Selects devices whose name contains the name pattern of any of `profiles`.
+    pub fn for_profiles(profiles: &[DeviceProfile]) -> DeviceSelector {
+        let pattern = profiles
+            .iter()
+            .map(|p| regex::escape(p.name_pattern))
+            .collect::<Vec<_>>()
+            .join("|");
+        DeviceSelector {
+            name: Regex::new(&pattern).ok(),
+            ..DeviceSelector::default()
+        }
+    }
+
+    /// Whether no criterion is set.
+    pub fn is_empty(&self) -> bool {
+        self.address.is_none()
+            && self.name.is_none()
+            && self.services.is_empty()
+            && self.manufacturer_id.is_none()
+            && self.min_rssi.is_none()
+    }
+
+    pub fn matches<P: Peripheral>(&self, device: &Discovered<P>) -> bool {
+        let properties = &device.properties;
+        if let Some(address) = &self.address {
+            let known_as = [
+                device.peripheral.address().to_string(),
+                device.peripheral.id().to_string(),
+            ];
+            if !known_as.iter().any(|id| id.eq_ignore_ascii_case(address)) {
+                return false;
+            }
+        }
+        if let Some(name) = &self.name {
+            if !properties
+                .local_name
+                .as_deref()
+                .is_some_and(|local_name| name.is_match(local_name))
+            {
+                return false;
+            }
+        }
+        if !self
+            .services
+            .iter()
+            .all(|s| properties.services.contains(s))
+        {
+            return false;
+        }
+        if let Some(id) = self.manufacturer_id {
+            if !properties.manufacturer_data.contains_key(&id) {
+                return false;
+            }
+        }
+        if let Some(min_rssi) = self.min_rssi {
+            // A device whose signal was not measured cannot be shown to be close enough.
+            if properties.rssi.is_none_or(|rssi| rssi < min_rssi) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// What to do when several devices match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchPolicy {
+    /// The first device seen.
+    First,
+    /// The device with the strongest signal.
+    Strongest,
+    /// Every matching device.
+    #[default]
+    All,
+    /// The device the user picks.
+    Ask,
+}
+
+impl FromStr for MatchPolicy {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<MatchPolicy, String> {
+        match text {
+            "first" => Ok(MatchPolicy::First),
+            "strongest" => Ok(MatchPolicy::Strongest),
+            "all" => Ok(MatchPolicy::All),
+            "ask" => Ok(MatchPolicy::Ask),
+            _ => Err(format!(
+                "invalid policy {:?}: expected first, strongest, all or ask",
+                text
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MatchPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MatchPolicy::First => "first",
+            MatchPolicy::Strongest => "strongest",
+            MatchPolicy::All => "all",
+            MatchPolicy::Ask => "ask",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Narrows the matching `devices` down according to `policy`.
+///
+/// With [`MatchPolicy::Ask`] and more than one device, `ask` is given the
+/// candidates and returns the index of the one picked, or `None` to pick none.
+pub fn choose<P>(
+    mut devices: Vec<Discovered<P>>,
+    policy: MatchPolicy,
+    ask: impl FnOnce(&[Discovered<P>]) -> Option<usize>,
+) -> Vec<Discovered<P>> {
+    match policy {
+        MatchPolicy::All => devices,
+        MatchPolicy::First => {
+            devices.truncate(1);
+            devices
+        }
+        MatchPolicy::Strongest => {
+            let strongest = devices
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, d)| d.properties.rssi.unwrap_or(i16::MIN))
+                .map(|(i, _)| i);
+            strongest
+                .map(|i| devices.swap_remove(i))
+                .into_iter()
+                .collect()
+        }
+        MatchPolicy::Ask if devices.len() > 1 => match ask(&devices) {
+            Some(i) if i < devices.len() => vec![devices.swap_remove(i)],
+            _ => Vec::new(),
+        },
+        MatchPolicy::Ask => devices,
+    }
+}
+
+/// Parses a Bluetooth SIG company identifier, in decimal or as `0x0157`.
+pub fn parse_company_id(text: &str) -> Result<u16, String> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(digits) => u16::from_str_radix(digits, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|err| format!("invalid company identifier {:?}: {}", text, err))
+}
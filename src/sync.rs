@@ -0,0 +1,256 @@
+//! Setting the watch clock and the optional time services around it.
+
+use crate::backend::Peripheral;
+use crate::clock::Clock;
+use crate::connection::{self, find_characteristic};
+use crate::error::{Result, SmartwatchError};
+use crate::gatt::cts::{
+    AdjustReason, CurrentTime, ReferenceTimeInformation, TimeAccuracy, TimeSource,
+};
+use crate::gatt::{
+    self, CodecError, LOCAL_TIME_INFORMATION_UUID, REFERENCE_TIME_INFORMATION_UUID,
+    TIME_WITH_DST_UUID,
+};
+use crate::{precise, zone};
+use btleplug::api::{CharPropFlags, Characteristic, WriteType};
+use chrono_tz::Tz;
+use std::fmt;
+use std::time::Duration;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+/// What happened to a value meant for the watch.
+#[derive(Debug)]
+pub enum WriteOutcome {
+    Written {
+        round_trip: Duration,
+    },
+    DryRun,
+    NotExposed,
+    NotWritable,
+    /// An optional value could not be computed, encoded or written; the sync
+    /// carried on.
+    Failed(SmartwatchError),
+}
+
+/// A value meant for the watch, with its meaning and fate.
+#[derive(Debug)]
+pub struct WriteReport {
+    pub name: &'static str,
+    /// Empty when the value could not be encoded.
+    pub payload: Vec<u8>,
+    pub meaning: String,
+    pub outcome: WriteOutcome,
+}
+
+impl fmt::Display for WriteReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.payload.is_empty() {
+            write!(f, "{} ({}): ", gatt::hex(&self.payload), self.meaning)?;
+        }
+        match &self.outcome {
+            WriteOutcome::Written { round_trip } => {
+                write!(f, "{} written in {} ms", self.name, round_trip.as_millis())
+            }
+            WriteOutcome::DryRun => write!(f, "dry run, {} not written", self.name),
+            WriteOutcome::NotExposed => write!(f, "watch does not expose {}", self.name),
+            WriteOutcome::NotWritable => write!(f, "watch does not allow writing {}", self.name),
+            WriteOutcome::Failed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Settings of a time sync.
+#[derive(Debug, Clone)]
+pub struct TimeSync<C> {
+    /// Time zone the watch shows.
+    pub zone: Tz,
+    /// Clock the watch is set to.
+    pub clock: C,
+    /// Number of latency probes for a precise sync, `None` for a plain write.
+    pub precise_probes: Option<usize>,
+    /// Encode everything but write nothing.
+    pub dry_run: bool,
+    /// Source reported in Reference Time Information.
+    pub reference_source: TimeSource,
+}
+
+impl<C: Clock> TimeSync<C> {
+    /// Writes the current time of `clock`, sampled once right before the write.
+    ///
+    /// Unlike the optional time services, a failure here fails the sync.
+    pub async fn set_current_time(
+        &self,
+        peripheral: &impl Peripheral,
+        characteristic: &Characteristic,
+    ) -> Result<WriteReport> {
+        let now = self.clock.now().with_timezone(&self.zone);
+        let time = CurrentTime::from_datetime(&now, AdjustReason::MANUAL_TIME_UPDATE)?;
+        let payload = time.encode()?.to_vec();
+        let outcome = if self.dry_run {
+            WriteOutcome::DryRun
+        } else {
+            write(peripheral, characteristic, &payload).await?
+        };
+        Ok(WriteReport {
+            name: "current time",
+            payload,
+            meaning: time.to_string(),
+            outcome,
+        })
+    }
+
+    /// Sets the watch clock, precisely if configured so.
+    ///
+    /// Returns the report along with how far off the written time may be.
+    pub async fn write_current_time(
+        &self,
+        peripheral: &impl Peripheral,
+        characteristic: &Characteristic,
+    ) -> Result<(WriteReport, Duration)> {
+        let probes = match self.precise_probes {
+            Some(probes) if !self.dry_run => probes,
+            _ => {
+                let report = self.set_current_time(peripheral, characteristic).await?;
+                let uncertainty = match report.outcome {
+                    WriteOutcome::Written { round_trip } => round_trip,
+                    _ => Duration::ZERO,
+                };
+                return Ok((report, uncertainty));
+            }
+        };
+        let sync =
+            precise::set_current_time(peripheral, characteristic, &self.zone, probes, &self.clock)
+                .await?;
+        let report = WriteReport {
+            name: "current time",
+            payload: sync.written.encode()?.to_vec(),
+            meaning: format!(
+                "{}, sent at {}",
+                sync.written,
+                sync.sent_at
+                    .with_timezone(&self.zone)
+                    .format("%H:%M:%S%.3f")
+            ),
+            outcome: WriteOutcome::Written {
+                round_trip: sync.round_trip,
+            },
+        };
+        Ok((report, sync.round_trip / 2))
+    }
+
+    /// Writes the time zone and DST offset, if the watch exposes Local Time Information.
+    pub async fn set_local_time_information(&self, peripheral: &impl Peripheral) -> WriteReport {
+        let value = zone::local_time_information(&self.zone, &self.clock.now())
+            .and_then(|info| Ok((info.encode()?.to_vec(), info.to_string())));
+        self.write_if_supported(
+            peripheral,
+            LOCAL_TIME_INFORMATION_UUID,
+            "local time information",
+            value,
+        )
+        .await
+    }
+
+    /// Tells the watch where its time came from and how accurate it is.
+    pub async fn set_reference_time_information(
+        &self,
+        peripheral: &impl Peripheral,
+        uncertainty: Duration,
+    ) -> WriteReport {
+        let info = ReferenceTimeInformation::just_updated(
+            self.reference_source,
+            TimeAccuracy::from_duration(uncertainty),
+        );
+        let value = info
+            .encode()
+            .map(|payload| (payload.to_vec(), info.to_string()));
+        self.write_if_supported(
+            peripheral,
+            REFERENCE_TIME_INFORMATION_UUID,
+            "reference time information",
+            value,
+        )
+        .await
+    }
+
+    /// Announces the next DST transition through the Next DST Change Service.
+    pub async fn set_next_dst_change(&self, peripheral: &impl Peripheral) -> WriteReport {
+        let change = zone::time_with_dst(&self.zone, &self.clock.now());
+        let value = change
+            .encode()
+            .map(|payload| (payload.to_vec(), change.to_string()));
+        self.write_if_supported(peripheral, TIME_WITH_DST_UUID, "time with DST", value)
+            .await
+    }
+
+    /// Sets the clock, then every optional time service the watch exposes.
+    ///
+    /// Only a failure to set the clock fails the sync; the optional services
+    /// report theirs as [`WriteOutcome::Failed`].
+    pub async fn sync(
+        &self,
+        peripheral: &impl Peripheral,
+        characteristic: &Characteristic,
+    ) -> Result<Vec<WriteReport>> {
+        let (current_time, uncertainty) =
+            self.write_current_time(peripheral, characteristic).await?;
+        Ok(vec![
+            current_time,
+            self.set_local_time_information(peripheral).await,
+            self.set_reference_time_information(peripheral, uncertainty)
+                .await,
+            self.set_next_dst_change(peripheral).await,
+        ])
+    }
+
+    /// Writes the encoded payload and meaning of `value` to an optional
+    /// characteristic, skipping it when the watch does not expose it or does
+    /// not allow writing it.
+    async fn write_if_supported(
+        &self,
+        peripheral: &impl Peripheral,
+        uuid: Uuid,
+        name: &'static str,
+        value: std::result::Result<(Vec<u8>, String), CodecError>,
+    ) -> WriteReport {
+        let (payload, meaning) = match value {
+            Ok(value) => value,
+            Err(err) => {
+                let device = connection::device_label(peripheral);
+                return WriteReport {
+                    name,
+                    payload: Vec::new(),
+                    meaning: String::new(),
+                    outcome: WriteOutcome::Failed(SmartwatchError::codec(&device, uuid, err)),
+                };
+            }
+        };
+        let outcome = match find_characteristic(peripheral, uuid) {
+            None => WriteOutcome::NotExposed,
+            Some(c) if !c.properties.contains(CharPropFlags::WRITE) => WriteOutcome::NotWritable,
+            Some(_) if self.dry_run => WriteOutcome::DryRun,
+            Some(c) => write(peripheral, &c, &payload)
+                .await
+                .unwrap_or_else(WriteOutcome::Failed),
+        };
+        WriteReport {
+            name,
+            payload,
+            meaning,
+            outcome,
+        }
+    }
+}
+
+async fn write(
+    peripheral: &impl Peripheral,
+    characteristic: &Characteristic,
+    payload: &[u8],
+) -> Result<WriteOutcome> {
+    let started = Instant::now();
+    connection::write(peripheral, characteristic, payload, WriteType::WithResponse).await?;
+    Ok(WriteOutcome::Written {
+        round_trip: started.elapsed(),
+    })
+}
@@ -0,0 +1,111 @@
+//! Resolution of the time zone the watch clock is set to.
+
+use crate::gatt::cts::{DstOffset, LocalTimeInformation, TimeWithDst};
+use crate::gatt::CodecError;
+use chrono::{DateTime, Duration, NaiveDateTime, Offset, Timelike, Utc};
+use chrono_tz::{OffsetComponents, Tz};
+use std::fmt;
+
+/// Errors produced while picking the time zone to use.
+#[derive(Debug)]
+pub enum ZoneError {
+    /// The configured name is not in the IANA time zone database.
+    Unknown(String),
+    /// The host time zone could not be determined.
+    Host(iana_time_zone::GetTimezoneError),
+}
+
+impl fmt::Display for ZoneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZoneError::Unknown(name) => write!(f, "unknown IANA time zone {:?}", name),
+            ZoneError::Host(err) => write!(f, "cannot determine host time zone: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ZoneError {}
+
+/// Returns the IANA zone called `name`, or the host zone when `name` is `None`.
+pub fn resolve(name: Option<&str>) -> Result<Tz, ZoneError> {
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => iana_time_zone::get_timezone().map_err(ZoneError::Host)?,
+    };
+    name.parse().map_err(|_| ZoneError::Unknown(name))
+}
+
+/// Local Time Information describing `zone` at the instant `at`.
+///
+/// A DST offset the characteristic cannot express, such as the negative one
+/// of Europe/Dublin in winter, is reported as unknown and folded into the time
+/// zone, so that the watch still adds up to the right wall clock.
+pub fn local_time_information(
+    zone: &Tz,
+    at: &DateTime<Utc>,
+) -> Result<LocalTimeInformation, CodecError> {
+    let offset = *at.with_timezone(zone).offset();
+    let (base, dst) = (offset.base_utc_offset(), offset.dst_offset());
+    match dst_offset(dst) {
+        DstOffset::Unknown => Ok(LocalTimeInformation {
+            dst_offset: DstOffset::Unknown,
+            ..LocalTimeInformation::from_offsets(base + dst, Duration::zero())?
+        }),
+        _ => LocalTimeInformation::from_offsets(base, dst),
+    }
+}
+
+/// The DST offset as the time characteristics express it, unknown if they cannot.
+fn dst_offset(offset: Duration) -> DstOffset {
+    DstOffset::from_minutes(offset.num_minutes()).unwrap_or(DstOffset::Unknown)
+}
+
+/// How far ahead the zone database is searched for the next DST change.
+const DST_SEARCH_HORIZON_DAYS: i64 = 400;
+
+/// Finds the first instant after `after` at which the DST offset of `zone` changes.
+///
+/// Returns the instant together with the local time just before the change.
+pub fn next_dst_change(zone: &Tz, after: &DateTime<Utc>) -> Option<(DateTime<Utc>, NaiveDateTime)> {
+    let dst_at = |instant: &DateTime<Utc>| instant.with_timezone(zone).offset().dst_offset();
+    let current = dst_at(after);
+
+    // Transitions are months apart, so hourly steps cannot jump over one.
+    let step = Duration::hours(1);
+    let mut low = *after;
+    let mut high = low + step;
+    while dst_at(&high) == current {
+        if high - *after > Duration::days(DST_SEARCH_HORIZON_DAYS) {
+            return None;
+        }
+        low = high;
+        high = low + step;
+    }
+    while high - low > Duration::seconds(1) {
+        let middle = low + (high - low) / 2;
+        if dst_at(&middle) == current {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    let change = high.with_nanosecond(0)?;
+    let offset_before = low.with_timezone(zone).offset().fix();
+    Some((change, change.with_timezone(&offset_before).naive_local()))
+}
+
+/// Time with DST announcing the next DST change of `zone` after `at`.
+///
+/// The DST offset is unknown when the characteristic cannot express it.
+pub fn time_with_dst(zone: &Tz, at: &DateTime<Utc>) -> TimeWithDst {
+    match next_dst_change(zone, at) {
+        Some((change, local)) => TimeWithDst {
+            date_time: Some(local),
+            dst_offset: dst_offset(change.with_timezone(zone).offset().dst_offset()),
+        },
+        None => TimeWithDst {
+            date_time: None,
+            dst_offset: dst_offset(at.with_timezone(zone).offset().dst_offset()),
+        },
+    }
+}
@@ -0,0 +1,85 @@
+use btleplug::api::bleuuid::uuid_from_u16;
+use smartwatch::assigned::{Registry, UuidKind};
+use smartwatch::{ErrorCategory, SmartwatchError};
+use std::fs;
+use uuid::Uuid;
+
+const CHUNKED_TRANSFER: Uuid = Uuid::from_u128(0x00000020_0000_3512_2118_0009af100700);
+
+/// Loads `text` as a names file on top of the built-in registry.
+fn load(name: &str, text: &str) -> Result<Registry, SmartwatchError> {
+    let path = std::env::temp_dir().join(format!("smartwatch-{}-{}.txt", name, std::process::id()));
+    fs::write(&path, text).unwrap();
+    let mut registry = Registry::builtin();
+    let result = registry.load(&path);
+    fs::remove_file(&path).unwrap();
+    result.map(|()| registry)
+}
+
+#[test]
+fn loads_every_kind_of_name() {
+    let registry = load(
+        "names",
+        "# kind identifier name\n\
+         \n\
+         service FEE7 Tencent\n\
+         characteristic 00000020-0000-3512-2118-0009af100700 Huami Chunked Transfer\n\
+         descriptor 0x2999 Test Descriptor\n\
+         company 0x0157 Zepp Health\n\
+         appearance 0x00C2 Smartwatch\n",
+    )
+    .unwrap();
+    assert_eq!(registry.uuid_name(&uuid_from_u16(0xFEE7)), Some("Tencent"));
+    assert_eq!(
+        registry.uuid_kind(&uuid_from_u16(0xFEE7)),
+        Some(UuidKind::Service)
+    );
+    assert_eq!(
+        registry.uuid_name(&CHUNKED_TRANSFER),
+        Some("Huami Chunked Transfer")
+    );
+    assert_eq!(
+        registry.uuid_kind(&uuid_from_u16(0x2999)),
+        Some(UuidKind::Descriptor)
+    );
+    assert_eq!(registry.company_name(0x0157), Some("Zepp Health"));
+    assert_eq!(registry.appearance_name(0x00C2), Some("Smartwatch"));
+}
+
+#[test]
+fn later_names_replace_earlier_ones() {
+    let registry = load(
+        "duplicates",
+        "service 180D Pulse\n\
+         characteristic 2A2B Clock\n\
+         characteristic 2A2B Watch Clock\n",
+    )
+    .unwrap();
+    assert_eq!(registry.uuid_name(&uuid_from_u16(0x180D)), Some("Pulse"));
+    assert_eq!(
+        registry.uuid_name(&uuid_from_u16(0x2A2B)),
+        Some("Watch Clock")
+    );
+    // Names not in the file stay as built in.
+    assert_eq!(
+        registry.uuid_name(&uuid_from_u16(0x2A0F)),
+        Some("Local Time Information")
+    );
+}
+
+#[test]
+fn rejects_malformed_lines_with_their_number() {
+    let cases = [
+        ("uuid", "# names\nservice 1234-5678 Broken\n", "line 2"),
+        ("uuid-digits", "characteristic 2A2G Broken\n", "line 1"),
+        ("kind", "\nwidget 2A2B Clock\n", "unknown kind"),
+        ("name", "service 180D\n", "no name given"),
+        ("company", "company 0x10000 Too Big\n", "company"),
+    ];
+    for (name, text, expected) in cases {
+        let err = load(name, text).unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Io, "{}", name);
+        let message = err.to_string();
+        assert!(message.contains(expected), "{}: {}", name, message);
+    }
+}
@@ -0,0 +1,321 @@
+mod common;
+
+use chrono::{TimeZone, Utc};
+use common::{watch_address, CURRENT_TIME_VALUE};
+use futures::stream::StreamExt;
+use regex::Regex;
+use smartwatch::backend::replay::ReplayManager;
+use smartwatch::backend::trace::{Bytes, Outcome, Record};
+use smartwatch::backend::Peripheral;
+use smartwatch::btsnoop::{self, AuthExchange, HUAMI_AUTH_UUID};
+use smartwatch::connection;
+use smartwatch::discovery::{self, StopWhen};
+use smartwatch::gatt::CURRENT_TIME_UUID;
+use smartwatch::selector::DeviceSelector;
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+const HANDLE: u16 = 0x0040;
+const KEY: [u8; 16] = *b"0123456789abcdef";
+const CHALLENGE: [u8; 16] = [0xA5; 16];
+const ENCRYPTED: [u8; 16] = [0x5A; 16];
+
+/// A btsnoop log in the H4 format Android writes.
+struct Log {
+    bytes: Vec<u8>,
+    /// Timestamp of the next packet, in btsnoop microseconds.
+    at: i64,
+}
+
+impl Log {
+    fn new() -> Log {
+        let mut bytes = b"btsnoop\0".to_vec();
+        bytes.extend_from_slice(&1u32.to_be_bytes());
+        bytes.extend_from_slice(&1002u32.to_be_bytes());
+        let started = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+        Log {
+            bytes,
+            at: started.timestamp_micros() + 0x00E0_3AB4_4A67_6000,
+        }
+    }
+
+    /// Logs a packet 10 ms after the previous one.
+    fn packet(&mut self, received: bool, h4: u8, data: &[u8]) -> &mut Log {
+        self.at += 10_000;
+        let length = (data.len() as u32 + 1).to_be_bytes();
+        let flags: u32 = u32::from(received) | if h4 == 0x01 || h4 == 0x04 { 2 } else { 0 };
+        self.bytes.extend_from_slice(&length);
+        self.bytes.extend_from_slice(&length);
+        self.bytes.extend_from_slice(&flags.to_be_bytes());
+        self.bytes.extend_from_slice(&0u32.to_be_bytes());
+        self.bytes.extend_from_slice(&self.at.to_be_bytes());
+        self.bytes.push(h4);
+        self.bytes.extend_from_slice(data);
+        self
+    }
+
+    fn pause(&mut self, millis: i64) -> &mut Log {
+        self.at += millis * 1000;
+        self
+    }
+
+    fn command(&mut self, opcode: u16, params: &[u8]) -> &mut Log {
+        let mut data = opcode.to_le_bytes().to_vec();
+        data.push(params.len() as u8);
+        data.extend_from_slice(params);
+        self.packet(false, 0x01, &data)
+    }
+
+    fn event(&mut self, code: u8, params: &[u8]) -> &mut Log {
+        let mut data = vec![code, params.len() as u8];
+        data.extend_from_slice(params);
+        self.packet(true, 0x04, &data)
+    }
+
+    /// Logs an ATT PDU as an L2CAP frame split into ACL packets of `fragment` bytes.
+    fn att_in(&mut self, received: bool, pdu: &[u8], fragment: usize) -> &mut Log {
+        let mut frame = (pdu.len() as u16).to_le_bytes().to_vec();
+        frame.extend_from_slice(&0x0004u16.to_le_bytes());
+        frame.extend_from_slice(pdu);
+        for (index, part) in frame.chunks(fragment).enumerate() {
+            let boundary: u16 = if index == 0 { 0x2000 } else { 0x1000 };
+            let mut data = (HANDLE | boundary).to_le_bytes().to_vec();
+            data.extend_from_slice(&(part.len() as u16).to_le_bytes());
+            data.extend_from_slice(part);
+            self.packet(received, 0x02, &data);
+        }
+        self
+    }
+
+    /// The phone sends `pdu`.
+    fn sent(&mut self, pdu: &[u8]) -> &mut Log {
+        self.att_in(false, pdu, usize::MAX)
+    }
+
+    /// The watch sends `pdu`.
+    fn received(&mut self, pdu: &[u8]) -> &mut Log {
+        self.att_in(true, pdu, usize::MAX)
+    }
+}
+
+fn concat(parts: &[&[u8]]) -> Vec<u8> {
+    parts.concat()
+}
+
+/// The watch address as HCI sends it, least significant byte first.
+fn address() -> [u8; 6] {
+    let mut address = watch_address().into_inner();
+    address.reverse();
+    address
+}
+
+/// LE Connection Complete of the watch on [`HANDLE`].
+fn connected() -> Vec<u8> {
+    let mut event = vec![0x01, 0x00, 0x40, 0x00, 0x00, 0x00];
+    event.extend_from_slice(&address());
+    event.extend_from_slice(&[0x18, 0x00, 0x00, 0x00, 0xF4, 0x01, 0x00]);
+    event
+}
+
+/// The official app finding the watch, discovering its services, reading and
+/// following its Current Time and authenticating on the Huami Auth characteristic.
+fn session() -> Vec<u8> {
+    let name = b"Amazfit GTS 4 Mini";
+    let mut advert = vec![0x02, 0x01, 0x06, name.len() as u8 + 1, 0x09];
+    advert.extend_from_slice(name);
+    advert.extend_from_slice(&[0x05, 0xFF, 0x57, 0x01, 0x02, 0x00]);
+    let mut report = vec![0x02, 1, 0x00, 0x00];
+    report.extend_from_slice(&address());
+    report.push(advert.len() as u8);
+    report.extend_from_slice(&advert);
+    report.push(-60i8 as u8);
+    let auth_uuid = {
+        let mut bytes = HUAMI_AUTH_UUID.into_bytes();
+        bytes.reverse();
+        bytes
+    };
+    let mut later = CURRENT_TIME_VALUE;
+    later[6] = 1;
+
+    let mut log = Log::new();
+    log.command(0x200C, &[0x01, 0x00])
+        .event(0x3E, &report)
+        .command(0x200C, &[0x00, 0x00])
+        .command(0x200D, &[0; 25])
+        .event(0x3E, &connected())
+        // Services: Current Time at 1-5, Huami at 6-10.
+        .sent(&[0x10, 0x01, 0x00, 0xFF, 0xFF, 0x00, 0x28])
+        .received(&[
+            0x11, 6, 0x01, 0x00, 0x05, 0x00, 0x05, 0x18, 0x06, 0x00, 0x0A, 0x00, 0xE1, 0xFE,
+        ])
+        .sent(&[0x10, 0x0B, 0x00, 0xFF, 0xFF, 0x00, 0x28])
+        .received(&[0x01, 0x10, 0x0B, 0x00, 0x0A])
+        // Characteristics: Current Time at 3, Huami Auth at 8.
+        .sent(&[0x08, 0x01, 0x00, 0xFF, 0xFF, 0x03, 0x28])
+        .received(&[0x09, 7, 0x02, 0x00, 0x1A, 0x03, 0x00, 0x2B, 0x2A])
+        .sent(&[0x08, 0x04, 0x00, 0xFF, 0xFF, 0x03, 0x28])
+        .received(&concat(&[
+            &[0x09, 21, 0x07, 0x00, 0x14, 0x08, 0x00],
+            &auth_uuid,
+        ]))
+        .sent(&[0x08, 0x09, 0x00, 0xFF, 0xFF, 0x03, 0x28])
+        .received(&[0x01, 0x08, 0x09, 0x00, 0x0A])
+        // Their Client Characteristic Configuration descriptors.
+        .sent(&[0x04, 0x04, 0x00, 0x05, 0x00])
+        .received(&[0x05, 0x01, 0x04, 0x00, 0x02, 0x29, 0x05, 0x00, 0x03, 0x28])
+        .sent(&[0x04, 0x09, 0x00, 0x0A, 0x00])
+        .received(&[0x05, 0x01, 0x09, 0x00, 0x02, 0x29])
+        // Current Time, read then notified.
+        .sent(&[0x0A, 0x03, 0x00])
+        .received(&concat(&[&[0x0B], &CURRENT_TIME_VALUE]))
+        .sent(&[0x12, 0x04, 0x00, 0x01, 0x00])
+        .received(&[0x13])
+        .pause(500)
+        .att_in(true, &concat(&[&[0x1B, 0x03, 0x00], &later]), 6)
+        // Authentication.
+        .sent(&[0x12, 0x09, 0x00, 0x01, 0x00])
+        .received(&[0x13])
+        .sent(&concat(&[&[0x52, 0x08, 0x00, 0x01, 0x00], &KEY]))
+        .received(&[0x1B, 0x08, 0x00, 0x10, 0x01, 0x01])
+        .sent(&[0x52, 0x08, 0x00, 0x02, 0x00])
+        .received(&concat(&[
+            &[0x1B, 0x08, 0x00, 0x10, 0x02, 0x01],
+            &CHALLENGE,
+        ]))
+        .sent(&concat(&[&[0x52, 0x08, 0x00, 0x03, 0x00], &ENCRYPTED]))
+        .received(&[0x1B, 0x08, 0x00, 0x10, 0x03, 0x01])
+        // A handle the discovery did not reach.
+        .sent(&[0x0A, 0x20, 0x00])
+        .received(&[0x01, 0x0A, 0x20, 0x00, 0x01])
+        .command(0x0406, &[0x40, 0x00, 0x13])
+        .event(0x05, &[0x00, 0x40, 0x00, 0x16]);
+    log.bytes
+}
+
+fn imported() -> btsnoop::Import {
+    btsnoop::import(&btsnoop::parse(&session()).unwrap())
+}
+
+#[test]
+fn handles_are_named_after_the_logged_discovery() {
+    let import = imported();
+
+    let connection = &import.summary.connections[0];
+    assert_eq!(connection.device, watch_address().to_string());
+    assert_eq!(connection.attributes, 4);
+    assert_eq!(connection.skipped, 1);
+    let records: Vec<&Record> = import.entries.iter().map(|entry| &entry.record).collect();
+    assert!(records.contains(&&Record::Read {
+        device: watch_address().to_string(),
+        characteristic: CURRENT_TIME_UUID,
+        result: Outcome::Ok(Bytes(CURRENT_TIME_VALUE.to_vec())),
+    }));
+    let services = records.iter().find_map(|record| match record {
+        Record::DiscoverServices {
+            result: Outcome::Ok(services),
+            ..
+        } => Some(services),
+        _ => None,
+    });
+    let characteristics: Vec<_> = services
+        .unwrap()
+        .iter()
+        .flat_map(|service| &service.characteristics)
+        .map(|characteristic| (characteristic.uuid, characteristic.descriptors.len()))
+        .collect();
+    assert_eq!(
+        characteristics,
+        [(CURRENT_TIME_UUID, 1), (HUAMI_AUTH_UUID, 1)]
+    );
+}
+
+#[test]
+fn the_huami_auth_exchange_is_extracted() {
+    let import = imported();
+
+    assert_eq!(
+        import.summary.auth,
+        [AuthExchange {
+            device: watch_address().to_string(),
+            key: Some(String::from("0x30313233343536373839616263646566")),
+            challenge: Some(Bytes(CHALLENGE.to_vec())),
+            response: Some(Bytes(ENCRYPTED.to_vec())),
+            accepted: Some(true),
+        }]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn imported_logs_replay_like_a_session() {
+    let path =
+        std::env::temp_dir().join(format!("smartwatch-btsnoop-{}.jsonl", std::process::id()));
+    imported().save(&path).unwrap();
+    let manager = ReplayManager::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let adapter = discovery::adapters(&manager).await.unwrap().remove(0);
+    let selector = DeviceSelector {
+        name: Some(Regex::new("^Amazfit").unwrap()),
+        ..DeviceSelector::default()
+    };
+    let scan = discovery::find(
+        &adapter,
+        &selector,
+        Duration::from_secs(10),
+        StopWhen::FirstMatch,
+    )
+    .await
+    .unwrap();
+    let peripheral = &scan.devices[0].peripheral;
+    assert_eq!(peripheral.address(), watch_address());
+    assert!(connection::connect(peripheral).await.unwrap());
+    let characteristic = connection::require_characteristic(peripheral, CURRENT_TIME_UUID).unwrap();
+    assert_eq!(
+        peripheral.read(&characteristic).await.unwrap(),
+        CURRENT_TIME_VALUE
+    );
+    let mut notifications = peripheral.notifications().await.unwrap();
+    peripheral.subscribe(&characteristic).await.unwrap();
+
+    let mut later = CURRENT_TIME_VALUE;
+    later[6] = 1;
+    assert_eq!(notifications.next().await.unwrap().value, later);
+}
+
+#[test]
+fn truncated_discovery_responses_are_skipped() {
+    let mut log = Log::new();
+    log.event(0x3E, &connected());
+    // Read By Group Type responses whose records are too short for a UUID.
+    for length in 1..=5u8 {
+        log.sent(&[0x10, 0x01, 0x00, 0xFF, 0xFF, 0x00, 0x28])
+            .received(&concat(&[&[0x11, length], &vec![0x01; length.into()]]));
+    }
+    log.sent(&[0x0A, 0x03, 0x00])
+        .received(&concat(&[&[0x0B], &CURRENT_TIME_VALUE]));
+
+    let import = btsnoop::import(&btsnoop::parse(&log.bytes).unwrap());
+    let connection = &import.summary.connections[0];
+    assert_eq!(connection.attributes, 0);
+    assert_eq!(connection.skipped, 1);
+    let services = import.entries.iter().find_map(|entry| match &entry.record {
+        Record::DiscoverServices {
+            result: Outcome::Ok(services),
+            ..
+        } => Some(services.len()),
+        _ => None,
+    });
+    assert_eq!(services, Some(0));
+}
+
+#[test]
+fn other_formats_and_versions_are_rejected() {
+    let err = btsnoop::parse(b"PK\x03\x04 not a log at all").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let mut log = Log::new().bytes;
+    log[11] = 2;
+    let err = btsnoop::parse(&log).unwrap_err();
+    assert!(err.to_string().contains("version 2"));
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use btleplug::api::Characteristic;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use common::watch;
+use smartwatch::backend::fake::FakePeripheral;
+use smartwatch::clock::{Clock, FixedClock, OffsetClock};
+use smartwatch::connection;
+use smartwatch::gatt::cts::TimeSource;
+use smartwatch::gatt::{
+    CURRENT_TIME_UUID, LOCAL_TIME_INFORMATION_UUID, REFERENCE_TIME_INFORMATION_UUID,
+};
+use smartwatch::sync::TimeSync;
+
+fn time_sync<C: Clock>(clock: C) -> TimeSync<C> {
+    TimeSync {
+        zone: chrono_tz::Europe::Rome,
+        clock,
+        precise_probes: None,
+        dry_run: false,
+        reference_source: TimeSource::NetworkTimeProtocol,
+    }
+}
+
+async fn current_time(peripheral: &FakePeripheral) -> Characteristic {
+    connection::connect(peripheral).await.unwrap();
+    connection::require_characteristic(peripheral, CURRENT_TIME_UUID).unwrap()
+}
+
+/// Syncs the fake watch to `clock` and returns the payloads of the four time services.
+async fn payloads(clock: impl Clock, expected: [&[u8]; 3]) -> Vec<Vec<u8>> {
+    let peripheral = watch()
+        .expect_write(CURRENT_TIME_UUID, Some(expected[0]))
+        .expect_write(LOCAL_TIME_INFORMATION_UUID, Some(expected[1]))
+        .expect_write(REFERENCE_TIME_INFORMATION_UUID, Some(expected[2]));
+    let characteristic = current_time(&peripheral).await;
+    let reports = time_sync(clock)
+        .sync(&peripheral, &characteristic)
+        .await
+        .unwrap();
+    peripheral.verify().unwrap();
+    reports.into_iter().map(|report| report.payload).collect()
+}
+
+fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(year, month, day, hour, minute, second)
+        .unwrap()
+}
+
+// Paused so that the writes take no time and the reported accuracy is exact.
+#[tokio::test(start_paused = true)]
+async fn fixed_clock_in_winter() {
+    let current_time = [0xEA, 0x07, 3, 1, 13, 0, 0, 7, 0, 1];
+    let local_time = [4, 0];
+    let reference_time = [1, 0, 0, 0];
+    let payloads = payloads(
+        FixedClock(utc(2026, 3, 1, 12, 0, 0)),
+        [&current_time, &local_time, &reference_time],
+    )
+    .await;
+    assert_eq!(payloads[0], current_time);
+    assert_eq!(payloads[1], local_time);
+    assert_eq!(payloads[2], reference_time);
+    // Next change: 2026-03-29 02:00 local, to daylight time.
+    assert_eq!(payloads[3], [0xEA, 0x07, 3, 29, 2, 0, 0, 4]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn fixed_clock_in_summer() {
+    let current_time = [0xEA, 0x07, 7, 15, 12, 30, 15, 3, 0, 1];
+    let local_time = [4, 4];
+    let reference_time = [1, 0, 0, 0];
+    let payloads = payloads(
+        FixedClock(utc(2026, 7, 15, 10, 30, 15)),
+        [&current_time, &local_time, &reference_time],
+    )
+    .await;
+    assert_eq!(payloads[0], current_time);
+    assert_eq!(payloads[1], local_time);
+    assert_eq!(payloads[2], reference_time);
+    // Next change: 2026-10-25 03:00 local, back to standard time.
+    assert_eq!(payloads[3], [0xEA, 0x07, 10, 25, 3, 0, 0, 0]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn offset_clock_shifts_the_written_time() {
+    let clock = OffsetClock::new(
+        FixedClock(utc(2026, 3, 1, 22, 59, 30)),
+        Duration::seconds(45),
+    );
+    assert_eq!(clock.now(), utc(2026, 3, 1, 23, 0, 15));
+    // Past midnight in Rome: Monday 2026-03-02 00:00:15.
+    let current_time = [0xEA, 0x07, 3, 2, 0, 0, 15, 1, 0, 1];
+    let payloads = payloads(clock, [&current_time, &[4, 0], &[1, 0, 0, 0]]).await;
+    assert_eq!(payloads[0], current_time);
+}
+
+#[test]
+fn offset_clock_starting_at_an_instant() {
+    let at = utc(2030, 1, 1, 0, 0, 0);
+    let clock = OffsetClock::starting_at(FixedClock(utc(2026, 3, 1, 12, 0, 0)), at);
+    assert_eq!(clock.now(), at);
+    assert_eq!(clock.offset(), at - utc(2026, 3, 1, 12, 0, 0));
+}
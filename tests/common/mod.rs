@@ -0,0 +1,73 @@
+//! Fake watches shared by the integration tests.
+
+#![allow(dead_code)]
+
+use btleplug::api::bleuuid::uuid_from_u16;
+use btleplug::api::{BDAddr, CharPropFlags};
+use smartwatch::assigned::HUAMI_COMPANY_ID;
+use smartwatch::backend::fake::FakePeripheral;
+use smartwatch::gatt::{
+    CURRENT_TIME_UUID, FIRMWARE_REVISION_UUID, LOCAL_TIME_INFORMATION_UUID,
+    REFERENCE_TIME_INFORMATION_UUID,
+};
+use uuid::Uuid;
+
+pub fn watch_address() -> BDAddr {
+    BDAddr::from([0xC0, 0xFF, 0xEE, 0x00, 0x00, 0x01])
+}
+
+pub fn other_address() -> BDAddr {
+    BDAddr::from([0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC])
+}
+
+pub const CURRENT_TIME_SERVICE_UUID: Uuid = uuid_from_u16(0x1805);
+pub const DEVICE_INFORMATION_SERVICE_UUID: Uuid = uuid_from_u16(0x180A);
+pub const HEART_RATE_SERVICE_UUID: Uuid = uuid_from_u16(0x180D);
+pub const HEART_RATE_MEASUREMENT_UUID: Uuid = uuid_from_u16(0x2A37);
+
+/// Current Time of Sunday 2026-03-01 13:00:00, manually set.
+pub const CURRENT_TIME_VALUE: [u8; 10] = [0xEA, 0x07, 3, 1, 13, 0, 0, 7, 0, 1];
+
+/// Huami manufacturer data, ending with the device address.
+pub const HUAMI_ADVERT: [u8; 8] = [0x02, 0x00, 0xC0, 0xFF, 0xEE, 0x00, 0x00, 0x01];
+
+/// An Amazfit GTS 4 Mini exposing the Current Time Service, without Next DST Change.
+pub fn watch() -> FakePeripheral {
+    let read_write = CharPropFlags::READ | CharPropFlags::WRITE;
+    FakePeripheral::new(watch_address())
+        .with_name("Amazfit GTS 4 Mini")
+        .with_rssi(-60)
+        .with_advertised_service(CURRENT_TIME_SERVICE_UUID)
+        .with_manufacturer_data(HUAMI_COMPANY_ID, &HUAMI_ADVERT)
+        .with_characteristic(
+            CURRENT_TIME_SERVICE_UUID,
+            CURRENT_TIME_UUID,
+            read_write | CharPropFlags::NOTIFY,
+            &CURRENT_TIME_VALUE,
+        )
+        .with_characteristic(
+            CURRENT_TIME_SERVICE_UUID,
+            LOCAL_TIME_INFORMATION_UUID,
+            read_write,
+            &[4, 0],
+        )
+        .with_characteristic(
+            CURRENT_TIME_SERVICE_UUID,
+            REFERENCE_TIME_INFORMATION_UUID,
+            read_write,
+            &[4, 0, 0, 0],
+        )
+        .with_characteristic(
+            DEVICE_INFORMATION_SERVICE_UUID,
+            FIRMWARE_REVISION_UUID,
+            CharPropFlags::READ,
+            b"3.18.1",
+        )
+}
+
+/// A device in range that is not a watch.
+pub fn headphones() -> FakePeripheral {
+    FakePeripheral::new(other_address())
+        .with_name("Headphones")
+        .with_rssi(-80)
+}
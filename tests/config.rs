@@ -0,0 +1,159 @@
+use smartwatch::config::{Config, OutputFormat, RetryPolicy};
+use smartwatch::error::ErrorCategory;
+use smartwatch::SmartwatchError;
+use std::fs;
+use std::time::Duration;
+
+const SETTINGS: &str = r#"
+[defaults]
+adapter = 1
+scan_timeout = "15s"
+output = "json"
+time_zone = "Europe/Rome"
+
+[defaults.retry]
+attempts = 3
+delay = "500ms"
+
+[[devices]]
+alias = "kitchen-watch"
+address = "C0:FF:EE:00:00:01"
+profile = "Amazfit GTS 4 Mini"
+auth_key = "0x30313233343536373839616263646566"
+time_zone = "Asia/Tokyo"
+
+[devices.sync]
+error_bound = "250ms"
+max_interval = "1d"
+
+[[devices]]
+alias = "spare"
+name = "^Amazfit"
+"#;
+
+/// Writes `text` to a settings file and loads it.
+fn load(name: &str, text: &str) -> Result<Config, SmartwatchError> {
+    let path =
+        std::env::temp_dir().join(format!("smartwatch-{}-{}.toml", name, std::process::id()));
+    fs::write(&path, text).unwrap();
+    let config = Config::load(&path);
+    fs::remove_file(&path).unwrap();
+    config
+}
+
+/// The message of the error `text` is rejected with.
+fn rejection(name: &str, text: &str) -> String {
+    match load(name, text) {
+        Err(err @ SmartwatchError::Config { .. }) => err.to_string(),
+        other => panic!("expected invalid settings, got {:?}", other),
+    }
+}
+
+#[test]
+fn loads_defaults_and_devices() {
+    let config = load("valid", SETTINGS).unwrap();
+    let defaults = &config.defaults;
+    assert_eq!(defaults.adapter, Some(1));
+    assert_eq!(defaults.scan_timeout, Some(Duration::from_secs(15)));
+    assert_eq!(defaults.output, OutputFormat::Json);
+    assert_eq!(defaults.time_zone.as_deref(), Some("Europe/Rome"));
+    assert_eq!(
+        defaults.retry,
+        RetryPolicy {
+            attempts: 3,
+            delay: Duration::from_millis(500),
+        }
+    );
+
+    let kitchen = config.device("kitchen-watch").unwrap();
+    assert_eq!(kitchen.address.as_deref(), Some("C0:FF:EE:00:00:01"));
+    assert_eq!(kitchen.profile.unwrap().model, "Amazfit GTS 4 Mini");
+    assert_eq!(kitchen.auth_key, Some(*b"0123456789abcdef"));
+    assert_eq!(kitchen.time_zone.as_deref(), Some("Asia/Tokyo"));
+    assert_eq!(
+        kitchen.sync.error_bound,
+        Some(chrono::Duration::milliseconds(250))
+    );
+    assert_eq!(kitchen.sync.min_interval, None);
+    assert_eq!(kitchen.sync.max_interval, Some(chrono::Duration::days(1)));
+
+    let spare = config.device("spare").unwrap();
+    assert!(spare.name.as_ref().unwrap().is_match("Amazfit Bip"));
+    assert_eq!(spare.profile, None);
+}
+
+#[test]
+fn empty_file_keeps_the_builtin_defaults() {
+    let config = load("empty", "").unwrap();
+    assert_eq!(config.defaults.adapter, None);
+    assert_eq!(config.defaults.output, OutputFormat::Text);
+    assert_eq!(config.defaults.retry, RetryPolicy::default());
+    assert!(config.devices.is_empty());
+}
+
+#[test]
+fn unknown_alias_lists_the_known_ones() {
+    let config = load("aliases", SETTINGS).unwrap();
+    let err = config.device("bedroom-watch").unwrap_err();
+    assert_eq!(err.category(), ErrorCategory::Config);
+    let message = err.to_string();
+    assert!(message.contains("bedroom-watch"), "{}", message);
+    assert!(message.contains("kitchen-watch"), "{}", message);
+    assert!(message.contains("spare"), "{}", message);
+}
+
+#[test]
+fn rejects_invalid_settings() {
+    let cases = [
+        (
+            "duplicate",
+            "[[devices]]\nalias = \"a\"\nname = \"x\"\n[[devices]]\nalias = \"a\"\nname = \"y\"\n",
+            "listed twice",
+        ),
+        (
+            "zone",
+            "[[devices]]\nalias = \"a\"\nname = \"x\"\ntime_zone = \"Mars/Olympus\"\n",
+            "Mars/Olympus",
+        ),
+        (
+            "key",
+            "[[devices]]\nalias = \"a\"\nname = \"x\"\nauth_key = \"0x0102\"\n",
+            "auth key",
+        ),
+        (
+            "profile",
+            "[[devices]]\nalias = \"a\"\nprofile = \"Pebble\"\n",
+            "unknown profile",
+        ),
+        ("field", "[defaults]\ncolour = \"blue\"\n", "colour"),
+        ("unfindable", "[[devices]]\nalias = \"a\"\n", "no address"),
+        ("attempts", "[defaults.retry]\nattempts = 0\n", "attempts"),
+        (
+            "interval",
+            "[[devices]]\nalias = \"a\"\nname = \"x\"\n[devices.sync]\nmin_interval = \"2d\"\nmax_interval = \"1d\"\n",
+            "exceeds",
+        ),
+        // The built-in maximum interval of 7 days applies.
+        (
+            "lone-minimum",
+            "[[devices]]\nalias = \"a\"\nname = \"x\"\n[devices.sync]\nmin_interval = \"10d\"\n",
+            "exceeds",
+        ),
+        (
+            "bound",
+            "[[devices]]\nalias = \"a\"\nname = \"x\"\n[devices.sync]\nerror_bound = \"0s\"\n",
+            "must be positive",
+        ),
+    ];
+    for (name, text, expected) in cases {
+        let message = rejection(name, text);
+        assert!(message.contains(expected), "{}: {}", name, message);
+    }
+}
+
+#[test]
+fn missing_explicit_file_is_an_error() {
+    let path = std::env::temp_dir().join("smartwatch-missing-settings.toml");
+    let err = Config::locate(Some(&path)).unwrap_err();
+    assert_eq!(err.category(), ErrorCategory::Io);
+}
@@ -0,0 +1,219 @@
+mod common;
+
+use btleplug::api::{CharPropFlags, Characteristic, WriteType};
+use common::{watch, CURRENT_TIME_VALUE, HEART_RATE_MEASUREMENT_UUID, HEART_RATE_SERVICE_UUID};
+use futures::stream::StreamExt;
+use smartwatch::backend::fake::FakePeripheral;
+use smartwatch::backend::Peripheral;
+use smartwatch::connection::{self, CONNECT_TIMEOUT};
+use smartwatch::gatt::descriptor::PRESENTATION_FORMAT_UUID;
+use smartwatch::gatt::{CURRENT_TIME_UUID, TIME_WITH_DST_UUID};
+use smartwatch::SmartwatchError;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Connects to `peripheral` and looks up one of its characteristics.
+async fn connected(peripheral: &FakePeripheral, uuid: Uuid) -> Characteristic {
+    connection::connect(peripheral).await.unwrap();
+    connection::require_characteristic(peripheral, uuid).unwrap()
+}
+
+#[tokio::test]
+async fn connect_discovers_the_services() {
+    let peripheral = watch();
+    assert!(peripheral.services().is_empty());
+
+    assert!(connection::connect(&peripheral).await.unwrap());
+
+    assert_eq!(peripheral.services().len(), 2);
+    assert!(connection::find_characteristic(&peripheral, CURRENT_TIME_UUID).is_some());
+    assert!(connection::find_characteristic(&peripheral, TIME_WITH_DST_UUID).is_none());
+}
+
+#[tokio::test]
+async fn connect_reports_a_refused_connection() {
+    let peripheral = watch().failing_connects(1);
+
+    let result = connection::connect(&peripheral).await;
+    assert!(matches!(result, Err(SmartwatchError::Connect { .. })));
+
+    assert!(connection::connect(&peripheral).await.unwrap());
+    assert_eq!(peripheral.connect_attempts(), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn connect_gives_up_on_a_slow_device() {
+    let peripheral = watch().with_connect_delay(CONNECT_TIMEOUT * 2);
+
+    let result = connection::connect(&peripheral).await;
+    assert!(matches!(
+        result,
+        Err(SmartwatchError::Timeout { after, .. }) if after == CONNECT_TIMEOUT
+    ));
+}
+
+#[tokio::test]
+async fn require_characteristic_names_the_missing_one() {
+    let peripheral = watch();
+    connection::connect(&peripheral).await.unwrap();
+
+    let result = connection::require_characteristic(&peripheral, TIME_WITH_DST_UUID);
+    assert!(matches!(
+        result,
+        Err(SmartwatchError::MissingCharacteristic { characteristic, .. })
+            if characteristic == TIME_WITH_DST_UUID
+    ));
+}
+
+#[tokio::test]
+async fn read_follows_the_script_then_the_value() {
+    let peripheral = watch().with_reads(
+        CURRENT_TIME_UUID,
+        vec![
+            Ok(vec![0; 10]),
+            Err(String::from("insufficient authentication")),
+        ],
+    );
+    let characteristic = connected(&peripheral, CURRENT_TIME_UUID).await;
+
+    assert_eq!(
+        connection::read(&peripheral, &characteristic)
+            .await
+            .unwrap(),
+        vec![0; 10]
+    );
+    assert!(matches!(
+        connection::read(&peripheral, &characteristic).await,
+        Err(SmartwatchError::Read { characteristic, .. }) if characteristic == CURRENT_TIME_UUID
+    ));
+    assert_eq!(
+        connection::read(&peripheral, &characteristic)
+            .await
+            .unwrap(),
+        CURRENT_TIME_VALUE
+    );
+}
+
+#[tokio::test]
+async fn read_fails_when_disconnected() {
+    let peripheral = watch();
+    let characteristic = connected(&peripheral, CURRENT_TIME_UUID).await;
+    connection::disconnect(&peripheral).await.unwrap();
+
+    let result = connection::read(&peripheral, &characteristic).await;
+    assert!(matches!(result, Err(SmartwatchError::Read { .. })));
+}
+
+#[tokio::test]
+async fn write_is_recorded_and_read_back() {
+    let peripheral = watch().expect_write(CURRENT_TIME_UUID, Some(&[1, 2, 3]));
+    let characteristic = connected(&peripheral, CURRENT_TIME_UUID).await;
+
+    connection::write(
+        &peripheral,
+        &characteristic,
+        &[1, 2, 3],
+        WriteType::WithResponse,
+    )
+    .await
+    .unwrap();
+
+    peripheral.verify().unwrap();
+    assert_eq!(peripheral.writes()[0].write_type, WriteType::WithResponse);
+    assert_eq!(
+        connection::read(&peripheral, &characteristic)
+            .await
+            .unwrap(),
+        [1, 2, 3]
+    );
+}
+
+#[tokio::test]
+async fn verify_reports_unexpected_and_missing_writes() {
+    let peripheral = watch().expect_write(CURRENT_TIME_UUID, Some(&[1]));
+    let characteristic = connected(&peripheral, CURRENT_TIME_UUID).await;
+
+    connection::write(&peripheral, &characteristic, &[2], WriteType::WithResponse)
+        .await
+        .unwrap();
+
+    let problems = peripheral.verify().unwrap_err();
+    assert!(problems.contains("missing write"));
+    assert!(problems.contains("unexpected write"));
+}
+
+#[tokio::test(start_paused = true)]
+async fn write_times_out_on_a_stalled_device() {
+    let peripheral = watch().with_latency(connection::GATT_TIMEOUT * 2);
+    peripheral.connect().await.unwrap();
+    peripheral.discover_services().await.unwrap();
+    let characteristic = connection::find_characteristic(&peripheral, CURRENT_TIME_UUID).unwrap();
+
+    let result =
+        connection::write(&peripheral, &characteristic, &[0], WriteType::WithResponse).await;
+    assert!(matches!(result, Err(SmartwatchError::Timeout { .. })));
+}
+
+#[tokio::test(start_paused = true)]
+async fn subscribe_streams_the_notifications_of_the_characteristic() {
+    let peripheral = watch()
+        .with_characteristic(
+            HEART_RATE_SERVICE_UUID,
+            HEART_RATE_MEASUREMENT_UUID,
+            CharPropFlags::NOTIFY,
+            &[],
+        )
+        .with_notifications(
+            HEART_RATE_MEASUREMENT_UUID,
+            vec![
+                (Duration::from_secs(1), vec![0x00, 72]),
+                (Duration::from_secs(1), vec![0x00, 75]),
+            ],
+        );
+    let heart_rate = connected(&peripheral, HEART_RATE_MEASUREMENT_UUID).await;
+
+    let notifications = connection::subscribe(&peripheral, &heart_rate)
+        .await
+        .unwrap();
+    // Notifications of other characteristics are filtered out.
+    peripheral.notify(CURRENT_TIME_UUID, &CURRENT_TIME_VALUE);
+    let values: Vec<Vec<u8>> = notifications.map(|n| n.value).take(2).collect().await;
+
+    assert_eq!(values, [vec![0x00, 72], vec![0x00, 75]]);
+    assert!(peripheral.is_subscribed(HEART_RATE_MEASUREMENT_UUID));
+    connection::unsubscribe(&peripheral, &heart_rate)
+        .await
+        .unwrap();
+    assert!(!peripheral.is_subscribed(HEART_RATE_MEASUREMENT_UUID));
+}
+
+#[tokio::test]
+async fn read_presentation_format_decodes_the_descriptor() {
+    let peripheral = watch()
+        .with_characteristic(
+            HEART_RATE_SERVICE_UUID,
+            HEART_RATE_MEASUREMENT_UUID,
+            CharPropFlags::READ,
+            &[72],
+        )
+        .with_descriptor(
+            HEART_RATE_MEASUREMENT_UUID,
+            PRESENTATION_FORMAT_UUID,
+            &[0x04, 0x00, 0xA7, 0x27, 0x01, 0x00, 0x00],
+        );
+    let heart_rate = connected(&peripheral, HEART_RATE_MEASUREMENT_UUID).await;
+    let current_time = connection::find_characteristic(&peripheral, CURRENT_TIME_UUID).unwrap();
+
+    let format = connection::read_presentation_format(&peripheral, &heart_rate)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(format.format_name(), Some("uint8"));
+    assert_eq!(format.unit, 0x27A7);
+    assert!(
+        connection::read_presentation_format(&peripheral, &current_time)
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
@@ -0,0 +1,299 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime, Weekday};
+use smartwatch::gatt::cts::{AdjustReason, CurrentTime, DstOffset, TimeWithDst};
+use smartwatch::gatt::CodecError;
+
+/// 2026-03-01 13:00:00.5, a Sunday, manually set.
+const SUNDAY_ONE_PM: [u8; 10] = [0xEA, 0x07, 3, 1, 13, 0, 0, 7, 128, 1];
+
+fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(year, month, day)
+        .unwrap()
+        .and_hms_opt(hour, minute, second)
+        .unwrap()
+}
+
+fn sunday_one_pm() -> CurrentTime {
+    CurrentTime {
+        year: 2026,
+        month: 3,
+        day: 1,
+        hour: 13,
+        minute: 0,
+        second: 0,
+        day_of_week: Some(Weekday::Sun),
+        fractions256: 128,
+        adjust_reason: AdjustReason::MANUAL_TIME_UPDATE,
+    }
+}
+
+#[test]
+fn current_time_round_trips() {
+    assert_eq!(CurrentTime::decode(&SUNDAY_ONE_PM), Ok(sunday_one_pm()));
+    assert_eq!(sunday_one_pm().encode(), Ok(SUNDAY_ONE_PM));
+}
+
+#[test]
+fn unknown_fields_round_trip_as_zero() {
+    let bytes = [0, 0, 0, 0, 10, 20, 30, 0, 0, 0];
+    let time = CurrentTime::decode(&bytes).unwrap();
+    assert_eq!((time.year, time.month, time.day), (0, 0, 0));
+    assert_eq!(time.day_of_week, None);
+    assert_eq!(time.encode(), Ok(bytes));
+    assert!(time.to_naive_datetime().is_err());
+}
+
+#[test]
+fn rejects_out_of_range_fields() {
+    let cases: [(usize, u8, &str); 6] = [
+        (2, 13, "month"),
+        (3, 32, "day"),
+        (4, 24, "hour"),
+        (5, 60, "minute"),
+        (6, 60, "second"),
+        (7, 8, "day of week"),
+    ];
+    for (index, value, field) in cases {
+        let mut bytes = SUNDAY_ONE_PM;
+        bytes[index] = value;
+        assert_eq!(
+            CurrentTime::decode(&bytes),
+            Err(CodecError::OutOfRange {
+                field,
+                value: value.into()
+            }),
+            "{}",
+            field
+        );
+    }
+
+    let mut time = sunday_one_pm();
+    time.hour = 24;
+    assert_eq!(
+        time.encode(),
+        Err(CodecError::OutOfRange {
+            field: "hour",
+            value: 24
+        })
+    );
+}
+
+#[test]
+fn rejects_impossible_dates_and_lengths() {
+    let mut bytes = SUNDAY_ONE_PM;
+    bytes[2] = 2;
+    bytes[3] = 30;
+    assert_eq!(
+        CurrentTime::decode(&bytes),
+        Err(CodecError::InvalidDate {
+            year: 2026,
+            month: 2,
+            day: 30
+        })
+    );
+    assert_eq!(
+        CurrentTime::decode(&SUNDAY_ONE_PM[..9]),
+        Err(CodecError::Length {
+            expected: 10,
+            actual: 9
+        })
+    );
+}
+
+#[test]
+fn fractions_round_down_to_256ths() {
+    let cases = [
+        (0, 0),
+        (3_906_249, 0),
+        (3_906_250, 1),
+        (500_000_000, 128),
+        (999_999_999, 255),
+        // A leap second counts as the last fraction of the second before.
+        (1_500_000_000, 255),
+    ];
+    for (nanos, fractions) in cases {
+        let at = NaiveDate::from_ymd_opt(2026, 3, 1)
+            .unwrap()
+            .and_hms_nano_opt(13, 0, 59, nanos)
+            .unwrap();
+        let time = CurrentTime::from_datetime(&at, AdjustReason::NONE).unwrap();
+        assert_eq!(time.fractions256, fractions, "{} ns", nanos);
+    }
+}
+
+#[test]
+fn adjust_reason_bits() {
+    let all = AdjustReason::from_bits(0x0F).unwrap();
+    for flag in [
+        AdjustReason::MANUAL_TIME_UPDATE,
+        AdjustReason::EXTERNAL_REFERENCE_TIME_UPDATE,
+        AdjustReason::CHANGE_OF_TIME_ZONE,
+        AdjustReason::CHANGE_OF_DST,
+    ] {
+        assert!(all.contains(flag));
+        assert!(!AdjustReason::NONE.contains(flag));
+    }
+    assert_eq!(
+        all.to_string(),
+        "manual, external reference, time zone, DST"
+    );
+    assert_eq!(AdjustReason::CHANGE_OF_DST.bits(), 0x08);
+    assert_eq!(AdjustReason::from_bits(0x10), None);
+
+    let mut bytes = SUNDAY_ONE_PM;
+    bytes[9] = 0x06;
+    let time = CurrentTime::decode(&bytes).unwrap();
+    assert!(time
+        .adjust_reason
+        .contains(AdjustReason::EXTERNAL_REFERENCE_TIME_UPDATE));
+    assert!(time
+        .adjust_reason
+        .contains(AdjustReason::CHANGE_OF_TIME_ZONE));
+    assert_eq!(time.encode(), Ok(bytes));
+
+    bytes[9] = 0x81;
+    assert_eq!(
+        CurrentTime::decode(&bytes),
+        Err(CodecError::ReservedBits {
+            field: "adjust reason",
+            value: 0x81
+        })
+    );
+}
+
+#[test]
+fn encodes_calendar_matrix() {
+    let cases = [
+        // Year boundaries.
+        (
+            at(1999, 12, 31, 23, 59, 59),
+            [0xCF, 0x07, 12, 31, 23, 59, 59, 5, 0, 0],
+        ),
+        (
+            at(2000, 1, 1, 0, 0, 0),
+            [0xD0, 0x07, 1, 1, 0, 0, 0, 6, 0, 0],
+        ),
+        (
+            at(2025, 12, 31, 23, 59, 59),
+            [0xE9, 0x07, 12, 31, 23, 59, 59, 3, 0, 0],
+        ),
+        (
+            at(2026, 1, 1, 0, 0, 0),
+            [0xEA, 0x07, 1, 1, 0, 0, 0, 4, 0, 0],
+        ),
+        // Feb 29 of leap years, including a century divisible by 400.
+        (
+            at(2024, 2, 29, 12, 0, 0),
+            [0xE8, 0x07, 2, 29, 12, 0, 0, 4, 0, 0],
+        ),
+        (
+            at(2000, 2, 29, 12, 0, 0),
+            [0xD0, 0x07, 2, 29, 12, 0, 0, 2, 0, 0],
+        ),
+        // Every weekday, Monday = 1 ... Sunday = 7.
+        (
+            at(2026, 3, 2, 8, 0, 0),
+            [0xEA, 0x07, 3, 2, 8, 0, 0, 1, 0, 0],
+        ),
+        (
+            at(2026, 3, 3, 8, 0, 0),
+            [0xEA, 0x07, 3, 3, 8, 0, 0, 2, 0, 0],
+        ),
+        (
+            at(2026, 3, 4, 8, 0, 0),
+            [0xEA, 0x07, 3, 4, 8, 0, 0, 3, 0, 0],
+        ),
+        (
+            at(2026, 3, 5, 8, 0, 0),
+            [0xEA, 0x07, 3, 5, 8, 0, 0, 4, 0, 0],
+        ),
+        (
+            at(2026, 3, 6, 8, 0, 0),
+            [0xEA, 0x07, 3, 6, 8, 0, 0, 5, 0, 0],
+        ),
+        (
+            at(2026, 3, 7, 8, 0, 0),
+            [0xEA, 0x07, 3, 7, 8, 0, 0, 6, 0, 0],
+        ),
+        (
+            at(2026, 3, 8, 8, 0, 0),
+            [0xEA, 0x07, 3, 8, 8, 0, 0, 7, 0, 0],
+        ),
+    ];
+    for (datetime, bytes) in cases {
+        let time = CurrentTime::from_datetime(&datetime, AdjustReason::NONE).unwrap();
+        assert_eq!(time.encode(), Ok(bytes), "{}", datetime);
+        let decoded = CurrentTime::decode(&bytes).unwrap();
+        assert_eq!(decoded, time, "{}", datetime);
+        assert_eq!(decoded.to_naive_datetime(), Ok(datetime));
+    }
+}
+
+#[test]
+fn rolls_over_after_last_second_of_day() {
+    let cases = [
+        // Into March of a common year and of a leap year.
+        (
+            at(2026, 2, 28, 23, 59, 59),
+            [0xEA, 0x07, 3, 1, 0, 0, 0, 7, 0, 0],
+        ),
+        (
+            at(2024, 2, 28, 23, 59, 59),
+            [0xE8, 0x07, 2, 29, 0, 0, 0, 4, 0, 0],
+        ),
+        (
+            at(2024, 2, 29, 23, 59, 59),
+            [0xE8, 0x07, 3, 1, 0, 0, 0, 5, 0, 0],
+        ),
+        // Into the next year.
+        (
+            at(2025, 12, 31, 23, 59, 59),
+            [0xEA, 0x07, 1, 1, 0, 0, 0, 4, 0, 0],
+        ),
+    ];
+    for (datetime, bytes) in cases {
+        let next = datetime + Duration::seconds(1);
+        let time = CurrentTime::from_datetime(&next, AdjustReason::NONE).unwrap();
+        assert_eq!(time.encode(), Ok(bytes), "{}", datetime);
+    }
+}
+
+#[test]
+fn rejects_feb_29_of_common_years() {
+    for year in [2025u16, 2026, 1900, 2100] {
+        let mut bytes = [0, 0, 2, 29, 12, 0, 0, 0, 0, 0];
+        bytes[..2].copy_from_slice(&year.to_le_bytes());
+        assert_eq!(
+            CurrentTime::decode(&bytes),
+            Err(CodecError::InvalidDate {
+                year,
+                month: 2,
+                day: 29
+            }),
+            "{}",
+            year
+        );
+    }
+}
+
+#[test]
+fn time_with_dst_names_the_bad_field() {
+    let cases: [(usize, u8, &str); 3] = [(4, 24, "hour"), (5, 60, "minute"), (6, 60, "second")];
+    for (index, value, field) in cases {
+        let mut bytes = [0xEA, 0x07, 3, 29, 2, 0, 0, 4];
+        bytes[index] = value;
+        assert_eq!(
+            TimeWithDst::decode(&bytes),
+            Err(CodecError::OutOfRange {
+                field,
+                value: value.into()
+            })
+        );
+    }
+    assert_eq!(
+        TimeWithDst::decode(&[0xEA, 0x07, 3, 29, 2, 0, 0, 4]),
+        Ok(TimeWithDst {
+            date_time: Some(at(2026, 3, 29, 2, 0, 0)),
+            dst_offset: DstOffset::Daylight,
+        })
+    );
+}
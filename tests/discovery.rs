@@ -0,0 +1,125 @@
+mod common;
+
+use btleplug::api::BDAddr;
+use common::{headphones, watch, watch_address};
+use regex::Regex;
+use smartwatch::backend::fake::{FakeAdapter, FakeManager, FakePeripheral};
+use smartwatch::discovery::{self, StopWhen};
+use smartwatch::selector::DeviceSelector;
+use smartwatch::SmartwatchError;
+use std::time::Duration;
+
+fn watch_selector() -> DeviceSelector {
+    DeviceSelector {
+        name: Some(Regex::new("^Amazfit").unwrap()),
+        ..DeviceSelector::default()
+    }
+}
+
+#[tokio::test]
+async fn adapters_fails_without_adapter() {
+    let result = discovery::adapters(&FakeManager::default()).await;
+    assert!(matches!(result, Err(SmartwatchError::NoAdapter)));
+}
+
+#[tokio::test(start_paused = true)]
+async fn scan_lists_every_device_until_the_timeout() {
+    let adapter = FakeAdapter::new()
+        .with_peripheral(watch())
+        .with_peripheral(headphones().advertising_after(Duration::from_secs(3)));
+
+    let scan = discovery::scan(&adapter, Duration::from_secs(5))
+        .await
+        .unwrap();
+
+    let names: Vec<&str> = scan.devices.iter().map(|d| d.local_name.as_str()).collect();
+    assert_eq!(names, ["Amazfit GTS 4 Mini", "Headphones"]);
+    assert_eq!(scan.elapsed, Duration::from_secs(5));
+    assert!(!scan.stopped_early);
+    assert!(!adapter.is_scanning());
+}
+
+#[tokio::test(start_paused = true)]
+async fn find_stops_at_the_first_match() {
+    let adapter = FakeAdapter::new()
+        .with_peripheral(headphones())
+        .with_peripheral(watch().advertising_after(Duration::from_secs(2)));
+
+    let scan = discovery::find(
+        &adapter,
+        &watch_selector(),
+        Duration::from_secs(10),
+        StopWhen::FirstMatch,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(scan.devices.len(), 1);
+    assert_eq!(scan.devices[0].properties.address, watch_address());
+    assert!(scan.stopped_early);
+    assert_eq!(scan.elapsed, Duration::from_secs(2));
+    assert!(!adapter.is_scanning());
+}
+
+#[tokio::test(start_paused = true)]
+async fn find_keeps_the_latest_advert_of_a_device() {
+    let adapter = FakeAdapter::new().with_peripheral(
+        watch()
+            .with_advert(Duration::from_secs(1), Some(-70))
+            .with_advert(Duration::from_secs(1), Some(-50)),
+    );
+
+    let scan = discovery::find(
+        &adapter,
+        &watch_selector(),
+        Duration::from_secs(5),
+        StopWhen::Timeout,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(scan.devices.len(), 1);
+    assert_eq!(scan.devices[0].properties.rssi, Some(-50));
+}
+
+#[tokio::test(start_paused = true)]
+async fn find_gives_up_at_the_timeout() {
+    let adapter = FakeAdapter::new()
+        .with_peripheral(headphones())
+        .with_peripheral(watch().advertising_after(Duration::from_secs(20)));
+
+    let scan = discovery::find(
+        &adapter,
+        &watch_selector(),
+        Duration::from_secs(10),
+        StopWhen::FirstMatch,
+    )
+    .await
+    .unwrap();
+
+    assert!(scan.devices.is_empty());
+    assert!(!scan.stopped_early);
+    assert_eq!(scan.elapsed, Duration::from_secs(10));
+}
+
+#[tokio::test(start_paused = true)]
+async fn find_skips_devices_that_vanish_before_the_lookup() {
+    let gone = FakePeripheral::new(BDAddr::from([0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F]))
+        .with_name("Amazfit Bip");
+    let adapter = FakeAdapter::new()
+        .with_vanishing_peripheral(gone)
+        .with_peripheral(watch().advertising_after(Duration::from_secs(1)));
+
+    let scan = discovery::find(
+        &adapter,
+        &watch_selector(),
+        Duration::from_secs(5),
+        StopWhen::Timeout,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(scan.devices.len(), 1);
+    assert_eq!(scan.devices[0].properties.address, watch_address());
+    assert!(!adapter.is_scanning());
+}
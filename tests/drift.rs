@@ -0,0 +1,58 @@
+mod common;
+
+use btleplug::api::Characteristic;
+use chrono::{TimeZone, Utc};
+use common::{watch, CURRENT_TIME_VALUE};
+use smartwatch::backend::fake::FakePeripheral;
+use smartwatch::clock::MockClock;
+use smartwatch::connection;
+use smartwatch::drift;
+use smartwatch::gatt::cts::TimeSource;
+use smartwatch::gatt::CURRENT_TIME_UUID;
+use smartwatch::sync::TimeSync;
+use smartwatch::SmartwatchError;
+
+async fn current_time(peripheral: &FakePeripheral) -> Characteristic {
+    connection::connect(peripheral).await.unwrap();
+    connection::require_characteristic(peripheral, CURRENT_TIME_UUID).unwrap()
+}
+
+#[tokio::test]
+async fn measure_drift_before_and_after_a_sync() {
+    let zone = chrono_tz::Europe::Rome;
+    // The watch shows 13:00:00 while it is 12:59:55 in Rome.
+    let clock = MockClock::new(Utc.with_ymd_and_hms(2026, 3, 1, 11, 59, 55).unwrap());
+    let peripheral = watch();
+    let characteristic = current_time(&peripheral).await;
+
+    let before = drift::measure(&peripheral, &characteristic, &zone, &clock)
+        .await
+        .unwrap();
+    assert_eq!(before.offset, chrono::Duration::seconds(5));
+    assert_eq!(before.watch_time.encode().unwrap(), CURRENT_TIME_VALUE);
+
+    let sync = TimeSync {
+        zone,
+        clock: &clock,
+        precise_probes: None,
+        dry_run: false,
+        reference_source: TimeSource::NetworkTimeProtocol,
+    };
+    sync.set_current_time(&peripheral, &characteristic)
+        .await
+        .unwrap();
+    let after = drift::measure(&peripheral, &characteristic, &zone, &clock)
+        .await
+        .unwrap();
+    assert_eq!(after.offset, chrono::Duration::zero());
+}
+
+#[tokio::test]
+async fn measure_rejects_a_garbled_time() {
+    let peripheral = watch().with_reads(CURRENT_TIME_UUID, vec![Ok(vec![0xFF; 10])]);
+    let characteristic = current_time(&peripheral).await;
+    let clock = MockClock::new(Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap());
+
+    let result = drift::measure(&peripheral, &characteristic, &chrono_tz::UTC, &clock).await;
+    assert!(matches!(result, Err(SmartwatchError::Codec { .. })));
+}
@@ -0,0 +1,52 @@
+use smartwatch::gatt::CURRENT_TIME_UUID;
+use smartwatch::{ErrorCategory, SmartwatchError};
+use std::collections::HashSet;
+
+const CATEGORIES: [ErrorCategory; 15] = [
+    ErrorCategory::Adapter,
+    ErrorCategory::Scan,
+    ErrorCategory::Connect,
+    ErrorCategory::Discovery,
+    ErrorCategory::Read,
+    ErrorCategory::Write,
+    ErrorCategory::Notify,
+    ErrorCategory::Codec,
+    ErrorCategory::Timeout,
+    ErrorCategory::Unsupported,
+    ErrorCategory::Time,
+    ErrorCategory::Io,
+    ErrorCategory::History,
+    ErrorCategory::Changed,
+    ErrorCategory::Config,
+];
+
+#[test]
+fn every_category_has_its_own_exit_code() {
+    let codes: HashSet<u8> = CATEGORIES.iter().map(|c| c.exit_code()).collect();
+    assert_eq!(codes.len(), CATEGORIES.len());
+    // 0 is success and 2 is a command line error reported by clap.
+    assert!(!codes.contains(&0));
+    assert!(!codes.contains(&2));
+}
+
+#[test]
+fn notification_and_history_failures_are_told_apart() {
+    let write = SmartwatchError::Write {
+        device: String::from("watch"),
+        characteristic: CURRENT_TIME_UUID,
+        source: btleplug::Error::NotConnected,
+    };
+    let subscribe = SmartwatchError::Subscribe {
+        device: String::from("watch"),
+        characteristic: CURRENT_TIME_UUID,
+        source: btleplug::Error::NotConnected,
+    };
+    let no_sync = SmartwatchError::NoSyncRecorded {
+        device: String::from("watch"),
+    };
+    assert_eq!(write.category(), ErrorCategory::Write);
+    assert_eq!(subscribe.category(), ErrorCategory::Notify);
+    assert_eq!(no_sync.category(), ErrorCategory::History);
+    assert_ne!(subscribe.exit_code(), write.exit_code());
+    assert_ne!(no_sync.exit_code(), ErrorCategory::Io.exit_code());
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use btleplug::api::CharPropFlags;
+use chrono::{TimeZone, Utc};
+use common::{watch, HEART_RATE_MEASUREMENT_UUID, HEART_RATE_SERVICE_UUID};
+use smartwatch::backend::fake::FakePeripheral;
+use smartwatch::clock::FixedClock;
+use smartwatch::connection;
+use smartwatch::diff::{self, Change};
+use smartwatch::dump::{self, GattDump, Reading};
+use smartwatch::gatt::descriptor::{
+    ClientConfiguration, CLIENT_CONFIGURATION_UUID, PRESENTATION_FORMAT_UUID, USER_DESCRIPTION_UUID,
+};
+use smartwatch::gatt::CURRENT_TIME_UUID;
+
+fn with_heart_rate(peripheral: FakePeripheral) -> FakePeripheral {
+    peripheral
+        .with_characteristic(
+            HEART_RATE_SERVICE_UUID,
+            HEART_RATE_MEASUREMENT_UUID,
+            CharPropFlags::READ | CharPropFlags::NOTIFY,
+            &[72],
+        )
+        .with_descriptor(
+            HEART_RATE_MEASUREMENT_UUID,
+            USER_DESCRIPTION_UUID,
+            b"Pulse\0",
+        )
+        .with_descriptor(
+            HEART_RATE_MEASUREMENT_UUID,
+            PRESENTATION_FORMAT_UUID,
+            &[0x04, 0x00, 0xA7, 0x27, 0x01, 0x00, 0x00],
+        )
+        .with_descriptor(
+            HEART_RATE_MEASUREMENT_UUID,
+            CLIENT_CONFIGURATION_UUID,
+            &[0x01, 0x00],
+        )
+}
+
+async fn capture(peripheral: &FakePeripheral, read_values: bool) -> GattDump {
+    connection::connect(peripheral).await.unwrap();
+    let clock = FixedClock(Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap());
+    dump::capture(peripheral, "Amazfit GTS 4 Mini", read_values, &clock).await
+}
+
+#[tokio::test]
+async fn capture_decodes_the_descriptors() {
+    let peripheral = with_heart_rate(watch());
+
+    let dump = capture(&peripheral, true).await;
+
+    assert_eq!(dump.firmware.as_deref(), Some("3.18.1"));
+    assert_eq!(dump.services.len(), 3);
+    let heart_rate = dump
+        .services
+        .iter()
+        .flat_map(|s| &s.characteristics)
+        .find(|c| c.uuid == HEART_RATE_MEASUREMENT_UUID)
+        .unwrap();
+    assert_eq!(heart_rate.properties, ["read", "notify"]);
+    assert_eq!(heart_rate.user_description.as_deref(), Some("Pulse"));
+    assert_eq!(heart_rate.presentation_format.unwrap().unit, 0x27A7);
+    assert_eq!(
+        heart_rate.client_configuration,
+        Some(ClientConfiguration {
+            notifications: true,
+            indications: false,
+        })
+    );
+    assert_eq!(heart_rate.value.as_ref().unwrap().bytes(), Some(vec![72]));
+    assert_eq!(heart_rate.descriptors.len(), 3);
+}
+
+#[tokio::test]
+async fn capture_records_failed_reads() {
+    let peripheral = watch().with_reads(
+        CURRENT_TIME_UUID,
+        vec![Err(String::from("insufficient encryption"))],
+    );
+
+    let dump = capture(&peripheral, true).await;
+
+    let current_time = dump
+        .services
+        .iter()
+        .flat_map(|s| &s.characteristics)
+        .find(|c| c.uuid == CURRENT_TIME_UUID)
+        .unwrap();
+    assert!(matches!(current_time.value, Some(Reading::Error(_))));
+}
+
+#[tokio::test]
+async fn capture_reads_values_only_when_asked() {
+    let dump = capture(&watch(), false).await;
+
+    assert!(dump
+        .services
+        .iter()
+        .flat_map(|s| &s.characteristics)
+        .all(|c| c.value.is_none()));
+}
+
+#[tokio::test]
+async fn diff_lists_what_a_firmware_update_changed() {
+    let old = capture(&watch(), false).await;
+    let new = capture(&with_heart_rate(watch()), false).await;
+
+    let diff = diff::compare(&old, &new);
+
+    assert_eq!(
+        diff.changes,
+        [Change::ServiceAdded {
+            service: HEART_RATE_SERVICE_UUID
+        }]
+    );
+    assert!(diff::compare(&new, &new).is_empty());
+}
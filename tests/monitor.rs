@@ -0,0 +1,88 @@
+mod common;
+
+use btleplug::api::BDAddr;
+use chrono::{TimeZone, Utc};
+use common::{headphones, watch, HUAMI_ADVERT};
+use smartwatch::assigned::HUAMI_COMPANY_ID;
+use smartwatch::backend::fake::{FakeAdapter, FakePeripheral};
+use smartwatch::clock::FixedClock;
+use smartwatch::monitor::{self, AdvertKind, AdvertUpdate, ByteChange};
+use smartwatch::selector::DeviceSelector;
+use std::time::Duration;
+use tokio::time;
+
+#[tokio::test(start_paused = true)]
+async fn watch_reports_the_bytes_that_changed() {
+    let mut counter = HUAMI_ADVERT;
+    counter[1] = 0x01;
+    let adapter = FakeAdapter::new()
+        .with_peripheral(watch().with_manufacturer_advert(
+            Duration::from_secs(1),
+            HUAMI_COMPANY_ID,
+            &counter,
+        ))
+        .with_peripheral(headphones().advertising_after(Duration::from_secs(2)));
+    let selector = DeviceSelector {
+        manufacturer_id: Some(HUAMI_COMPANY_ID),
+        ..DeviceSelector::default()
+    };
+    let clock = FixedClock(Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap());
+
+    let mut updates: Vec<AdvertUpdate> = Vec::new();
+    monitor::watch(
+        &adapter,
+        &selector,
+        &clock,
+        time::sleep(Duration::from_secs(5)),
+        |update| {
+            updates.push(update);
+            Ok(())
+        },
+    )
+    .await
+    .unwrap();
+
+    let kinds: Vec<AdvertKind> = updates.iter().map(|u| u.kind).collect();
+    assert_eq!(
+        kinds,
+        [AdvertKind::Discovered, AdvertKind::ManufacturerData]
+    );
+    assert_eq!(updates[0].manufacturer_data[0].changes, None);
+    assert_eq!(
+        updates[1].manufacturer_data[0].changes,
+        Some(vec![ByteChange {
+            offset: 1,
+            old: Some(0x00),
+            new: Some(0x01),
+        }])
+    );
+    assert!(!adapter.is_scanning());
+}
+
+#[tokio::test(start_paused = true)]
+async fn watch_carries_on_past_devices_that_vanish() {
+    let gone = FakePeripheral::new(BDAddr::from([0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F]))
+        .with_manufacturer_data(HUAMI_COMPANY_ID, &HUAMI_ADVERT);
+    let adapter = FakeAdapter::new()
+        .with_vanishing_peripheral(gone)
+        .with_peripheral(watch().advertising_after(Duration::from_secs(1)));
+    let clock = FixedClock(Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap());
+
+    let mut updates: Vec<AdvertUpdate> = Vec::new();
+    monitor::watch(
+        &adapter,
+        &DeviceSelector::default(),
+        &clock,
+        time::sleep(Duration::from_secs(5)),
+        |update| {
+            updates.push(update);
+            Ok(())
+        },
+    )
+    .await
+    .unwrap();
+
+    let names: Vec<Option<&str>> = updates.iter().map(|u| u.name.as_deref()).collect();
+    assert_eq!(names, [Some("Amazfit GTS 4 Mini")]);
+    assert!(!adapter.is_scanning());
+}
@@ -0,0 +1,170 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use smartwatch::drift::{DriftRecord, Phase};
+use smartwatch::schedule::{self, SchedulePolicy};
+
+const WATCH: &str = "C0:FF:EE:00:00:01";
+
+fn policy() -> SchedulePolicy {
+    SchedulePolicy {
+        error_bound: Duration::milliseconds(500),
+        min_interval: Duration::hours(1),
+        max_interval: Duration::days(7),
+    }
+}
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap()
+}
+
+fn record(device: &str, at: DateTime<Utc>, phase: Phase, offset_ms: i64) -> DriftRecord {
+    DriftRecord {
+        measured_at: at,
+        device: device.to_string(),
+        phase,
+        offset_ms,
+        round_trip_ms: 40,
+    }
+}
+
+/// A sync, a day on its own drifting by `drift_ms`, then a sync leaving
+/// `residual_ms` behind. Returns the records and the time of the last sync.
+fn history(drift_ms: i64, residual_ms: i64) -> (Vec<DriftRecord>, DateTime<Utc>) {
+    let next_day = start() + Duration::days(1);
+    let last_sync = next_day + Duration::minutes(1);
+    let records = vec![
+        record(WATCH, start(), Phase::AfterSync, 0),
+        record(WATCH, next_day, Phase::BeforeSync, drift_ms),
+        record(WATCH, last_sync, Phase::AfterSync, residual_ms),
+    ];
+    (records, last_sync)
+}
+
+#[test]
+fn plans_when_the_error_bound_is_reached() {
+    let cases = [
+        // 500 ms at 100 ms/day is 5 days, 4 days with the safety margin.
+        (100, 0, Duration::days(4)),
+        // Running slow uses the error budget just as fast.
+        (-100, 0, Duration::days(4)),
+        // 500 ms at 250 ms/day is 2 days, 1.6 days with the safety margin.
+        (250, 0, Duration::hours(38) + Duration::minutes(24)),
+        // What is left after the sync shrinks the budget to 400 ms.
+        (100, 100, Duration::hours(76) + Duration::minutes(48)),
+        (100, -100, Duration::hours(76) + Duration::minutes(48)),
+    ];
+    for (drift_ms, residual_ms, interval) in cases {
+        let (records, last_sync) = history(drift_ms, residual_ms);
+        let plan = schedule::next_sync(&records, WATCH, &policy()).unwrap();
+        assert_eq!(plan.at, last_sync + interval, "{} ms/day", drift_ms);
+        assert_eq!(plan.drift_per_day_ms, Some(drift_ms as f64));
+    }
+}
+
+#[test]
+fn clamps_to_the_interval_bounds() {
+    let cases = [
+        // 57.6 minutes would be due, the minimum interval wins.
+        (10_000, 0, Duration::hours(1)),
+        // 40 days would be due, the maximum interval wins.
+        (10, 0, Duration::days(7)),
+        // The sync already left the watch beyond the bound.
+        (100, 600, Duration::hours(1)),
+        // A watch that does not drift is synced as rarely as allowed.
+        (0, 0, Duration::days(7)),
+    ];
+    for (drift_ms, residual_ms, interval) in cases {
+        let (records, last_sync) = history(drift_ms, residual_ms);
+        let plan = schedule::next_sync(&records, WATCH, &policy()).unwrap();
+        assert_eq!(plan.at, last_sync + interval, "{} ms/day", drift_ms);
+    }
+}
+
+#[test]
+fn uses_the_minimum_interval_without_an_estimate() {
+    let records = vec![record(WATCH, start(), Phase::AfterSync, 0)];
+    let plan = schedule::next_sync(&records, WATCH, &policy()).unwrap();
+    assert_eq!(plan.at, start() + Duration::hours(1));
+    assert_eq!(plan.drift_per_day_ms, None);
+}
+
+#[test]
+fn ignores_other_devices_and_unsynced_ones() {
+    let (mut records, last_sync) = history(100, 0);
+    records.push(record(
+        "12:34:56:78:9A:BC",
+        last_sync + Duration::hours(2),
+        Phase::AfterSync,
+        0,
+    ));
+    let plan = schedule::next_sync(&records, WATCH, &policy()).unwrap();
+    assert_eq!(plan.at, last_sync + Duration::days(4));
+
+    let before_only = vec![record(WATCH, start(), Phase::BeforeSync, 250)];
+    assert!(schedule::next_sync(&before_only, WATCH, &policy()).is_none());
+    assert!(schedule::next_sync(&records, "AA:BB:CC:DD:EE:FF", &policy()).is_none());
+}
+
+#[test]
+fn checks_the_policy_bounds() {
+    assert!(SchedulePolicy::default().check().is_ok());
+    let cases = [
+        (
+            SchedulePolicy {
+                min_interval: Duration::hours(10),
+                max_interval: Duration::hours(1),
+                ..policy()
+            },
+            "exceeds the maximum",
+        ),
+        (
+            SchedulePolicy {
+                error_bound: Duration::zero(),
+                ..policy()
+            },
+            "error bound must be positive",
+        ),
+        (
+            SchedulePolicy {
+                min_interval: Duration::minutes(-5),
+                ..policy()
+            },
+            "minimum interval must be positive",
+        ),
+        (
+            SchedulePolicy {
+                max_interval: schedule::LONGEST_BOUND + Duration::days(1),
+                ..policy()
+            },
+            "maximum interval must not exceed",
+        ),
+    ];
+    for (policy, expected) in cases {
+        let message = policy.check().unwrap_err();
+        assert!(message.contains(expected), "{}", message);
+    }
+}
+
+#[test]
+fn retries_a_failed_sync_after_the_minimum_interval() {
+    let (mut records, last_sync) = history(100, 0);
+    let failed_at = last_sync + Duration::days(4);
+    records.push(DriftRecord::failure(WATCH, failed_at));
+    let plan = schedule::next_sync(&records, WATCH, &policy()).unwrap();
+    assert_eq!(plan.at, failed_at + Duration::hours(1));
+    // The failure leaves the drift estimate alone.
+    assert_eq!(plan.drift_per_day_ms, Some(100.0));
+
+    // A watch that never synced is retried as well.
+    let never_synced = vec![DriftRecord::failure(WATCH, start())];
+    let plan = schedule::next_sync(&never_synced, WATCH, &policy()).unwrap();
+    assert_eq!(plan.at, start() + Duration::hours(1));
+}
+
+#[test]
+fn a_later_sync_supersedes_a_failure() {
+    let (mut records, last_sync) = history(100, 0);
+    records.insert(1, DriftRecord::failure(WATCH, start() + Duration::hours(2)));
+    let plan = schedule::next_sync(&records, WATCH, &policy()).unwrap();
+    assert_eq!(plan.at, last_sync + Duration::days(4));
+    assert_eq!(plan.drift_per_day_ms, Some(100.0));
+}
@@ -0,0 +1,187 @@
+mod common;
+
+use btleplug::api::BDAddr;
+use common::{headphones, watch, CURRENT_TIME_SERVICE_UUID, HEART_RATE_SERVICE_UUID};
+use regex::Regex;
+use smartwatch::assigned::HUAMI_COMPANY_ID;
+use smartwatch::backend::fake::FakePeripheral;
+use smartwatch::backend::Peripheral;
+use smartwatch::discovery::Discovered;
+use smartwatch::profile::KNOWN_PROFILES;
+use smartwatch::selector::{self, DeviceSelector, MatchPolicy};
+
+async fn discovered(peripheral: FakePeripheral) -> Discovered<FakePeripheral> {
+    let properties = peripheral.properties().await.unwrap().unwrap();
+    Discovered {
+        local_name: properties.local_name.clone().unwrap_or_default(),
+        peripheral,
+        properties,
+    }
+}
+
+/// The watch, the headphones and a nameless device with no signal reading.
+async fn devices() -> Vec<Discovered<FakePeripheral>> {
+    let beacon = FakePeripheral::new(BDAddr::from([0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F]));
+    vec![
+        discovered(watch()).await,
+        discovered(headphones().with_advertised_service(HEART_RATE_SERVICE_UUID)).await,
+        discovered(beacon).await,
+    ]
+}
+
+fn names(devices: &[Discovered<FakePeripheral>]) -> Vec<&str> {
+    devices.iter().map(|d| d.local_name.as_str()).collect()
+}
+
+#[tokio::test]
+async fn matches_each_criterion_and_their_combinations() {
+    let devices = devices().await;
+    let cases = [
+        (
+            DeviceSelector::default(),
+            vec!["Amazfit GTS 4 Mini", "Headphones", ""],
+        ),
+        (
+            DeviceSelector {
+                address: Some(String::from("c0:ff:ee:00:00:01")),
+                ..DeviceSelector::default()
+            },
+            vec!["Amazfit GTS 4 Mini"],
+        ),
+        (
+            DeviceSelector {
+                name: Some(Regex::new("phones$").unwrap()),
+                ..DeviceSelector::default()
+            },
+            vec!["Headphones"],
+        ),
+        (
+            DeviceSelector {
+                services: vec![CURRENT_TIME_SERVICE_UUID],
+                ..DeviceSelector::default()
+            },
+            vec!["Amazfit GTS 4 Mini"],
+        ),
+        (
+            DeviceSelector {
+                manufacturer_id: Some(HUAMI_COMPANY_ID),
+                ..DeviceSelector::default()
+            },
+            vec!["Amazfit GTS 4 Mini"],
+        ),
+        // The beacon has no RSSI and cannot be shown to be close enough.
+        (
+            DeviceSelector {
+                min_rssi: Some(-90),
+                ..DeviceSelector::default()
+            },
+            vec!["Amazfit GTS 4 Mini", "Headphones"],
+        ),
+        (
+            DeviceSelector {
+                min_rssi: Some(-70),
+                ..DeviceSelector::default()
+            },
+            vec!["Amazfit GTS 4 Mini"],
+        ),
+        // Every criterion must hold.
+        (
+            DeviceSelector {
+                name: Some(Regex::new("^Amazfit").unwrap()),
+                services: vec![CURRENT_TIME_SERVICE_UUID, HEART_RATE_SERVICE_UUID],
+                ..DeviceSelector::default()
+            },
+            vec![],
+        ),
+        (
+            DeviceSelector {
+                address: Some(String::from("C0:FF:EE:00:00:01")),
+                manufacturer_id: Some(HUAMI_COMPANY_ID),
+                min_rssi: Some(-60),
+                ..DeviceSelector::default()
+            },
+            vec!["Amazfit GTS 4 Mini"],
+        ),
+        (
+            DeviceSelector {
+                address: Some(String::from("C0:FF:EE:00:00:01")),
+                min_rssi: Some(-59),
+                ..DeviceSelector::default()
+            },
+            vec![],
+        ),
+        (
+            DeviceSelector::for_profiles(KNOWN_PROFILES),
+            vec!["Amazfit GTS 4 Mini"],
+        ),
+    ];
+    for (selector, expected) in cases {
+        let matching: Vec<&str> = devices
+            .iter()
+            .filter(|device| selector.matches(device))
+            .map(|device| device.local_name.as_str())
+            .collect();
+        assert_eq!(matching, expected, "{:?}", selector);
+    }
+}
+
+#[test]
+fn empty_selector_has_no_criterion() {
+    assert!(DeviceSelector::default().is_empty());
+    assert!(!DeviceSelector::for_profiles(KNOWN_PROFILES).is_empty());
+    let selector = DeviceSelector {
+        min_rssi: Some(-70),
+        ..DeviceSelector::default()
+    };
+    assert!(!selector.is_empty());
+}
+
+#[tokio::test]
+async fn pick_policies() {
+    let never = |_: &[Discovered<FakePeripheral>]| -> Option<usize> { panic!("nobody to ask") };
+    let all = selector::choose(devices().await, MatchPolicy::All, never);
+    assert_eq!(names(&all), ["Amazfit GTS 4 Mini", "Headphones", ""]);
+
+    let first = selector::choose(devices().await, MatchPolicy::First, never);
+    assert_eq!(names(&first), ["Amazfit GTS 4 Mini"]);
+
+    // The device without a signal reading counts as the weakest.
+    let mut reversed = devices().await;
+    reversed.reverse();
+    let strongest = selector::choose(reversed, MatchPolicy::Strongest, never);
+    assert_eq!(names(&strongest), ["Amazfit GTS 4 Mini"]);
+
+    let none = selector::choose(Vec::new(), MatchPolicy::Strongest, never);
+    assert!(none.is_empty());
+}
+
+#[tokio::test]
+async fn ask_policy() {
+    let asked = selector::choose(devices().await, MatchPolicy::Ask, |devices| {
+        assert_eq!(devices.len(), 3);
+        Some(1)
+    });
+    assert_eq!(names(&asked), ["Headphones"]);
+
+    let declined = selector::choose(devices().await, MatchPolicy::Ask, |_| None);
+    assert!(declined.is_empty());
+    let out_of_range = selector::choose(devices().await, MatchPolicy::Ask, |_| Some(3));
+    assert!(out_of_range.is_empty());
+
+    // A single match is taken without asking.
+    let single = vec![discovered(watch()).await];
+    let taken = selector::choose(single, MatchPolicy::Ask, |_| panic!("nobody to ask"));
+    assert_eq!(names(&taken), ["Amazfit GTS 4 Mini"]);
+}
+
+#[test]
+fn parses_policies_and_company_ids() {
+    assert_eq!(
+        "strongest".parse::<MatchPolicy>(),
+        Ok(MatchPolicy::Strongest)
+    );
+    assert!("loudest".parse::<MatchPolicy>().is_err());
+    assert_eq!(selector::parse_company_id("0x0157"), Ok(0x0157));
+    assert_eq!(selector::parse_company_id("343"), Ok(343));
+    assert!(selector::parse_company_id("0x1FFFF").is_err());
+}
@@ -0,0 +1,178 @@
+mod common;
+
+use btleplug::api::{bleuuid::uuid_from_u16, CharPropFlags, WriteType};
+use chrono::{NaiveDate, NaiveDateTime, TimeZone, Utc};
+use common::{watch_address, HEART_RATE_MEASUREMENT_UUID};
+use futures::stream::StreamExt;
+use regex::Regex;
+use smartwatch::assigned::Registry;
+use smartwatch::backend::sim::{SimManager, SimProfile, SimWatch};
+use smartwatch::backend::{Adapter, AdapterEvent, Manager, Peripheral};
+use smartwatch::clock::{FixedClock, MockClock};
+use smartwatch::connection;
+use smartwatch::discovery::{self, StopWhen};
+use smartwatch::gatt::cts::{AdjustReason, CurrentTime, TimeSource};
+use smartwatch::gatt::{CURRENT_TIME_UUID, LOCAL_TIME_INFORMATION_UUID};
+use smartwatch::profile::AMAZFIT_GTS_4_MINI;
+use smartwatch::selector::DeviceSelector;
+use smartwatch::sync::TimeSync;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+const BATTERY_LEVEL_UUID: Uuid = uuid_from_u16(0x2A19);
+const UART_RX_UUID: Uuid = Uuid::from_u128(0x6e400002_b5a3_f393_e0a9_e50e24dcca9e);
+const UART_TX_UUID: Uuid = Uuid::from_u128(0x6e400003_b5a3_f393_e0a9_e50e24dcca9e);
+
+fn profile() -> SimProfile {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("profiles/amazfit-gts-4-mini.toml");
+    SimProfile::load(&path).unwrap()
+}
+
+fn rome(hour: u32, min: u32, sec: u32, milli: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2026, 3, 1)
+        .unwrap()
+        .and_hms_milli_opt(hour, min, sec, milli)
+        .unwrap()
+}
+
+/// The example watch, its clock running off a host clock at 2026-03-01 12:00Z.
+fn simulation() -> (SimManager, Arc<MockClock>) {
+    let host = Arc::new(MockClock::new(
+        Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap(),
+    ));
+    let manager = SimManager::new(profile(), host.clone()).unwrap();
+    (manager, host)
+}
+
+async fn connected() -> (SimWatch, Arc<MockClock>) {
+    let (manager, host) = simulation();
+    let watch = manager.watch().clone();
+    assert!(connection::connect(&watch).await.unwrap());
+    (watch, host)
+}
+
+#[tokio::test(start_paused = true)]
+async fn the_watch_advertises_while_scanning() {
+    let (manager, _) = simulation();
+    let adapter = manager.adapters().await.unwrap().remove(0);
+    let selector = DeviceSelector {
+        name: Some(Regex::new("^Amazfit").unwrap()),
+        ..DeviceSelector::default()
+    };
+
+    let scan = discovery::find(
+        &adapter,
+        &selector,
+        Duration::from_secs(5),
+        StopWhen::FirstMatch,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(scan.devices.len(), 1);
+    assert_eq!(scan.devices[0].properties.address, watch_address());
+    assert!(scan.stopped_early);
+}
+
+#[tokio::test(start_paused = true)]
+async fn the_clock_starts_off_and_drifts() {
+    let (watch, host) = connected().await;
+    assert_eq!(watch.local_time(), rome(12, 59, 57, 0));
+
+    host.advance(chrono::Duration::hours(1));
+
+    // 25 ppm of an hour is 90 ms.
+    assert_eq!(watch.local_time(), rome(13, 59, 57, 90));
+    let characteristic = connection::require_characteristic(&watch, CURRENT_TIME_UUID).unwrap();
+    let time = CurrentTime::decode(&watch.read(&characteristic).await.unwrap()).unwrap();
+    assert_eq!(
+        time,
+        CurrentTime::from_datetime(&rome(13, 59, 57, 90), AdjustReason::NONE).unwrap()
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn sync_sets_the_watch_clock() {
+    let (watch, _) = connected().await;
+    let characteristic = connection::require_characteristic(&watch, CURRENT_TIME_UUID).unwrap();
+    let sync = TimeSync {
+        zone: chrono_tz::Europe::Rome,
+        clock: FixedClock(Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap()),
+        precise_probes: None,
+        dry_run: false,
+        reference_source: TimeSource::NetworkTimeProtocol,
+    };
+
+    sync.sync(&watch, &characteristic).await.unwrap();
+
+    assert_eq!(watch.local_time(), rome(13, 0, 0, 0));
+    let local_time_information =
+        connection::require_characteristic(&watch, LOCAL_TIME_INFORMATION_UUID).unwrap();
+    assert_eq!(watch.read(&local_time_information).await.unwrap(), [4, 0]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn subscribed_characteristics_are_notified_periodically() {
+    let (watch, _) = connected().await;
+    let heart_rate =
+        connection::require_characteristic(&watch, HEART_RATE_MEASUREMENT_UUID).unwrap();
+    let battery = connection::require_characteristic(&watch, BATTERY_LEVEL_UUID).unwrap();
+    let notifications = watch.notifications().await.unwrap();
+
+    watch.subscribe(&heart_rate).await.unwrap();
+    let pulses: Vec<Vec<u8>> = notifications
+        .take(3)
+        .map(|notification| notification.value)
+        .collect()
+        .await;
+    watch.unsubscribe(&heart_rate).await.unwrap();
+
+    assert_eq!(pulses, [[0x00, 69], [0x00, 70], [0x00, 71]]);
+    assert_eq!(watch.read(&battery).await.unwrap(), [80]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn the_uart_answers_and_drops_the_connection_on_request() {
+    let (manager, _) = simulation();
+    let adapter = manager.adapters().await.unwrap().remove(0);
+    let watch = manager.watch().clone();
+    connection::connect(&watch).await.unwrap();
+    let rx = connection::require_characteristic(&watch, UART_RX_UUID).unwrap();
+    let tx = connection::require_characteristic(&watch, UART_TX_UUID).unwrap();
+    let mut notifications = watch.notifications().await.unwrap();
+    let mut events = adapter.events().await.unwrap();
+    watch.subscribe(&tx).await.unwrap();
+
+    watch
+        .write(&rx, b"battery\n", WriteType::WithoutResponse)
+        .await
+        .unwrap();
+    assert_eq!(notifications.next().await.unwrap().value, b"80%\n");
+    watch
+        .write(&rx, b"hello\n", WriteType::WithoutResponse)
+        .await
+        .unwrap();
+    assert_eq!(notifications.next().await.unwrap().value, b"hello\n");
+
+    watch
+        .write(&rx, b"disconnect\n", WriteType::WithoutResponse)
+        .await
+        .unwrap();
+    assert!(!watch.is_connected().await.unwrap());
+    assert_eq!(
+        events.next().await,
+        Some(AdapterEvent::DeviceDisconnected(watch_address()))
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn the_profile_names_the_uart_characteristic_of_the_watch() {
+    let (watch, _) = connected().await;
+    let uuid = AMAZFIT_GTS_4_MINI.notify_characteristic.unwrap();
+    assert_eq!(uuid, UART_TX_UUID);
+    let characteristic = connection::find_characteristic(&watch, uuid).unwrap();
+    assert!(characteristic.properties.contains(CharPropFlags::NOTIFY));
+    assert_eq!(Registry::builtin().uuid_name(&uuid), Some("Nordic UART TX"));
+}
@@ -0,0 +1,175 @@
+mod common;
+
+use btleplug::api::Characteristic;
+use chrono::{DateTime, TimeZone, Utc};
+use common::{watch, CURRENT_TIME_VALUE};
+use smartwatch::backend::fake::FakePeripheral;
+use smartwatch::clock::FixedClock;
+use smartwatch::connection;
+use smartwatch::gatt::cts::TimeSource;
+use smartwatch::gatt::{
+    CURRENT_TIME_UUID, LOCAL_TIME_INFORMATION_UUID, REFERENCE_TIME_INFORMATION_UUID,
+};
+use smartwatch::sync::{TimeSync, WriteOutcome};
+use smartwatch::SmartwatchError;
+use std::time::Duration;
+
+/// 2026-03-01 13:00:00 in Rome, the time [`CURRENT_TIME_VALUE`] encodes.
+fn one_pm() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap()
+}
+
+fn time_sync(precise_probes: Option<usize>, dry_run: bool) -> TimeSync<FixedClock> {
+    TimeSync {
+        zone: chrono_tz::Europe::Rome,
+        clock: FixedClock(one_pm()),
+        precise_probes,
+        dry_run,
+        reference_source: TimeSource::NetworkTimeProtocol,
+    }
+}
+
+async fn current_time(peripheral: &FakePeripheral) -> Characteristic {
+    connection::connect(peripheral).await.unwrap();
+    connection::require_characteristic(peripheral, CURRENT_TIME_UUID).unwrap()
+}
+
+#[tokio::test]
+async fn set_current_time_writes_the_clock_in_the_watch_zone() {
+    let peripheral = watch().expect_write(CURRENT_TIME_UUID, Some(&CURRENT_TIME_VALUE));
+    let characteristic = current_time(&peripheral).await;
+
+    let report = time_sync(None, false)
+        .set_current_time(&peripheral, &characteristic)
+        .await
+        .unwrap();
+
+    peripheral.verify().unwrap();
+    assert_eq!(report.payload, CURRENT_TIME_VALUE);
+    assert!(matches!(report.outcome, WriteOutcome::Written { .. }));
+}
+
+#[tokio::test]
+async fn set_current_time_fails_when_the_write_fails() {
+    let peripheral = watch().failing_writes(CURRENT_TIME_UUID, "write not permitted");
+    let characteristic = current_time(&peripheral).await;
+
+    let result = time_sync(None, false)
+        .set_current_time(&peripheral, &characteristic)
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(SmartwatchError::Write { characteristic, .. }) if characteristic == CURRENT_TIME_UUID
+    ));
+}
+
+#[tokio::test]
+async fn dry_run_writes_nothing() {
+    let peripheral = watch();
+    let characteristic = current_time(&peripheral).await;
+
+    let reports = time_sync(None, true)
+        .sync(&peripheral, &characteristic)
+        .await
+        .unwrap();
+
+    assert!(peripheral.writes().is_empty());
+    assert!(matches!(reports[0].outcome, WriteOutcome::DryRun));
+    assert_eq!(reports[0].payload, CURRENT_TIME_VALUE);
+}
+
+#[tokio::test]
+async fn sync_sets_every_time_service_the_watch_exposes() {
+    let peripheral = watch()
+        .expect_write(CURRENT_TIME_UUID, Some(&CURRENT_TIME_VALUE))
+        .expect_write(LOCAL_TIME_INFORMATION_UUID, Some(&[4, 0]))
+        .expect_write(REFERENCE_TIME_INFORMATION_UUID, None);
+    let characteristic = current_time(&peripheral).await;
+
+    let reports = time_sync(None, false)
+        .sync(&peripheral, &characteristic)
+        .await
+        .unwrap();
+
+    peripheral.verify().unwrap();
+    let names: Vec<&str> = reports.iter().map(|r| r.name).collect();
+    assert_eq!(
+        names,
+        [
+            "current time",
+            "local time information",
+            "reference time information",
+            "time with DST"
+        ]
+    );
+    assert!(matches!(reports[3].outcome, WriteOutcome::NotExposed));
+}
+
+// Liberia kept UTC-00:44:30 until 1972, which Local Time Information cannot
+// express in 15 minute steps.
+#[tokio::test]
+async fn sync_carries_on_when_an_optional_value_cannot_be_encoded() {
+    let peripheral = watch();
+    let characteristic = current_time(&peripheral).await;
+    let sync = TimeSync {
+        zone: chrono_tz::Africa::Monrovia,
+        clock: FixedClock(Utc.with_ymd_and_hms(1960, 6, 1, 12, 0, 0).unwrap()),
+        ..time_sync(None, false)
+    };
+
+    let reports = sync.sync(&peripheral, &characteristic).await.unwrap();
+
+    assert!(matches!(reports[0].outcome, WriteOutcome::Written { .. }));
+    assert!(matches!(
+        &reports[1].outcome,
+        WriteOutcome::Failed(SmartwatchError::Codec { characteristic, .. })
+            if *characteristic == Some(LOCAL_TIME_INFORMATION_UUID)
+    ));
+    assert!(reports[1].payload.is_empty());
+    assert!(matches!(reports[2].outcome, WriteOutcome::Written { .. }));
+    assert!(peripheral
+        .writes()
+        .iter()
+        .all(|write| write.characteristic != LOCAL_TIME_INFORMATION_UUID));
+}
+
+#[tokio::test]
+async fn sync_carries_on_when_an_optional_write_fails() {
+    let peripheral = watch().failing_writes(LOCAL_TIME_INFORMATION_UUID, "write not permitted");
+    let characteristic = current_time(&peripheral).await;
+
+    let reports = time_sync(None, false)
+        .sync(&peripheral, &characteristic)
+        .await
+        .unwrap();
+
+    assert!(matches!(reports[1].outcome, WriteOutcome::Failed(_)));
+    assert!(matches!(reports[2].outcome, WriteOutcome::Written { .. }));
+    assert_eq!(
+        peripheral.value(REFERENCE_TIME_INFORMATION_UUID),
+        Some(reports[2].payload.clone())
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn precise_sync_probes_the_round_trip_before_writing() {
+    let latency = Duration::from_millis(40);
+    let peripheral = watch().with_latency(latency);
+    let characteristic = current_time(&peripheral).await;
+
+    let (report, uncertainty) = time_sync(Some(3), false)
+        .write_current_time(&peripheral, &characteristic)
+        .await
+        .unwrap();
+
+    let writes = peripheral.writes();
+    assert_eq!(writes.len(), 4);
+    assert!(writes.iter().all(|w| w.characteristic == CURRENT_TIME_UUID));
+    assert!(matches!(
+        report.outcome,
+        WriteOutcome::Written { round_trip } if round_trip == latency
+    ));
+    assert_eq!(uncertainty, latency / 2);
+    assert_eq!(peripheral.value(CURRENT_TIME_UUID), Some(report.payload));
+}
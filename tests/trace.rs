@@ -0,0 +1,154 @@
+mod common;
+
+use btleplug::api::BDAddr;
+use chrono::{TimeZone, Utc};
+use common::{watch, CURRENT_TIME_VALUE};
+use futures::stream::StreamExt;
+use regex::Regex;
+use smartwatch::backend::fake::{FakeAdapter, FakeManager, FakePeripheral};
+use smartwatch::backend::record::{Recorder, RecordingManager};
+use smartwatch::backend::replay::ReplayManager;
+use smartwatch::backend::{Manager, Peripheral};
+use smartwatch::clock::FixedClock;
+use smartwatch::connection;
+use smartwatch::discovery::{self, StopWhen};
+use smartwatch::gatt::CURRENT_TIME_UUID;
+use smartwatch::selector::DeviceSelector;
+use std::fs;
+use std::io;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// What a session with the watch saw.
+#[derive(Debug, PartialEq)]
+struct Session {
+    address: BDAddr,
+    found_after: Duration,
+    current_time: Result<Vec<u8>, String>,
+    notifications: Vec<Vec<u8>>,
+    elapsed: Duration,
+}
+
+/// Finds the watch, reads its Current Time and waits for two notifications of it.
+async fn session<M: Manager>(manager: &M) -> Session {
+    let started = Instant::now();
+    let adapter = discovery::adapters(manager).await.unwrap().remove(0);
+    let selector = DeviceSelector {
+        name: Some(Regex::new("^Amazfit").unwrap()),
+        ..DeviceSelector::default()
+    };
+    let scan = discovery::find(
+        &adapter,
+        &selector,
+        Duration::from_secs(10),
+        StopWhen::FirstMatch,
+    )
+    .await
+    .unwrap();
+    let peripheral = &scan.devices[0].peripheral;
+    connection::connect(peripheral).await.unwrap();
+    let characteristic = connection::require_characteristic(peripheral, CURRENT_TIME_UUID).unwrap();
+    let current_time = peripheral
+        .read(&characteristic)
+        .await
+        .map_err(|err| err.to_string());
+    let notifications = peripheral.notifications().await.unwrap();
+    peripheral.subscribe(&characteristic).await.unwrap();
+    let notifications = notifications
+        .take(2)
+        .map(|notification| notification.value)
+        .collect()
+        .await;
+    Session {
+        address: peripheral.address(),
+        found_after: scan.elapsed,
+        current_time,
+        notifications,
+        elapsed: started.elapsed(),
+    }
+}
+
+/// Records a session with `peripheral`, then replays it.
+async fn record_and_replay(name: &str, peripheral: FakePeripheral) -> (Session, Session) {
+    let path =
+        std::env::temp_dir().join(format!("smartwatch-{}-{}.jsonl", name, std::process::id()));
+    let clock = FixedClock(Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap());
+    let recorder = Recorder::create(&path, &clock).unwrap();
+    let manager = FakeManager::new(vec![FakeAdapter::new().with_peripheral(peripheral)]);
+    let recorded = session(&RecordingManager::new(manager, recorder.clone())).await;
+    recorder.finish().unwrap();
+
+    let replayed = session(&ReplayManager::load(&path).unwrap()).await;
+    fs::remove_file(&path).unwrap();
+    (recorded, replayed)
+}
+
+fn notifying(peripheral: FakePeripheral) -> FakePeripheral {
+    let mut later = CURRENT_TIME_VALUE;
+    later[6] = 1;
+    peripheral.with_notifications(
+        CURRENT_TIME_UUID,
+        vec![
+            (Duration::from_secs(1), CURRENT_TIME_VALUE.to_vec()),
+            (Duration::from_secs(1), later.to_vec()),
+        ],
+    )
+}
+
+#[tokio::test(start_paused = true)]
+async fn replay_answers_and_times_like_the_recording() {
+    let peripheral = notifying(
+        watch()
+            .advertising_after(Duration::from_secs(2))
+            .with_latency(Duration::from_millis(40)),
+    );
+
+    let (recorded, replayed) = record_and_replay("session", peripheral).await;
+
+    assert_eq!(recorded.found_after, Duration::from_secs(2));
+    assert_eq!(recorded.current_time, Ok(CURRENT_TIME_VALUE.to_vec()));
+    assert_eq!(recorded.notifications.len(), 2);
+    assert_eq!(replayed, recorded);
+}
+
+#[tokio::test(start_paused = true)]
+async fn replay_fails_where_the_recording_failed() {
+    let peripheral = notifying(watch().with_reads(
+        CURRENT_TIME_UUID,
+        vec![Err(String::from("insufficient encryption"))],
+    ));
+
+    let (recorded, replayed) = record_and_replay("failure", peripheral).await;
+
+    assert!(recorded.current_time.is_err());
+    assert_eq!(replayed, recorded);
+}
+
+#[test]
+fn traces_of_another_version_are_rejected() {
+    let trace = concat!(
+        r#"{"format":"smartwatch-trace","version":2,"started_at":"2026-03-01T12:00:00Z"}"#,
+        "\n"
+    );
+
+    let err = ReplayManager::read(trace.as_bytes()).unwrap_err();
+
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("version 2"));
+}
+
+#[test]
+fn malformed_lines_are_reported_with_their_number() {
+    let trace = concat!(
+        r#"{"format":"smartwatch-trace","version":1,"started_at":"2026-03-01T12:00:00Z"}"#,
+        "\n",
+        r#"{"at_us":0,"took_us":0,"op":"adapters","result":{"ok":1}}"#,
+        "\n",
+        r#"{"at_us":5,"op":"teleport"}"#,
+        "\n"
+    );
+
+    let err = ReplayManager::read(trace.as_bytes()).unwrap_err();
+
+    assert!(err.to_string().starts_with("line 3:"));
+}
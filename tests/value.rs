@@ -0,0 +1,203 @@
+use smartwatch::gatt::descriptor::PresentationFormat;
+use smartwatch::gatt::value::{self, Quantity, Value, ValueType};
+use smartwatch::gatt::CodecError;
+
+fn decimal(mantissa: i64, exponent: i32) -> Value {
+    Value::Decimal { mantissa, exponent }
+}
+
+#[test]
+fn decodes_special_values() {
+    let cases: [(&[u8], ValueType, &str); 10] = [
+        (&[0xFF, 0x07], ValueType::SFloat, "NaN"),
+        (&[0x00, 0x08], ValueType::SFloat, "NRes"),
+        (&[0xFE, 0x07], ValueType::SFloat, "+INFINITY"),
+        (&[0x02, 0x08], ValueType::SFloat, "-INFINITY"),
+        // The exponent does not matter for reserved mantissas.
+        (&[0x01, 0xF8], ValueType::SFloat, "reserved"),
+        (&[0xFF, 0xFF, 0x7F, 0x00], ValueType::Float, "NaN"),
+        (&[0x00, 0x00, 0x80, 0x00], ValueType::Float, "NRes"),
+        (&[0xFE, 0xFF, 0x7F, 0x00], ValueType::Float, "+INFINITY"),
+        (&[0x02, 0x00, 0x80, 0x00], ValueType::Float, "-INFINITY"),
+        (&[0x01, 0x00, 0x80, 0x05], ValueType::Float, "reserved"),
+    ];
+    for (bytes, value_type, name) in cases {
+        assert_eq!(
+            value_type.decode(bytes),
+            Ok(Value::Special(name)),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn round_trips_ieee_11073_numbers() {
+    let cases: [(ValueType, &str, &[u8], Value, &str); 10] = [
+        (
+            ValueType::SFloat,
+            "36.6",
+            &[0x6E, 0xF1],
+            decimal(366, -1),
+            "36.6",
+        ),
+        (
+            ValueType::SFloat,
+            "-36.6",
+            &[0x92, 0xFE],
+            decimal(-366, -1),
+            "-36.6",
+        ),
+        (ValueType::SFloat, "0", &[0x00, 0x00], decimal(0, 0), "0"),
+        (
+            ValueType::SFloat,
+            "2045",
+            &[0xFD, 0x07],
+            decimal(2045, 0),
+            "2045",
+        ),
+        (
+            ValueType::SFloat,
+            "-2045",
+            &[0x03, 0x08],
+            decimal(-2045, 0),
+            "-2045",
+        ),
+        // Trailing zeros move into the exponent when the mantissa is too large.
+        (
+            ValueType::SFloat,
+            "-36.60",
+            &[0x92, 0xFE],
+            decimal(-366, -1),
+            "-36.6",
+        ),
+        (
+            ValueType::SFloat,
+            "20000000",
+            &[0xD0, 0x47],
+            decimal(2000, 4),
+            "20000000",
+        ),
+        (
+            ValueType::SFloat,
+            "20450000000",
+            &[0xFD, 0x77],
+            decimal(2045, 7),
+            "20450000000",
+        ),
+        (
+            ValueType::Float,
+            "98.6",
+            &[0xDA, 0x03, 0x00, 0xFF],
+            decimal(986, -1),
+            "98.6",
+        ),
+        (
+            ValueType::Float,
+            "-8388605",
+            &[0x03, 0x00, 0x80, 0x00],
+            decimal(-8_388_605, 0),
+            "-8388605",
+        ),
+    ];
+    for (value_type, text, bytes, value, shown) in cases {
+        assert_eq!(value_type.encode(text).as_deref(), Ok(bytes), "{}", text);
+        let decoded = value_type.decode(bytes).unwrap();
+        assert_eq!(decoded, value, "{}", text);
+        assert_eq!(decoded.to_string(), shown);
+    }
+}
+
+#[test]
+fn normalizes_exponents_below_the_minimum() {
+    // 10 × 10^-9 only fits as 1 × 10^-8.
+    let bytes = ValueType::SFloat.encode("0.000000010").unwrap();
+    assert_eq!(bytes, [0x01, 0x80]);
+    assert_eq!(ValueType::SFloat.decode(&bytes), Ok(decimal(1, -8)));
+
+    let tiny = format!("0.{}10", "0".repeat(127));
+    let bytes = ValueType::Float.encode(&tiny).unwrap();
+    assert_eq!(bytes, [0x01, 0x00, 0x00, 0x80]);
+    assert_eq!(ValueType::Float.decode(&bytes), Ok(decimal(1, -128)));
+
+    assert_eq!(
+        ValueType::SFloat.encode("0.0000000000"),
+        Ok(vec![0x00, 0x80])
+    );
+}
+
+#[test]
+fn rejects_numbers_that_do_not_fit() {
+    let cases = [
+        (ValueType::SFloat, "12345", "too many significant digits"),
+        (ValueType::SFloat, "2046", "too many significant digits"),
+        (ValueType::SFloat, "0.000000015", "out of range"),
+        (ValueType::SFloat, "1000000000000", "out of range"),
+        (ValueType::Float, "8388606", "too many significant digits"),
+        // The extremes of the parsed mantissa must not overflow.
+        (
+            ValueType::Float,
+            "-9223372036854775808",
+            "too many significant digits",
+        ),
+        (
+            ValueType::SFloat,
+            "-922337203685477580.8",
+            "too many significant digits",
+        ),
+        (
+            ValueType::SFloat,
+            "9223372036854775807",
+            "too many significant digits",
+        ),
+        (ValueType::SFloat, "1e5", "not a decimal number"),
+        (ValueType::SFloat, "1.-5", "not a decimal number"),
+    ];
+    for (value_type, text, expected) in cases {
+        let err = value_type.encode(text).unwrap_err();
+        assert!(err.contains(expected), "{}: {}", text, err);
+    }
+}
+
+#[test]
+fn decodes_by_presentation_format() {
+    // sfloat, exponent -1, degrees Celsius.
+    let format = PresentationFormat::decode(&[0x16, 0xFF, 0x2F, 0x27, 0x01, 0x00, 0x00]).unwrap();
+    assert_eq!(format.format, 0x16);
+    assert_eq!(format.exponent, -1);
+    assert_eq!(format.unit, 0x272F);
+    assert_eq!(
+        ValueType::from_presentation_format(&format),
+        Some(ValueType::SFloat)
+    );
+    let quantity = Quantity::decode(&format, &[0x6E, 0x01]).unwrap();
+    assert_eq!(quantity.value, decimal(366, -1));
+    assert_eq!(quantity.to_string(), "36.6 °C");
+
+    // uint16, exponent -2, unitless.
+    let format = PresentationFormat::decode(&[0x06, 0xFE, 0x00, 0x27, 0x01, 0x00, 0x00]).unwrap();
+    let quantity = Quantity::decode(&format, &2026u16.to_le_bytes()).unwrap();
+    assert_eq!(quantity.to_string(), "20.26");
+
+    // A format the tool cannot decode leaves the bytes alone.
+    let format = PresentationFormat::decode(&[0x1B, 0x00, 0xAD, 0x27, 0x01, 0x00, 0x00]).unwrap();
+    assert_eq!(
+        Quantity::decode(&format, &[1, 2]).unwrap().value,
+        Value::Bytes(vec![1, 2])
+    );
+
+    assert_eq!(
+        PresentationFormat::decode(&[0x16, 0xFF]),
+        Err(CodecError::Length {
+            expected: 7,
+            actual: 2
+        })
+    );
+}
+
+#[test]
+fn parses_typed_values_to_write() {
+    assert_eq!(value::parse_typed("u16le:2026"), Ok(vec![0xEA, 0x07]));
+    assert_eq!(value::parse_typed("sfloat:36.6"), Ok(vec![0x6E, 0xF1]));
+    assert_eq!(value::parse_typed("e8:07"), Ok(vec![0xE8, 0x07]));
+}
@@ -0,0 +1,86 @@
+use chrono::{NaiveDate, TimeZone, Utc};
+use smartwatch::gatt::cts::{DstOffset, TimeWithDst};
+use smartwatch::zone;
+
+#[test]
+fn finds_the_spring_change_in_rome() {
+    let after = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+    let (change, local) = zone::next_dst_change(&chrono_tz::Europe::Rome, &after).unwrap();
+    // Last Sunday of March, 02:00 CET becomes 03:00 CEST.
+    assert_eq!(change, Utc.with_ymd_and_hms(2026, 3, 29, 1, 0, 0).unwrap());
+    assert_eq!(
+        local,
+        NaiveDate::from_ymd_opt(2026, 3, 29)
+            .unwrap()
+            .and_hms_opt(2, 0, 0)
+            .unwrap()
+    );
+
+    let value = zone::time_with_dst(&chrono_tz::Europe::Rome, &after);
+    assert_eq!(value.dst_offset, DstOffset::Daylight);
+    assert_eq!(value.encode(), Ok([0xEA, 0x07, 3, 29, 2, 0, 0, 4]));
+}
+
+#[test]
+fn finds_the_autumn_change_in_rome() {
+    let after = Utc.with_ymd_and_hms(2026, 3, 29, 1, 0, 0).unwrap();
+    let value = zone::time_with_dst(&chrono_tz::Europe::Rome, &after);
+    // Last Sunday of October, 03:00 CEST becomes 02:00 CET.
+    assert_eq!(value.dst_offset, DstOffset::Standard);
+    assert_eq!(value.encode(), Ok([0xEA, 0x07, 10, 25, 3, 0, 0, 0]));
+}
+
+#[test]
+fn zone_without_dst_has_no_change() {
+    let after = Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap();
+    assert_eq!(zone::next_dst_change(&chrono_tz::Asia::Tokyo, &after), None);
+    assert_eq!(
+        zone::time_with_dst(&chrono_tz::Asia::Tokyo, &after),
+        TimeWithDst {
+            date_time: None,
+            dst_offset: DstOffset::Standard,
+        }
+    );
+}
+
+#[test]
+fn local_time_information_follows_dst() {
+    let rome = chrono_tz::Europe::Rome;
+    let winter = Utc.with_ymd_and_hms(2026, 1, 15, 12, 0, 0).unwrap();
+    let summer = Utc.with_ymd_and_hms(2026, 7, 15, 12, 0, 0).unwrap();
+    assert_eq!(
+        zone::local_time_information(&rome, &winter)
+            .unwrap()
+            .encode(),
+        Ok([4, 0])
+    );
+    assert_eq!(
+        zone::local_time_information(&rome, &summer)
+            .unwrap()
+            .encode(),
+        Ok([4, 4])
+    );
+}
+
+/// Irish Standard Time is the summer one, with a negative DST offset in winter.
+#[test]
+fn negative_dst_is_reported_as_unknown() {
+    let dublin = chrono_tz::Europe::Dublin;
+    let winter = Utc.with_ymd_and_hms(2026, 1, 15, 12, 0, 0).unwrap();
+    let summer = Utc.with_ymd_and_hms(2026, 7, 15, 12, 0, 0).unwrap();
+    assert_eq!(
+        zone::local_time_information(&dublin, &winter)
+            .unwrap()
+            .encode(),
+        Ok([0, 255])
+    );
+    assert_eq!(
+        zone::local_time_information(&dublin, &summer)
+            .unwrap()
+            .encode(),
+        Ok([4, 0])
+    );
+    let change = zone::time_with_dst(&dublin, &summer);
+    assert_eq!(change.dst_offset, DstOffset::Unknown);
+    assert_eq!(change.encode(), Ok([0xEA, 0x07, 10, 25, 2, 0, 0, 255]));
+}